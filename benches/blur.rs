@@ -0,0 +1,20 @@
+//! Blurring a 1080p frame for the payment background, which has to take
+//! under 5 ms in release to keep up with the camera.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use photo_booth_v2::camera_feed::blur;
+
+/// Standard deviation the payment background is blurred with.
+const SIGMA: f32 = 24.0;
+
+fn bench_blur(c: &mut Criterion) {
+    let frame = image::RgbaImage::from_fn(1920, 1080, |x, y| {
+        image::Rgba([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 255])
+    });
+    c.bench_function("gaussian 1080p", |b| {
+        b.iter(|| blur::gaussian(&frame, SIGMA))
+    });
+}
+
+criterion_group!(benches, bench_blur);
+criterion_main!(benches);
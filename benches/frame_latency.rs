@@ -0,0 +1,50 @@
+//! What the capture loop does with a 1080p MJPEG frame before it can be shown,
+//! with the options setup's preview uses: handing iced the JPEG as it came, or
+//! decoding it into a handle of pixels when the preview is mirrored. iced then
+//! decodes the JPEG itself the first time it draws it, which is timed on its
+//! own.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use photo_booth_v2::{
+    camera_feed::{video_frame_handle, CameraFeedOptions},
+    cameras::{Frame, VideoFrame},
+};
+
+/// A 1920x1080 frame at the quality a webcam's MJPEG stream is usually
+/// encoded at.
+const FRAME: &[u8] = include_bytes!("fixtures/frame_1080p.jpg");
+
+/// What setup's preview shrinks frames to.
+const SETUP_PREVIEW_MAX_DIMENSION: u32 = 320;
+
+fn bench_frame_latency(c: &mut Criterion) {
+    let jpeg = bytes::Bytes::from_static(FRAME);
+    let frame = VideoFrame::Jpeg(jpeg.clone());
+    let preview = CameraFeedOptions {
+        max_dimension: Some(SETUP_PREVIEW_MAX_DIMENSION),
+        ..Default::default()
+    };
+    let mirrored = CameraFeedOptions {
+        mirror: true,
+        ..preview
+    };
+
+    let mut group = c.benchmark_group("mjpeg 1080p");
+    group.bench_function("setup preview", |b| {
+        b.iter(|| video_frame_handle(frame.clone(), preview).unwrap())
+    });
+    group.bench_function("setup preview, mirrored", |b| {
+        b.iter(|| video_frame_handle(frame.clone(), mirrored).unwrap())
+    });
+    // what the capture loop did before frames were passed through
+    group.bench_function("decoded on capture", |b| {
+        b.iter(|| Frame::from(frame.clone().into_rgba().unwrap()).to_handle())
+    });
+    group.bench_function("decoded by iced when drawn", |b| {
+        b.iter(|| image::load_from_memory(&jpeg).unwrap().to_rgba8())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_frame_latency);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Rendering a strip from four camera-sized photos, as `render_take` does it
+//! now and as it did one photo at a time with Lanczos over the whole strip.
+//! `render_take`'s tests check the two come out the same within tolerance.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use photo_booth_v2::render_take::{
+    render_take, render_take_single_threaded, Caption, RenderAssets, StripTemplate,
+};
+
+/// A photo the size the camera takes them, with gradients and hard edges.
+fn synthetic_photo(seed: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(4000, 2667, |x, y| {
+        let square = ((x + seed * 50) / 40 + y / 40) % 2;
+        image::Rgba([
+            (x * 255 / 4000) as u8,
+            (y * 255 / 2667) as u8,
+            (square * 200 + seed * 10) as u8,
+            255,
+        ])
+    })
+}
+
+fn bench_render(c: &mut Criterion) {
+    let template = StripTemplate::bundled();
+    let assets = RenderAssets::load(&template).unwrap();
+    let photos: Vec<_> = (0..4).map(synthetic_photo).collect();
+    let caption = Caption {
+        event: "Winter Festival".to_string(),
+        date: "2025-01-01".to_string(),
+    };
+
+    let mut group = c.benchmark_group("render_take");
+    // each render takes around a second
+    group.sample_size(10);
+    group.bench_function("parallel", |b| {
+        b.iter(|| render_take(photos.clone(), &assets, Some(&caption)).unwrap())
+    });
+    group.bench_function("single_threaded", |b| {
+        b.iter(|| render_take_single_threaded(photos.clone(), &assets, Some(&caption)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_render);
+criterion_main!(benches);
@@ -1,3 +1,8 @@
+pub mod audio;
 pub mod cameras;
+pub mod config;
+pub mod filters;
+pub mod printing;
+pub mod queue;
 pub mod render_take;
 pub mod servers;
@@ -0,0 +1,96 @@
+/// A bundled sound effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    /// Played as each number of the countdown comes up.
+    CountdownBeep,
+    /// Played as each photo is taken.
+    Shutter,
+}
+
+/// Plays sound effects without blocking the caller. If there's no audio
+/// device, or the crate was built without the `audio` feature, it stays
+/// silent instead.
+#[derive(Debug)]
+pub struct SoundPlayer {
+    /// `None` if the player was made silent.
+    #[cfg(feature = "audio")]
+    sender: Option<std::sync::mpsc::Sender<Sound>>,
+}
+
+#[cfg(feature = "audio")]
+impl Sound {
+    fn clip(self) -> &'static [u8] {
+        match self {
+            Self::CountdownBeep => include_bytes!("../../assets/sounds/countdown_beep.wav"),
+            Self::Shutter => include_bytes!("../../assets/sounds/shutter.wav"),
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl SoundPlayer {
+    pub fn new() -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel::<Sound>();
+        // the output stream can't leave the thread it was opened on, so it
+        // gets a thread of its own that lives as long as the player
+        std::thread::spawn(move || {
+            let (_stream, handle) = match rodio::OutputStream::try_default() {
+                Ok(output) => output,
+                Err(err) => {
+                    log::warn!("Failed to open an audio output, staying silent: {}", err);
+                    // drain so senders never notice
+                    for _ in receiver {}
+                    return;
+                }
+            };
+            for sound in receiver {
+                let source = match rodio::Decoder::new(std::io::Cursor::new(sound.clip())) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        log::warn!("Failed to decode {:?}: {}", sound, err);
+                        continue;
+                    }
+                };
+                if let Err(err) = handle.play_raw(rodio::Source::convert_samples::<f32>(source)) {
+                    log::warn!("Failed to play {:?}: {}", sound, err);
+                }
+            }
+        });
+        Self {
+            sender: Some(sender),
+        }
+    }
+
+    /// A player that never plays anything, for when sound is turned off.
+    pub fn silent() -> Self {
+        Self { sender: None }
+    }
+
+    pub fn play(&self, sound: Sound) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if sender.send(sound).is_err() {
+            log::warn!("The audio thread has stopped, not playing {:?}", sound);
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+impl SoundPlayer {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn silent() -> Self {
+        Self {}
+    }
+
+    pub fn play(&self, _sound: Sound) {}
+}
+
+impl Default for SoundPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,9 +1,210 @@
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    time::{Duration, Instant},
+};
 
+#[cfg(feature = "camera_file")]
+pub mod file;
 #[cfg(feature = "camera_gphoto2")]
 pub mod gphoto2;
+#[cfg(feature = "camera_mock")]
+pub mod mock;
 #[cfg(feature = "camera_nokhwa")]
 pub mod nokhwa;
+#[cfg(feature = "camera_v4l2")]
+pub mod v4l2;
+
+/// A capture resolution and frame rate offered by a camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct CameraFormat {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: u32,
+}
+
+impl Display for CameraFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} @ {} fps",
+            self.width, self.height, self.frame_rate
+        )
+    }
+}
+
+/// A camera setting that can be adjusted without reopening the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum ControlId {
+    Brightness,
+    Exposure,
+    Gain,
+}
+
+impl ControlId {
+    pub const ALL: [ControlId; 3] = [Self::Brightness, Self::Exposure, Self::Gain];
+}
+
+impl Display for ControlId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Brightness => write!(f, "Brightness"),
+            Self::Exposure => write!(f, "Exposure"),
+            Self::Gain => write!(f, "Gain"),
+        }
+    }
+}
+
+/// Values are in whatever unit the camera uses, so they only make sense
+/// between the control's `min` and `max`.
+pub type ControlValue = f32;
+
+/// A control the camera supports, with its range and current value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraControl {
+    pub id: ControlId,
+    pub min: ControlValue,
+    pub max: ControlValue,
+    pub step: ControlValue,
+    pub value: ControlValue,
+}
+
+/// Readings from cameras that run on batteries and memory cards, so staff can
+/// swap them before they run out mid-event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CameraStatus {
+    /// Charge from 0 to 100, if the camera reports it as a percentage.
+    pub battery_percent: Option<u8>,
+    /// Photos that still fit on the camera's storage.
+    pub remaining_shots: Option<u32>,
+}
+
+/// Why a camera couldn't be opened, as far as the backend can tell, so setup
+/// can say how to fix it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CameraOpenFailure {
+    /// The OS hasn't given the app access to the camera.
+    PermissionDenied,
+    /// Another app, like a video call, has the camera open.
+    DeviceBusy,
+    /// The camera was unplugged since it was listed.
+    NotFound,
+    Other(String),
+}
+
+impl CameraOpenFailure {
+    /// Guesses from an error message, for backends whose errors only pass on
+    /// what the OS said. Covers the wording of Linux, macOS and Windows.
+    pub fn from_message(message: &str) -> Self {
+        let lowercase = message.to_lowercase();
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| lowercase.contains(needle));
+        if mentions(&[
+            "permission denied",
+            "permissiondenied",
+            "access denied",
+            "access is denied",
+            "not authorized",
+            "os error 13",
+            "0x80070005",
+        ]) {
+            Self::PermissionDenied
+        } else if mentions(&[
+            "busy",
+            "in use",
+            "os error 16",
+            "0xc00d3704",
+            "0xc00d3e85",
+            "could not claim",
+            "could not lock",
+        ]) {
+            Self::DeviceBusy
+        } else if mentions(&[
+            "no such file",
+            "no such device",
+            "not found",
+            "notfound",
+            "os error 2)",
+            "os error 19",
+            "could not find",
+        ]) {
+            Self::NotFound
+        } else {
+            Self::Other(message.to_string())
+        }
+    }
+}
+
+impl Display for CameraOpenFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PermissionDenied => write!(f, "the app isn't allowed to use the camera"),
+            Self::DeviceBusy => write!(f, "another app is using the camera"),
+            Self::NotFound => write!(f, "the camera isn't connected"),
+            Self::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// A decoded RGBA image whose pixels are shared, so turning it into a widget
+/// handle or passing it around doesn't copy the buffer.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    pixels: bytes::Bytes,
+}
+
+impl Frame {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn to_handle(&self) -> iced::widget::image::Handle {
+        iced::widget::image::Handle::from_rgba(self.width, self.height, self.pixels.clone())
+    }
+
+    /// Copies the pixels into an owned image, for code that needs to modify or
+    /// keep its own copy.
+    pub fn to_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(self.width, self.height, self.pixels.to_vec())
+            .expect("frame buffer matches its dimensions")
+    }
+}
+
+impl From<image::RgbaImage> for Frame {
+    fn from(image: image::RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw().into(),
+        }
+    }
+}
+
+/// A video frame the way the camera delivered it.
+#[derive(Debug, Clone)]
+pub enum VideoFrame {
+    Rgba(image::RgbaImage),
+    /// Still JPEG encoded, as MJPEG cameras send it. Left for iced to decode
+    /// when it's drawn if nothing needs the pixels before then.
+    Jpeg(bytes::Bytes),
+}
+
+impl VideoFrame {
+    pub fn into_rgba(self) -> Result<image::RgbaImage, image::ImageError> {
+        match self {
+            Self::Rgba(image) => Ok(image),
+            Self::Jpeg(data) => Ok(image::load_from_memory_with_format(
+                &data,
+                image::ImageFormat::Jpeg,
+            )?
+            .to_rgba8()),
+        }
+    }
+}
 
 pub trait CameraBackend: Clone {
     type Error: Debug + Send;
@@ -14,24 +215,255 @@ pub trait CameraBackend: Clone {
         Ok(())
     }
     fn enumerate_cameras() -> Result<Vec<Self::EnumeratedCamera>, Self::Error>;
-    fn open_camera(item: Self::EnumeratedCamera) -> Result<Self::Camera, Self::Error>;
+    /// Lists the formats `camera` can be opened with. Backends that can't
+    /// choose a format return an empty list.
+    fn supported_formats(
+        _camera: &Self::EnumeratedCamera,
+    ) -> Result<Vec<CameraFormat>, Self::Error> {
+        Ok(Vec::new())
+    }
+    /// Opens `item`. If `format` is given, it is used for both video and still
+    /// captures; otherwise the backend picks its own.
+    fn open_camera(
+        item: Self::EnumeratedCamera,
+        format: Option<CameraFormat>,
+    ) -> Result<Self::Camera, Self::Error>;
+    /// Works out why `open_camera` failed. Guesses from the error's debug
+    /// output by default.
+    fn classify_open_error(err: &Self::Error) -> CameraOpenFailure {
+        CameraOpenFailure::from_message(&format!("{:?}", err))
+    }
 }
 
 pub trait CameraBackendCamera: Send {
     type Error: Debug + Send + Clone;
 
+    /// Whether the `_async` capture methods do their own waiting without
+    /// blocking, so they can be awaited directly. Otherwise the blocking
+    /// methods are run on a blocking thread.
+    const NATIVE_ASYNC: bool = false;
+
     fn capture_video_frame(&mut self) -> Result<image::RgbaImage, Self::Error>;
+    /// Like `capture_video_frame`, but may leave the frame encoded, for when
+    /// nothing needs its pixels. Decodes it like `capture_video_frame` by
+    /// default. Not used for `NATIVE_ASYNC` cameras.
+    fn capture_video_frame_encoded(&mut self) -> Result<VideoFrame, Self::Error> {
+        self.capture_video_frame().map(VideoFrame::Rgba)
+    }
+    /// Only awaited directly if `NATIVE_ASYNC` is set. Calls the blocking
+    /// version by default.
+    fn capture_video_frame_async(
+        &mut self,
+    ) -> impl std::future::Future<Output = Result<image::RgbaImage, Self::Error>> + Send {
+        async move { self.capture_video_frame() }
+    }
+    /// Takes a photo. Backends that open a separate stream for stills discard
+    /// the first few frames of it (see `set_warmup_frames`) so the sensor has
+    /// adjusted its exposure by the time the photo is taken.
     fn capture_still_frame(&mut self) -> Result<image::RgbaImage, Self::Error>;
+    /// Only awaited directly if `NATIVE_ASYNC` is set. Calls the blocking
+    /// version by default.
+    fn capture_still_frame_async(
+        &mut self,
+    ) -> impl std::future::Future<Output = Result<image::RgbaImage, Self::Error>> + Send {
+        async move { self.capture_still_frame() }
+    }
+    /// Takes `count` photos, starting one every `interval` or as soon as the
+    /// last one is done if that takes longer. Takes them one at a time by
+    /// default; backends with a faster burst mode can override it. Not used
+    /// for `NATIVE_ASYNC` cameras.
+    fn capture_still_burst(
+        &mut self,
+        count: usize,
+        interval: Duration,
+    ) -> Result<Vec<image::RgbaImage>, Self::Error> {
+        let mut photos = Vec::with_capacity(count);
+        for taken in 1..=count {
+            let started = Instant::now();
+            photos.push(self.capture_still_frame()?);
+            if taken < count {
+                std::thread::sleep(interval.saturating_sub(started.elapsed()));
+            }
+        }
+        Ok(photos)
+    }
+    /// Called shortly before `capture_still_frame` so slow work like focusing
+    /// or warming up the still stream can overlap with the countdown. Does
+    /// nothing by default.
+    fn prepare_still_capture(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Sets how many frames are discarded after opening a still stream.
+    /// Ignored by backends that don't need a warm-up.
+    fn set_warmup_frames(&mut self, _frames: u32) {}
+    /// Whether the device is still attached, checked after a capture fails
+    /// to tell a hiccup from a pulled cable. Assumes it is by default.
+    fn is_connected(&mut self) -> bool {
+        true
+    }
+    /// Fixes the exposure at `ev` stops from what the camera's auto exposure
+    /// would pick, so every photo in a strip matches. Does nothing by default.
+    fn set_exposure(&mut self, _ev: f32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Fixes the white balance at a color temperature in kelvin. Does nothing
+    /// by default.
+    fn set_white_balance(&mut self, _kelvin: u32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Holds the focus where it is, so it doesn't hunt during a countdown, or
+    /// hands it back to autofocus. Does nothing by default.
+    fn set_focus_locked(&mut self, _locked: bool) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// The controls this camera supports. Unsupported controls are left out,
+    /// so by default there are none.
+    fn controls(&mut self) -> Vec<CameraControl> {
+        Vec::new()
+    }
+    /// Sets one of the controls listed by `controls`, keeping it across
+    /// reopens. A value the camera rejects is returned as an error and not
+    /// kept. Does nothing by default.
+    fn set_control(&mut self, _id: ControlId, _value: ControlValue) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Battery and storage readings, or `None` for cameras without either,
+    /// which is the default.
+    fn status(&mut self) -> Result<Option<CameraStatus>, Self::Error> {
+        Ok(None)
+    }
 }
 
 #[cfg(all(feature = "camera_nokhwa", feature = "camera_gphoto2"))]
 compile_error!(
     "feature \"camera_nokhwa\" and feature \"camera_gphoto2\" cannot be enabled at the same time"
 );
-#[cfg(not(any(feature = "camera_nokhwa", feature = "camera_gphoto2")))]
-compile_error!("one of feature \"camera_nokhwa\" and feature \"camera_gphoto2\" should be enabled");
+#[cfg(all(feature = "camera_nokhwa", feature = "camera_v4l2"))]
+compile_error!(
+    "feature \"camera_nokhwa\" and feature \"camera_v4l2\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_gphoto2", feature = "camera_v4l2"))]
+compile_error!(
+    "feature \"camera_gphoto2\" and feature \"camera_v4l2\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_nokhwa", feature = "camera_mock"))]
+compile_error!(
+    "feature \"camera_nokhwa\" and feature \"camera_mock\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_gphoto2", feature = "camera_mock"))]
+compile_error!(
+    "feature \"camera_gphoto2\" and feature \"camera_mock\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_v4l2", feature = "camera_mock"))]
+compile_error!(
+    "feature \"camera_v4l2\" and feature \"camera_mock\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_nokhwa", feature = "camera_file"))]
+compile_error!(
+    "feature \"camera_nokhwa\" and feature \"camera_file\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_gphoto2", feature = "camera_file"))]
+compile_error!(
+    "feature \"camera_gphoto2\" and feature \"camera_file\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_v4l2", feature = "camera_file"))]
+compile_error!(
+    "feature \"camera_v4l2\" and feature \"camera_file\" cannot be enabled at the same time"
+);
+#[cfg(all(feature = "camera_mock", feature = "camera_file"))]
+compile_error!(
+    "feature \"camera_mock\" and feature \"camera_file\" cannot be enabled at the same time"
+);
+#[cfg(not(any(
+    feature = "camera_nokhwa",
+    feature = "camera_gphoto2",
+    feature = "camera_v4l2",
+    feature = "camera_mock",
+    feature = "camera_file"
+)))]
+compile_error!(
+    "one of feature \"camera_nokhwa\", feature \"camera_gphoto2\", feature \"camera_v4l2\", feature \"camera_mock\" and feature \"camera_file\" should be enabled"
+);
 
 #[cfg(feature = "camera_gphoto2")]
 pub type DefaultCameraBackend = gphoto2::GPhoto2Backend;
 #[cfg(feature = "camera_nokhwa")]
 pub type DefaultCameraBackend = nokhwa::NokhwaBackend;
+#[cfg(feature = "camera_v4l2")]
+pub type DefaultCameraBackend = v4l2::V4l2Backend;
+#[cfg(feature = "camera_mock")]
+pub type DefaultCameraBackend = mock::MockCameraBackend;
+#[cfg(feature = "camera_file")]
+pub type DefaultCameraBackend = file::FileBackend;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_all(messages: &[&str], expected: CameraOpenFailure) {
+        for message in messages {
+            assert_eq!(
+                CameraOpenFailure::from_message(message),
+                expected,
+                "{:?}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn permission_denied_is_recognized() {
+        assert_all(
+            &[
+                "Could not open device /dev/video0: Permission denied (os error 13)",
+                "OpenDeviceError(\"0\", \"PermissionDenied\")",
+                "Failed to open camera: Access is denied. (0x80070005)",
+                "AVCaptureDevice: not authorized to capture video",
+            ],
+            CameraOpenFailure::PermissionDenied,
+        );
+    }
+
+    #[test]
+    fn busy_camera_is_recognized() {
+        assert_all(
+            &[
+                "Device or resource busy (os error 16)",
+                "The camera is in use by another application",
+                "MF_E_HW_MFT_FAILED_START_STREAMING (0xC00D3704)",
+                "Could not claim the USB device",
+                "PTP I/O error: could not lock the device",
+            ],
+            CameraOpenFailure::DeviceBusy,
+        );
+    }
+
+    #[test]
+    fn missing_camera_is_recognized() {
+        assert_all(
+            &[
+                "Could not open /dev/video2: No such file or directory (os error 2)",
+                "No such device (os error 19)",
+                "Camera not found",
+                "Could not find the requested camera",
+            ],
+            CameraOpenFailure::NotFound,
+        );
+    }
+
+    #[test]
+    fn anything_else_keeps_its_message() {
+        for message in [
+            "Unsupported pixel format YUYV",
+            "Timed out (os error 110)",
+            // only "os error 2" itself means not found
+            "Protocol error (os error 22)",
+            "",
+        ] {
+            assert_eq!(
+                CameraOpenFailure::from_message(message),
+                CameraOpenFailure::Other(message.to_string())
+            );
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use image::RgbaImage;
+
+/// Directory whose subdirectories are offered as cameras, overridable with
+/// the `CAMERA_FILE_DIR` environment variable.
+const DEFAULT_ROOT: &str = "test_cameras";
+/// Roughly what a webcam would give us, so the capture loop doesn't spin.
+const VIDEO_FRAME_TIME: Duration = Duration::from_millis(33);
+/// A camera directory may contain an image with this file stem to be returned
+/// for every still. Otherwise stills take the next frame in the cycle.
+const STILL_STEM: &str = "still";
+
+#[derive(Debug, Clone, Copy)]
+pub struct FileBackend {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCameraDescriptor {
+    path: PathBuf,
+}
+
+impl Display for FileCameraDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Images in {}", self.path.display())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileError(String);
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<std::io::Error> for FileError {
+    fn from(value: std::io::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<image::ImageError> for FileError {
+    fn from(value: image::ImageError) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl super::CameraBackend for FileBackend {
+    type Error = FileError;
+    type EnumeratedCamera = FileCameraDescriptor;
+    type Camera = FileCamera;
+
+    fn enumerate_cameras() -> Result<Vec<FileCameraDescriptor>, FileError> {
+        let root = std::env::var("CAMERA_FILE_DIR").unwrap_or_else(|_| DEFAULT_ROOT.to_string());
+        let mut cameras = Vec::new();
+        for entry in std::fs::read_dir(&root)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                cameras.push(FileCameraDescriptor { path: entry.path() });
+            }
+        }
+        cameras.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(cameras)
+    }
+
+    fn open_camera(
+        item: Self::EnumeratedCamera,
+        _format: Option<super::CameraFormat>,
+    ) -> Result<FileCamera, Self::Error> {
+        FileCamera::open(&item.path)
+    }
+}
+
+pub struct FileCamera {
+    frames: Vec<PathBuf>,
+    still: Option<RgbaImage>,
+    next_frame: usize,
+}
+
+impl FileCamera {
+    fn open(path: &Path) -> Result<Self, FileError> {
+        let mut frames = Vec::new();
+        let mut still = None;
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if image::ImageFormat::from_path(&path).is_err() {
+                continue;
+            }
+            if path.file_stem().is_some_and(|stem| stem == STILL_STEM) {
+                still = Some(image::open(&path)?.to_rgba8());
+            } else {
+                frames.push(path);
+            }
+        }
+        if frames.is_empty() {
+            return Err(FileError(format!("no images in {}", path.display())));
+        }
+        frames.sort();
+        Ok(FileCamera {
+            frames,
+            still,
+            next_frame: 0,
+        })
+    }
+
+    fn next_frame(&mut self) -> Result<RgbaImage, FileError> {
+        let path = &self.frames[self.next_frame];
+        self.next_frame = (self.next_frame + 1) % self.frames.len();
+        Ok(image::open(path)?.to_rgba8())
+    }
+}
+
+impl super::CameraBackendCamera for FileCamera {
+    type Error = FileError;
+
+    fn capture_still_frame(&mut self) -> Result<RgbaImage, FileError> {
+        match &self.still {
+            Some(still) => Ok(still.clone()),
+            None => self.next_frame(),
+        }
+    }
+
+    fn capture_video_frame(&mut self) -> Result<RgbaImage, FileError> {
+        std::thread::sleep(VIDEO_FRAME_TIME);
+        self.next_frame()
+    }
+}
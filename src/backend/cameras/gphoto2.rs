@@ -1,6 +1,30 @@
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use gphoto2::{list::CameraDescriptor, Camera, Context};
+use gphoto2::{
+    camera::CameraEvent,
+    list::CameraDescriptor,
+    widget::{RadioWidget, TextWidget, ToggleWidget},
+    Camera, Context,
+};
+use tokio::sync::watch;
+
+use super::{CameraControl, CameraOpenFailure, CameraStatus, ControlId, ControlValue};
+
+/// How long to wait for the lens to report that it has focused.
+const AUTOFOCUS_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long to wait for the liveview thread to deliver a frame.
+const LIVEVIEW_FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the liveview thread logs the frame rate it's getting.
+const LIVEVIEW_FPS_LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// How often a blocking wait for a liveview frame checks for one.
+const LIVEVIEW_POLL_INTERVAL: Duration = Duration::from_millis(5);
 
 #[derive(Debug, Clone, Copy)]
 pub struct GPhoto2Backend {}
@@ -31,21 +55,144 @@ impl super::CameraBackend for GPhoto2Backend {
             .collect())
     }
 
-    fn open_camera(item: Self::EnumeratedCamera) -> Result<GPhoto2Camera, Self::Error> {
+    fn open_camera(
+        item: Self::EnumeratedCamera,
+        _format: Option<super::CameraFormat>,
+    ) -> Result<GPhoto2Camera, Self::Error> {
         let context = gphoto2::context::Context::new()?;
         let camera = context.get_camera(&item.0).wait()?;
-        Ok(GPhoto2Camera::new(camera, context))
+        Ok(GPhoto2Camera::new(camera, context, item.0.port))
+    }
+
+    fn classify_open_error(err: &gphoto2::Error) -> CameraOpenFailure {
+        let message = err.to_string();
+        // on Linux the desktop's file manager often grabs the camera as soon
+        // as it's plugged in, which libgphoto2 reports as a failed claim
+        if message.contains("Could not claim the USB device") {
+            CameraOpenFailure::DeviceBusy
+        } else if message.contains("Unknown model") {
+            CameraOpenFailure::NotFound
+        } else {
+            CameraOpenFailure::from_message(&message)
+        }
     }
 }
 
 pub struct GPhoto2Camera {
-    camera: Camera,
+    /// Shared with the liveview thread. gphoto2 runs every call on its own
+    /// task thread, so using the camera from two threads is safe.
+    camera: Arc<Camera>,
     context: Context,
+    /// USB port the camera was found on, to check it's still plugged in.
+    port: String,
+    /// Started by the first video frame and stopped for stills.
+    liveview: Option<LiveView>,
+    /// Cleared if the liveview fails before its first frame, after which
+    /// every video frame is requested separately instead.
+    liveview_supported: bool,
+    /// Whether the liveview has ever delivered a frame.
+    liveview_worked: bool,
 }
 
 impl GPhoto2Camera {
-    pub fn new(camera: Camera, context: Context) -> Self {
-        GPhoto2Camera { camera, context }
+    pub fn new(camera: Camera, context: Context, port: String) -> Self {
+        GPhoto2Camera {
+            camera: Arc::new(camera),
+            context,
+            port,
+            liveview: None,
+            liveview_supported: true,
+            liveview_worked: false,
+        }
+    }
+
+    fn liveview(&mut self) -> &mut LiveView {
+        self.liveview
+            .get_or_insert_with(|| LiveView::start(self.camera.clone(), self.context.clone()))
+    }
+
+    /// Takes the liveview out if `frame` shows it has stopped, so the next
+    /// video frame starts it again. The caller drops it, which waits for the
+    /// thread to finish.
+    fn liveview_failed(
+        &mut self,
+        frame: &Result<image::RgbaImage, GPhoto2StringError>,
+    ) -> Option<LiveView> {
+        match frame {
+            Ok(_) => {
+                self.liveview_worked = true;
+                None
+            }
+            Err(err) => {
+                if !self.liveview_worked {
+                    log::warn!(
+                        "Camera doesn't support liveview, requesting each frame instead: {}",
+                        err
+                    );
+                    self.liveview_supported = false;
+                }
+                self.liveview.take()
+            }
+        }
+    }
+
+    /// Stops the liveview so it doesn't get in the way of a still.
+    async fn stop_liveview(&mut self) {
+        if let Some(liveview) = self.liveview.take() {
+            // joining waits for the preview in flight, so do it off the runtime
+            let _ = tokio::task::spawn_blocking(move || drop(liveview)).await;
+        }
+    }
+
+    /// Sets the radio widget `key` to whichever of its numeric choices is
+    /// closest to `value`, since cameras only accept the exact strings they
+    /// list (e.g. "+0.3" or "5200").
+    fn set_closest_choice(&self, key: &str, value: f32) -> Result<(), GPhoto2StringError> {
+        let widget = self.camera.config_key::<RadioWidget>(key).wait()?;
+        let choice = widget
+            .choices_iter()
+            .filter_map(|choice| Some((choice.trim().parse::<f32>().ok()?, choice)))
+            .min_by(|(a, _), (b, _)| (a - value).abs().total_cmp(&(b - value).abs()))
+            .map(|(_, choice)| choice)
+            .ok_or_else(|| GPhoto2StringError(format!("{} has no numeric choices", key)))?;
+        widget.set_choice(&choice)?;
+        self.camera.set_config(&widget).wait()?;
+        Ok(())
+    }
+
+    /// Describes the radio widget `key` as a control ranging over its numeric
+    /// choices, or `None` if the camera doesn't have it.
+    fn radio_control(&self, id: ControlId, key: &str) -> Option<CameraControl> {
+        let widget = self.camera.config_key::<RadioWidget>(key).wait().ok()?;
+        let mut choices = widget
+            .choices_iter()
+            .filter_map(|choice| choice.trim().parse::<f32>().ok())
+            .collect::<Vec<_>>();
+        choices.sort_by(f32::total_cmp);
+        let (&min, &max) = (choices.first()?, choices.last()?);
+        let step = choices
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .filter(|gap| *gap > 0.0)
+            .min_by(f32::total_cmp)
+            .unwrap_or(1.0);
+        Some(CameraControl {
+            id,
+            min,
+            max,
+            step,
+            value: widget.choice().trim().parse().unwrap_or(min),
+        })
+    }
+}
+
+/// The config widget behind each control. Brightness only exists on the
+/// computer side of a DSLR, so it isn't offered.
+fn control_key(id: ControlId) -> Option<&'static str> {
+    match id {
+        ControlId::Brightness => None,
+        ControlId::Exposure => Some("exposurecompensation"),
+        ControlId::Gain => Some("iso"),
     }
 }
 
@@ -67,7 +214,50 @@ impl From<gphoto2::Error> for GPhoto2StringError {
 impl super::CameraBackendCamera for GPhoto2Camera {
     type Error = GPhoto2StringError;
 
+    const NATIVE_ASYNC: bool = true;
+
+    fn capture_still_frame_async(
+        &mut self,
+    ) -> impl std::future::Future<Output = Result<image::RgbaImage, GPhoto2StringError>> + Send
+    {
+        async move {
+            self.stop_liveview().await;
+            let path = self.camera.capture_image().await?;
+            let fs = self.camera.fs();
+            let data = fs
+                .download(&path.folder(), &path.name())
+                .await?
+                .get_data(&self.context)
+                .await?;
+            decode(data).await
+        }
+    }
+
+    fn capture_video_frame_async(
+        &mut self,
+    ) -> impl std::future::Future<Output = Result<image::RgbaImage, GPhoto2StringError>> + Send
+    {
+        async move {
+            if !self.liveview_supported {
+                let data = self
+                    .camera
+                    .capture_preview()
+                    .await?
+                    .get_data(&self.context)
+                    .await?;
+                return decode(data).await;
+            }
+            let frame = self.liveview().next_frame().await;
+            if let Some(liveview) = self.liveview_failed(&frame) {
+                let _ = tokio::task::spawn_blocking(move || drop(liveview)).await;
+            }
+            frame
+        }
+    }
+
     fn capture_still_frame(&mut self) -> Result<image::RgbaImage, GPhoto2StringError> {
+        // dropping the liveview stops it so it doesn't get in the way
+        self.liveview = None;
         let path = self.camera.capture_image().wait()?;
         let fs = self.camera.fs();
         let img = image::load_from_memory(
@@ -80,16 +270,266 @@ impl super::CameraBackendCamera for GPhoto2Camera {
         Ok(img.to_rgba8())
     }
 
+    fn prepare_still_capture(&mut self) -> Result<(), GPhoto2StringError> {
+        let autofocus = self
+            .camera
+            .config_key::<ToggleWidget>("autofocusdrive")
+            .wait()?;
+        autofocus.set_toggled(true);
+        self.camera.set_config(&autofocus).wait()?;
+
+        // Canon bodies report focus results as unknown events, so drain events
+        // until one mentions focus or we give up
+        let deadline = Instant::now() + AUTOFOCUS_TIMEOUT;
+        let mut focused = false;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match self.camera.wait_event(remaining).wait()? {
+                CameraEvent::Timeout => break,
+                CameraEvent::Unknown(event) if event.to_lowercase().contains("focus") => {
+                    log::debug!("Autofocus event: {}", event);
+                    focused = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        // release the drive so the next trigger starts a new focus
+        autofocus.set_toggled(false);
+        self.camera.set_config(&autofocus).wait()?;
+
+        if focused {
+            Ok(())
+        } else {
+            Err(GPhoto2StringError(format!(
+                "no focus event within {:?}",
+                AUTOFOCUS_TIMEOUT
+            )))
+        }
+    }
+
+    fn set_exposure(&mut self, ev: f32) -> Result<(), GPhoto2StringError> {
+        self.set_closest_choice("exposurecompensation", ev)
+    }
+
+    fn set_white_balance(&mut self, kelvin: u32) -> Result<(), GPhoto2StringError> {
+        // the temperature is only used in the color temperature white balance mode
+        let white_balance = self
+            .camera
+            .config_key::<RadioWidget>("whitebalance")
+            .wait()?;
+        if let Some(mode) = white_balance
+            .choices_iter()
+            .find(|choice| choice.to_lowercase().contains("temperature"))
+        {
+            white_balance.set_choice(&mode)?;
+            self.camera.set_config(&white_balance).wait()?;
+        }
+        self.set_closest_choice("colortemperature", kelvin as f32)
+    }
+
+    fn controls(&mut self) -> Vec<CameraControl> {
+        ControlId::ALL
+            .into_iter()
+            .filter_map(|id| self.radio_control(id, control_key(id)?))
+            .collect()
+    }
+
+    fn set_control(
+        &mut self,
+        id: ControlId,
+        value: ControlValue,
+    ) -> Result<(), GPhoto2StringError> {
+        let key =
+            control_key(id).ok_or_else(|| GPhoto2StringError(format!("{} isn't supported", id)))?;
+        self.set_closest_choice(key, value)
+    }
+
+    fn status(&mut self) -> Result<Option<CameraStatus>, GPhoto2StringError> {
+        // not every body has this widget, and some only say "Full" or "Low"
+        let battery_percent = self
+            .camera
+            .config_key::<TextWidget>("batterylevel")
+            .wait()
+            .ok()
+            .and_then(|widget| widget.value().trim().trim_end_matches('%').parse().ok());
+        let remaining_shots = self
+            .camera
+            .storages()
+            .wait()?
+            .iter()
+            .filter_map(|storage| storage.free_images())
+            .map(|shots| shots as u32)
+            .reduce(u32::saturating_add);
+        Ok(Some(CameraStatus {
+            battery_percent,
+            remaining_shots,
+        }))
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.context
+            .list_cameras()
+            .wait()
+            .map(|mut cameras| cameras.any(|camera| camera.port == self.port))
+            .unwrap_or(false)
+    }
+
     fn capture_video_frame(&mut self) -> Result<image::RgbaImage, GPhoto2StringError> {
-        let img = image::load_from_memory(
-            &self
-                .camera
-                .capture_preview()
-                .wait()?
-                .get_data(&self.context)
-                .wait()?,
-        )
-        .map_err(|err| gphoto2::Error::new(-1, Some(err.to_string())))?;
-        Ok(img.to_rgba8())
+        if !self.liveview_supported {
+            return read_preview(&self.camera, &self.context);
+        }
+        let frame = self.liveview().next_frame_blocking();
+        drop(self.liveview_failed(&frame));
+        frame
+    }
+}
+
+type LatestFrame = Option<Result<image::RgbaImage, GPhoto2StringError>>;
+
+/// Reads preview frames on a thread of its own, as fast as the camera sends
+/// them, so a video frame only has to wait for the next one to arrive instead
+/// of a whole USB round trip. Stops when dropped.
+struct LiveView {
+    frames: watch::Receiver<LatestFrame>,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl LiveView {
+    fn start(camera: Arc<Camera>, context: Context) -> Self {
+        let (sender, frames) = watch::channel(None);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::Builder::new()
+            .name("gphoto2 liveview".to_string())
+            .spawn({
+                let stop = stop.clone();
+                move || run_liveview(&camera, &context, &sender, &stop)
+            })
+            .expect("failed to spawn liveview thread");
+        Self {
+            frames,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// The first frame newer than the last one returned.
+    async fn next_frame(&mut self) -> Result<image::RgbaImage, GPhoto2StringError> {
+        tokio::time::timeout(LIVEVIEW_FRAME_TIMEOUT, self.frames.changed())
+            .await
+            .map_err(|_| {
+                GPhoto2StringError(format!(
+                    "no liveview frame within {:?}",
+                    LIVEVIEW_FRAME_TIMEOUT
+                ))
+            })?
+            .map_err(|_| GPhoto2StringError("liveview stopped".to_string()))?;
+        self.latest()
+    }
+
+    fn next_frame_blocking(&mut self) -> Result<image::RgbaImage, GPhoto2StringError> {
+        let deadline = Instant::now() + LIVEVIEW_FRAME_TIMEOUT;
+        loop {
+            match self.frames.has_changed() {
+                Ok(true) => return self.latest(),
+                Ok(false) if Instant::now() < deadline => {
+                    std::thread::sleep(LIVEVIEW_POLL_INTERVAL)
+                }
+                Ok(false) => {
+                    return Err(GPhoto2StringError(format!(
+                        "no liveview frame within {:?}",
+                        LIVEVIEW_FRAME_TIMEOUT
+                    )))
+                }
+                // the thread may have sent an error just before it stopped
+                Err(_) => return self.latest(),
+            }
+        }
+    }
+
+    fn latest(&mut self) -> Result<image::RgbaImage, GPhoto2StringError> {
+        self.frames
+            .borrow_and_update()
+            .clone()
+            .unwrap_or_else(|| Err(GPhoto2StringError("liveview stopped".to_string())))
     }
 }
+
+impl Drop for LiveView {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Body of the liveview thread. Holds the viewfinder up so the camera doesn't
+/// work the mirror for every frame, and sends frames until it's stopped,
+/// nobody is listening anymore, or a frame fails.
+fn run_liveview(
+    camera: &Camera,
+    context: &Context,
+    sender: &watch::Sender<LatestFrame>,
+    stop: &AtomicBool,
+) {
+    // only some bodies have this, the rest manage the mirror themselves
+    let viewfinder = camera.config_key::<ToggleWidget>("viewfinder").wait().ok();
+    if let Some(viewfinder) = &viewfinder {
+        viewfinder.set_toggled(true);
+        if let Err(err) = camera.set_config(viewfinder).wait() {
+            log::warn!("Failed to raise the viewfinder: {}", err);
+        }
+    }
+
+    let mut frames = 0u32;
+    let mut since = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        let frame = read_preview(camera, context);
+        let failed = frame.is_err();
+        if sender.send(Some(frame)).is_err() || failed {
+            break;
+        }
+        frames += 1;
+        let elapsed = since.elapsed();
+        if elapsed >= LIVEVIEW_FPS_LOG_INTERVAL {
+            log::info!(
+                "Liveview running at {:.1} fps",
+                frames as f32 / elapsed.as_secs_f32()
+            );
+            frames = 0;
+            since = Instant::now();
+        }
+    }
+
+    if let Some(viewfinder) = &viewfinder {
+        viewfinder.set_toggled(false);
+        if let Err(err) = camera.set_config(viewfinder).wait() {
+            log::warn!("Failed to lower the viewfinder: {}", err);
+        }
+    }
+}
+
+/// Requests a single preview frame and decodes it.
+fn read_preview(
+    camera: &Camera,
+    context: &Context,
+) -> Result<image::RgbaImage, GPhoto2StringError> {
+    let data = camera.capture_preview().wait()?.get_data(context).wait()?;
+    image::load_from_memory(&data)
+        .map(|img| img.to_rgba8())
+        .map_err(|err| GPhoto2StringError(err.to_string()))
+}
+
+/// Decodes a downloaded image on a blocking thread, since full size DSLR
+/// JPEGs take a while.
+async fn decode(data: Box<[u8]>) -> Result<image::RgbaImage, GPhoto2StringError> {
+    tokio::task::spawn_blocking(move || {
+        image::load_from_memory(&data)
+            .map(|img| img.to_rgba8())
+            .map_err(|err| GPhoto2StringError(err.to_string()))
+    })
+    .await
+    .map_err(|err| GPhoto2StringError(err.to_string()))?
+}
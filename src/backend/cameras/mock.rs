@@ -0,0 +1,157 @@
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use image::{Rgba, RgbaImage};
+
+use super::VideoFrame;
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1280;
+
+/// Roughly what a webcam would give us, so the capture loop doesn't spin.
+const VIDEO_FRAME_TIME: Duration = Duration::from_millis(33);
+/// Roughly how long the DSLR takes to fire and download a still.
+const STILL_FRAME_TIME: Duration = Duration::from_millis(500);
+/// What a webcam's MJPEG stream is usually encoded at.
+const JPEG_QUALITY: u8 = 85;
+
+/// SMPTE-ish color bars: white, yellow, cyan, green, magenta, red, blue
+const BARS: [[u8; 3]; 7] = [
+    [192, 192, 192],
+    [192, 192, 0],
+    [0, 192, 192],
+    [0, 192, 0],
+    [192, 0, 192],
+    [192, 0, 0],
+    [0, 0, 192],
+];
+
+/// 3x5 bitmaps for the digits 0-9, one bit per pixel, row-major from the
+/// top-left with the most significant bit first.
+const DIGITS: [u16; 10] = [
+    0b111_101_101_101_111,
+    0b010_110_010_010_111,
+    0b111_001_111_100_111,
+    0b111_001_111_001_111,
+    0b101_101_111_001_001,
+    0b111_100_111_001_111,
+    0b111_100_111_101_111,
+    0b111_001_001_001_001,
+    0b111_101_111_101_111,
+    0b111_101_111_001_111,
+];
+const DIGIT_SCALE: u32 = 24;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MockCameraBackend {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCameraDescriptor;
+
+impl Display for MockCameraDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mock camera ({}x{} test pattern)", WIDTH, HEIGHT)
+    }
+}
+
+impl super::CameraBackend for MockCameraBackend {
+    type Error = Infallible;
+    type EnumeratedCamera = MockCameraDescriptor;
+    type Camera = MockCamera;
+
+    fn enumerate_cameras() -> Result<Vec<MockCameraDescriptor>, Infallible> {
+        Ok(vec![MockCameraDescriptor])
+    }
+
+    fn open_camera(
+        _item: Self::EnumeratedCamera,
+        _format: Option<super::CameraFormat>,
+    ) -> Result<MockCamera, Self::Error> {
+        Ok(MockCamera::default())
+    }
+}
+
+#[derive(Default)]
+pub struct MockCamera {
+    frame_count: u64,
+}
+
+impl MockCamera {
+    fn next_frame(&mut self) -> RgbaImage {
+        self.frame_count += 1;
+        test_pattern(self.frame_count)
+    }
+}
+
+impl super::CameraBackendCamera for MockCamera {
+    type Error = Infallible;
+
+    fn capture_still_frame(&mut self) -> Result<RgbaImage, Infallible> {
+        std::thread::sleep(STILL_FRAME_TIME);
+        Ok(self.next_frame())
+    }
+
+    fn capture_video_frame(&mut self) -> Result<RgbaImage, Infallible> {
+        std::thread::sleep(VIDEO_FRAME_TIME);
+        Ok(self.next_frame())
+    }
+
+    /// Encodes the frame as JPEG, the way an MJPEG webcam sends it.
+    fn capture_video_frame_encoded(&mut self) -> Result<VideoFrame, Infallible> {
+        let started = Instant::now();
+        let frame = image::DynamicImage::ImageRgba8(self.next_frame()).into_rgb8();
+        let mut encoded = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY)
+            .encode_image(&frame)
+            .expect("encoding to memory doesn't fail");
+        std::thread::sleep(VIDEO_FRAME_TIME.saturating_sub(started.elapsed()));
+        Ok(VideoFrame::Jpeg(encoded.into()))
+    }
+}
+
+/// Color bars over the top two thirds, a gradient scrolling with `frame`
+/// below them, and the frame number drawn on top.
+fn test_pattern(frame: u64) -> RgbaImage {
+    let bars_height = HEIGHT * 2 / 3;
+    let bar_width = WIDTH.div_ceil(BARS.len() as u32);
+    let offset = (frame * 8 % WIDTH as u64) as u32;
+
+    let mut image = RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        if y < bars_height {
+            let [r, g, b] = BARS[(x / bar_width) as usize];
+            Rgba([r, g, b, 255])
+        } else {
+            let value = ((x + offset) % WIDTH * 255 / WIDTH) as u8;
+            Rgba([value, value, 255 - value, 255])
+        }
+    });
+
+    draw_number(&mut image, frame, DIGIT_SCALE, bars_height + DIGIT_SCALE);
+    image
+}
+
+fn draw_number(image: &mut RgbaImage, number: u64, left: u32, top: u32) {
+    for (i, digit) in number.to_string().bytes().enumerate() {
+        let bitmap = DIGITS[(digit - b'0') as usize];
+        let digit_left = left + i as u32 * 4 * DIGIT_SCALE;
+        for row in 0..5 {
+            for column in 0..3 {
+                if bitmap & (1 << (14 - (row * 3 + column))) == 0 {
+                    continue;
+                }
+                for y in 0..DIGIT_SCALE {
+                    for x in 0..DIGIT_SCALE {
+                        let x = digit_left + column * DIGIT_SCALE + x;
+                        let y = top + row * DIGIT_SCALE + y;
+                        if x < image.width() && y < image.height() {
+                            image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,30 @@
+use std::time::{Duration, Instant};
+
 use nokhwa::{
     self,
     pixel_format::RgbAFormat,
-    utils::{CameraIndex, CameraInfo, RequestedFormat},
-    Camera, NokhwaError,
+    utils::{
+        CameraIndex, CameraInfo, ControlValueDescription, ControlValueSetter, FrameFormat,
+        KnownCameraControl, RequestedFormat, RequestedFormatType, Resolution,
+    },
+    Buffer, Camera, NokhwaError,
 };
 
+use super::{CameraControl, CameraFormat, CameraOpenFailure, ControlId, ControlValue, VideoFrame};
+
+/// After a still is taken or prepared, video frames are read from the still
+/// camera for this long, so the stills in one session don't each reopen the
+/// device.
+const STILL_SESSION_TIMEOUT: Duration = Duration::from_secs(15);
+/// Frames thrown away after opening the still camera while the sensor adjusts
+/// its exposure.
+const DEFAULT_WARMUP_FRAMES: u32 = 5;
+/// V4L2 has autofocus as its own control, which nokhwa doesn't name.
+const V4L2_CID_FOCUS_AUTO: u128 = 0x009a_090c;
+/// Likewise auto exposure, a menu where `V4L2_EXPOSURE_MANUAL` turns it off.
+const V4L2_CID_EXPOSURE_AUTO: u128 = 0x009a_0901;
+const V4L2_EXPOSURE_MANUAL: i64 = 1;
+
 #[derive(Debug, Clone, Copy)]
 pub struct NokhwaBackend {}
 
@@ -26,59 +46,425 @@ impl super::CameraBackend for NokhwaBackend {
         nokhwa::query(nokhwa::utils::ApiBackend::Auto)
     }
 
-    fn open_camera(item: Self::EnumeratedCamera) -> Result<NokhwaCamera, Self::Error> {
-        Ok(NokhwaCamera::new(item.index().clone()))
+    fn supported_formats(camera: &CameraInfo) -> Result<Vec<CameraFormat>, NokhwaError> {
+        let mut camera = Camera::new(
+            camera.index().clone(),
+            RequestedFormat::new::<RgbAFormat>(RequestedFormatType::None),
+        )?;
+        let mut formats = camera
+            .compatible_camera_formats()?
+            .into_iter()
+            .map(|format| CameraFormat {
+                width: format.width(),
+                height: format.height(),
+                frame_rate: format.frame_rate(),
+            })
+            .collect::<Vec<_>>();
+        // the same resolution is usually offered in several pixel formats
+        formats.sort_by_key(|format| {
+            std::cmp::Reverse((format.width * format.height, format.frame_rate))
+        });
+        formats.dedup();
+        Ok(formats)
+    }
+
+    fn open_camera(
+        item: Self::EnumeratedCamera,
+        format: Option<CameraFormat>,
+    ) -> Result<NokhwaCamera, Self::Error> {
+        let mut camera = NokhwaCamera::new(item.index().clone(), format);
+        // opened now rather than on the first frame, so a camera that can't be
+        // used fails here where setup can explain why
+        camera.open_video_camera()?;
+        Ok(camera)
+    }
+
+    fn classify_open_error(err: &NokhwaError) -> CameraOpenFailure {
+        match err {
+            // macOS refuses to initialize until the app is allowed the camera
+            NokhwaError::UnitializedError => CameraOpenFailure::PermissionDenied,
+            // these only carry the OS's message
+            NokhwaError::OpenDeviceError(_, message) | NokhwaError::OpenStreamError(message) => {
+                CameraOpenFailure::from_message(message)
+            }
+            other => CameraOpenFailure::from_message(&other.to_string()),
+        }
     }
 }
 
 pub struct NokhwaCamera {
     index: CameraIndex,
+    format: Option<CameraFormat>,
     video_camera: Option<Camera>,
     still_camera: Option<Camera>,
+    last_still: Option<Instant>,
+    warmup_frames: u32,
+    /// Stops relative to the auto exposure, applied whenever a camera opens.
+    exposure: Option<f32>,
+    /// The auto exposure value `exposure` is relative to, read from the first
+    /// camera opened after it was set. UVC exposure is an absolute time and
+    /// the driver remembers it across opens, so it can't be re-read each time.
+    base_exposure: Option<i64>,
+    white_balance: Option<u32>,
+    /// Controls set from setup, applied before the exposure lock so the lock
+    /// wins if both change the exposure.
+    controls: Vec<(ControlId, ControlValue)>,
+    focus_locked: bool,
+    /// Where the focus was when it was locked, so a camera reopened for a
+    /// still is held at the same distance.
+    locked_focus: Option<i64>,
 }
 
 impl NokhwaCamera {
-    pub fn new(index: CameraIndex) -> Self {
+    pub fn new(index: CameraIndex, format: Option<CameraFormat>) -> Self {
         NokhwaCamera {
             index,
+            format,
             video_camera: None,
             still_camera: None,
+            last_still: None,
+            warmup_frames: DEFAULT_WARMUP_FRAMES,
+            exposure: None,
+            base_exposure: None,
+            white_balance: None,
+            controls: Vec::new(),
+            focus_locked: false,
+            locked_focus: None,
         }
     }
+
+    /// Opens the still camera if it isn't already, discarding the first
+    /// `warmup_frames` frames so the exposure has settled.
+    fn open_still_camera(&mut self) -> Result<(), NokhwaError> {
+        if self.still_camera.is_some() {
+            return Ok(());
+        }
+        self.video_camera = None; // drop the fast-taking video camera
+        let mut camera = Camera::new(
+            self.index.clone(),
+            self.requested_format(RequestedFormatType::AbsoluteHighestResolution),
+        )?;
+        camera.open_stream()?;
+        self.apply_controls(&mut camera);
+        for _ in 0..self.warmup_frames {
+            camera.frame()?;
+        }
+        self.still_camera = Some(camera);
+        Ok(())
+    }
+
+    /// Opens the fast video camera if it isn't already, closing the still
+    /// camera.
+    fn open_video_camera(&mut self) -> Result<(), NokhwaError> {
+        if self.video_camera.is_some() {
+            return Ok(());
+        }
+        self.still_camera = None; // drop the high-res still camera
+        let mut camera = Camera::new(
+            self.index.clone(),
+            self.requested_format(RequestedFormatType::AbsoluteHighestFrameRate),
+        )?;
+        camera.open_stream()?;
+        self.apply_controls(&mut camera);
+        self.video_camera = Some(camera);
+        Ok(())
+    }
+
+    /// Applies the controls from setup and the locked exposure and white
+    /// balance, if any, to `camera`. Each was accepted by a camera when it
+    /// was set, so a failure here is only logged rather than keeping the
+    /// camera from opening.
+    fn apply_controls(&mut self, camera: &mut Camera) {
+        for &(id, value) in &self.controls {
+            if let Err(err) = write_control(camera, id, value) {
+                log::warn!("Failed to apply {:?}: {}", id, err);
+            }
+        }
+        if let Some(kelvin) = self.white_balance {
+            if let Err(err) = write_white_balance(camera, kelvin) {
+                log::warn!("Failed to apply the white balance: {}", err);
+            }
+        }
+        if self.focus_locked {
+            self.lock_focus(camera);
+        }
+        if let Some(ev) = self.exposure {
+            if let Err(err) = self.write_exposure(camera, ev) {
+                log::warn!("Failed to apply the exposure: {}", err);
+            }
+        }
+    }
+
+    /// Switches `camera` to manual exposure `ev` stops from the auto
+    /// exposure, reading that from the camera first if it isn't known yet.
+    fn write_exposure(&mut self, camera: &mut Camera, ev: f32) -> Result<(), NokhwaError> {
+        let base = match self.base_exposure {
+            Some(base) => base,
+            None => match camera.camera_control(KnownCameraControl::Exposure)?.value() {
+                ControlValueSetter::Integer(value) => *self.base_exposure.insert(value),
+                other => {
+                    return Err(NokhwaError::GetPropertyError {
+                        property: "Exposure".to_string(),
+                        error: format!("unexpected value {:?}", other),
+                    })
+                }
+            },
+        };
+        // not every platform has the separate control, and the others switch
+        // to manual exposure when it's set
+        let _ = camera.set_camera_control(
+            KnownCameraControl::Other(V4L2_CID_EXPOSURE_AUTO),
+            ControlValueSetter::Integer(V4L2_EXPOSURE_MANUAL),
+        );
+        camera.set_camera_control(
+            KnownCameraControl::Exposure,
+            ControlValueSetter::Integer((base as f32 * 2f32.powf(ev)).round() as i64),
+        )
+    }
+
+    /// Switches `camera` to manual focus at `locked_focus`, reading it from
+    /// the camera first if it isn't known yet. Plenty of webcams have fixed
+    /// focus, so failures are only logged.
+    fn lock_focus(&mut self, camera: &mut Camera) {
+        // not every platform has the separate control, and the others switch
+        // to manual focus when it's set
+        let _ = camera.set_camera_control(
+            KnownCameraControl::Other(V4L2_CID_FOCUS_AUTO),
+            ControlValueSetter::Boolean(false),
+        );
+        let focus = match self.locked_focus {
+            Some(focus) => focus,
+            None => match camera
+                .camera_control(KnownCameraControl::Focus)
+                .map(|control| control.value())
+            {
+                Ok(ControlValueSetter::Integer(value)) => *self.locked_focus.insert(value),
+                Ok(other) => {
+                    log::debug!("Focus control has an unexpected value {:?}", other);
+                    return;
+                }
+                Err(err) => {
+                    log::debug!("Camera has no focus control: {}", err);
+                    return;
+                }
+            },
+        };
+        if let Err(err) = camera.set_camera_control(
+            KnownCameraControl::Focus,
+            ControlValueSetter::Integer(focus),
+        ) {
+            log::debug!("Failed to lock focus: {}", err);
+        }
+    }
+
+    /// Calls `write` on whichever cameras are open, stopping at the first
+    /// that rejects it.
+    fn write_open_cameras(
+        &mut self,
+        mut write: impl FnMut(&mut Self, &mut Camera) -> Result<(), NokhwaError>,
+    ) -> Result<(), NokhwaError> {
+        if let Some(mut camera) = self.video_camera.take() {
+            let result = write(self, &mut camera);
+            self.video_camera = Some(camera);
+            result?;
+        }
+        if let Some(mut camera) = self.still_camera.take() {
+            let result = write(self, &mut camera);
+            self.still_camera = Some(camera);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// The camera video frames are read from: the still camera during a still
+    /// session, otherwise the video camera, opening it if needed.
+    fn video_slot(&mut self) -> Result<&mut Option<Camera>, NokhwaError> {
+        let in_still_session = self
+            .last_still
+            .is_some_and(|taken| taken.elapsed() < STILL_SESSION_TIMEOUT);
+        if in_still_session && self.still_camera.is_some() {
+            // the preview is downscaled anyway, so reuse the open still camera
+            return Ok(&mut self.still_camera);
+        }
+        self.open_video_camera()?;
+        Ok(&mut self.video_camera)
+    }
+
+    /// The format to open the camera with, falling back to `default` if the
+    /// operator didn't pick one.
+    fn requested_format(&self, default: RequestedFormatType) -> RequestedFormat<'static> {
+        RequestedFormat::new::<RgbAFormat>(match self.format {
+            Some(format) => RequestedFormatType::Closest(nokhwa::utils::CameraFormat::new(
+                Resolution::new(format.width, format.height),
+                FrameFormat::MJPEG,
+                format.frame_rate,
+            )),
+            None => default,
+        })
+    }
 }
 
 impl super::CameraBackendCamera for NokhwaCamera {
     type Error = NokhwaError;
 
     fn capture_still_frame(&mut self) -> Result<image::RgbaImage, NokhwaError> {
-        if self.still_camera.is_none() {
-            self.video_camera = None; // drop the fast-taking video camera
-            let mut camera = Camera::new(
-                self.index.clone(),
-                RequestedFormat::new::<RgbAFormat>(
-                    nokhwa::utils::RequestedFormatType::AbsoluteHighestResolution,
-                ),
-            )?;
-            camera.open_stream()?;
-            self.still_camera = Some(camera);
+        self.open_still_camera()?;
+        self.last_still = Some(Instant::now());
+        read_frame(&mut self.still_camera)
+    }
+
+    fn prepare_still_capture(&mut self) -> Result<(), NokhwaError> {
+        self.open_still_camera()?;
+        // keep the preview on the still camera so it isn't closed again
+        self.last_still = Some(Instant::now());
+        Ok(())
+    }
+
+    fn set_warmup_frames(&mut self, frames: u32) {
+        self.warmup_frames = frames;
+    }
+
+    fn set_exposure(&mut self, ev: f32) -> Result<(), NokhwaError> {
+        // only kept once an open camera takes it, so a bad value isn't
+        // reapplied to every camera opened after it
+        self.write_open_cameras(|this, camera| this.write_exposure(camera, ev))?;
+        self.exposure = Some(ev);
+        Ok(())
+    }
+
+    fn set_white_balance(&mut self, kelvin: u32) -> Result<(), NokhwaError> {
+        self.write_open_cameras(|_, camera| write_white_balance(camera, kelvin))?;
+        self.white_balance = Some(kelvin);
+        Ok(())
+    }
+
+    fn set_focus_locked(&mut self, locked: bool) -> Result<(), NokhwaError> {
+        self.focus_locked = locked;
+        if locked {
+            // failures are only logged, since plenty of webcams have fixed focus
+            return self.write_open_cameras(|this, camera| {
+                this.lock_focus(camera);
+                Ok(())
+            });
+        }
+        self.locked_focus = None;
+        for camera in [&mut self.video_camera, &mut self.still_camera]
+            .into_iter()
+            .flatten()
+        {
+            // cameras without autofocus have nothing to go back to
+            let _ = camera.set_camera_control(
+                KnownCameraControl::Other(V4L2_CID_FOCUS_AUTO),
+                ControlValueSetter::Boolean(true),
+            );
+        }
+        Ok(())
+    }
+
+    fn controls(&mut self) -> Vec<CameraControl> {
+        if self.video_camera.is_none() && self.still_camera.is_none() {
+            if let Err(err) = self.open_video_camera() {
+                log::warn!("Failed to open camera to read its controls: {}", err);
+                return Vec::new();
+            }
         }
-        let camera = self.still_camera.as_mut().unwrap();
-        camera.frame()?.decode_image::<RgbAFormat>()
+        let camera = self
+            .video_camera
+            .as_ref()
+            .or(self.still_camera.as_ref())
+            .expect("a camera was just opened");
+        ControlId::ALL
+            .into_iter()
+            .filter_map(|id| {
+                // only ranges make sense as sliders
+                match camera.camera_control(known_control(id)).ok()?.description() {
+                    ControlValueDescription::IntegerRange {
+                        min,
+                        max,
+                        value,
+                        step,
+                        ..
+                    } => Some(CameraControl {
+                        id,
+                        min: *min as f32,
+                        max: *max as f32,
+                        step: (*step).max(1) as f32,
+                        value: *value as f32,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn set_control(&mut self, id: ControlId, value: ControlValue) -> Result<(), NokhwaError> {
+        self.write_open_cameras(|_, camera| write_control(camera, id, value))?;
+        self.controls.retain(|(set, _)| *set != id);
+        self.controls.push((id, value));
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        nokhwa::query(nokhwa::utils::ApiBackend::Auto)
+            .map(|cameras| cameras.iter().any(|camera| camera.index() == &self.index))
+            .unwrap_or(false)
     }
 
     fn capture_video_frame(&mut self) -> Result<image::RgbaImage, NokhwaError> {
-        if self.video_camera.is_none() {
-            self.still_camera = None; // drop the high-res still camera
-            let mut camera = Camera::new(
-                self.index.clone(),
-                RequestedFormat::new::<RgbAFormat>(
-                    nokhwa::utils::RequestedFormatType::AbsoluteHighestFrameRate,
-                ),
-            )?;
-            camera.open_stream()?;
-            self.video_camera = Some(camera);
+        read_frame(self.video_slot()?)
+    }
+
+    fn capture_video_frame_encoded(&mut self) -> Result<VideoFrame, NokhwaError> {
+        let buffer = read_buffer(self.video_slot()?)?;
+        if buffer.source_frame_format() == FrameFormat::MJPEG {
+            // iced decodes it on its own
+            Ok(VideoFrame::Jpeg(buffer.buffer_bytes()))
+        } else {
+            buffer.decode_image::<RgbAFormat>().map(VideoFrame::Rgba)
+        }
+    }
+}
+
+/// Reads a frame from the open camera in `slot` and decodes it.
+fn read_frame(slot: &mut Option<Camera>) -> Result<image::RgbaImage, NokhwaError> {
+    read_buffer(slot)?.decode_image::<RgbAFormat>()
+}
+
+/// Reads a frame from the open camera in `slot`, closing it on failure so it's
+/// reopened next time in case the device was unplugged.
+fn read_buffer(slot: &mut Option<Camera>) -> Result<Buffer, NokhwaError> {
+    let camera = slot.as_mut().expect("camera should be open");
+    match camera.frame() {
+        Ok(frame) => Ok(frame),
+        Err(err) => {
+            *slot = None;
+            Err(err)
         }
-        let camera = self.video_camera.as_mut().unwrap();
-        camera.frame()?.decode_image::<RgbAFormat>()
+    }
+}
+
+fn write_control(
+    camera: &mut Camera,
+    id: ControlId,
+    value: ControlValue,
+) -> Result<(), NokhwaError> {
+    camera.set_camera_control(
+        known_control(id),
+        ControlValueSetter::Integer(value.round() as i64),
+    )
+}
+
+fn write_white_balance(camera: &mut Camera, kelvin: u32) -> Result<(), NokhwaError> {
+    camera.set_camera_control(
+        KnownCameraControl::WhiteBalance,
+        ControlValueSetter::Integer(kelvin as i64),
+    )
+}
+
+fn known_control(id: ControlId) -> KnownCameraControl {
+    match id {
+        ControlId::Brightness => KnownCameraControl::Brightness,
+        ControlId::Exposure => KnownCameraControl::Exposure,
+        ControlId::Gain => KnownCameraControl::Gain,
     }
 }
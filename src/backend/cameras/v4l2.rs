@@ -0,0 +1,323 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use v4l::{
+    buffer::Type,
+    frameinterval::FrameIntervalEnum,
+    io::{mmap::Stream, traits::CaptureStream},
+    video::{capture::Parameters, Capture},
+    Device, Format, FourCC,
+};
+
+use super::CameraFormat;
+
+/// Pixel formats we know how to turn into an `RgbaImage`, in order of
+/// preference.
+const SUPPORTED_FOURCCS: [&[u8; 4]; 2] = [b"MJPG", b"YUYV"];
+/// Frames thrown away after opening the still stream while the sensor adjusts
+/// its exposure.
+const DEFAULT_WARMUP_FRAMES: u32 = 5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct V4l2Backend {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V4l2Device {
+    path: PathBuf,
+    name: String,
+}
+
+impl Display for V4l2Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on {}", self.name, self.path.display())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct V4l2Error(String);
+
+impl Display for V4l2Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<std::io::Error> for V4l2Error {
+    fn from(value: std::io::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<image::ImageError> for V4l2Error {
+    fn from(value: image::ImageError) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl super::CameraBackend for V4l2Backend {
+    type Error = V4l2Error;
+    type EnumeratedCamera = V4l2Device;
+    type Camera = V4l2Camera;
+
+    fn enumerate_cameras() -> Result<Vec<V4l2Device>, V4l2Error> {
+        let mut devices = v4l::context::enum_devices()
+            .into_iter()
+            // every UVC webcam also exposes a metadata node, so skip anything
+            // that can't actually capture video
+            .filter(|node| {
+                Device::with_path(node.path())
+                    .and_then(|device| device.query_caps())
+                    .map(|caps| {
+                        caps.capabilities
+                            .contains(v4l::capability::Flags::VIDEO_CAPTURE)
+                    })
+                    .unwrap_or(false)
+            })
+            .map(|node| V4l2Device {
+                name: node
+                    .name()
+                    .unwrap_or_else(|| node.path().display().to_string()),
+                path: node.path().to_path_buf(),
+            })
+            .collect::<Vec<_>>();
+        devices.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(devices)
+    }
+
+    fn supported_formats(camera: &V4l2Device) -> Result<Vec<CameraFormat>, V4l2Error> {
+        let device = Device::with_path(&camera.path)?;
+        let fourcc = preferred_fourcc(&device)?;
+        let mut formats = Vec::new();
+        for size in device.enum_framesizes(fourcc)? {
+            for size in size.size.to_discrete() {
+                for interval in device.enum_frameintervals(fourcc, size.width, size.height)? {
+                    if let FrameIntervalEnum::Discrete(fraction) = interval.interval {
+                        // slower than a frame a second can't be asked for
+                        // in whole frames per second
+                        let frame_rate = fraction
+                            .denominator
+                            .checked_div(fraction.numerator)
+                            .unwrap_or(0);
+                        if frame_rate == 0 {
+                            continue;
+                        }
+                        formats.push(CameraFormat {
+                            width: size.width,
+                            height: size.height,
+                            frame_rate,
+                        });
+                    }
+                }
+            }
+        }
+        formats.sort_by_key(|format| {
+            std::cmp::Reverse((format.width * format.height, format.frame_rate))
+        });
+        formats.dedup();
+        Ok(formats)
+    }
+
+    fn open_camera(
+        item: Self::EnumeratedCamera,
+        format: Option<CameraFormat>,
+    ) -> Result<V4l2Camera, Self::Error> {
+        Ok(V4l2Camera::new(item.path, format))
+    }
+}
+
+/// An open device with a running capture stream.
+struct V4l2Stream {
+    // the stream keeps its own handle to the device, but the device is kept
+    // around so the file descriptor isn't closed underneath it
+    _device: Device,
+    stream: Stream<'static>,
+    format: Format,
+}
+
+impl V4l2Stream {
+    fn open(
+        path: &Path,
+        highest_resolution: bool,
+        requested: Option<CameraFormat>,
+    ) -> Result<Self, V4l2Error> {
+        let device = Device::with_path(path)?;
+        let format = negotiate_format(&device, highest_resolution, requested)?;
+        log::debug!(
+            "Opened {} as {}x{} {}",
+            path.display(),
+            format.width,
+            format.height,
+            format.fourcc
+        );
+        let stream = Stream::with_buffers(&device, Type::VideoCapture, 4)?;
+        Ok(V4l2Stream {
+            _device: device,
+            stream,
+            format,
+        })
+    }
+
+    fn frame(&mut self) -> Result<image::RgbaImage, V4l2Error> {
+        let (buffer, metadata) = self.stream.next()?;
+        let data = &buffer[..(metadata.bytesused as usize).min(buffer.len())];
+        decode_frame(data, &self.format)
+    }
+
+    /// Reads `frames` frames without decoding them.
+    fn skip(&mut self, frames: u32) -> Result<(), V4l2Error> {
+        for _ in 0..frames {
+            self.stream.next()?;
+        }
+        Ok(())
+    }
+}
+
+pub struct V4l2Camera {
+    path: PathBuf,
+    format: Option<CameraFormat>,
+    video_stream: Option<V4l2Stream>,
+    still_stream: Option<V4l2Stream>,
+    warmup_frames: u32,
+}
+
+impl V4l2Camera {
+    pub fn new(path: PathBuf, format: Option<CameraFormat>) -> Self {
+        V4l2Camera {
+            path,
+            format,
+            video_stream: None,
+            still_stream: None,
+            warmup_frames: DEFAULT_WARMUP_FRAMES,
+        }
+    }
+}
+
+impl super::CameraBackendCamera for V4l2Camera {
+    type Error = V4l2Error;
+
+    fn capture_still_frame(&mut self) -> Result<image::RgbaImage, V4l2Error> {
+        if self.still_stream.is_none() {
+            self.video_stream = None; // drop the fast-taking video stream
+            let mut stream = V4l2Stream::open(&self.path, true, self.format)?;
+            stream.skip(self.warmup_frames)?;
+            self.still_stream = Some(stream);
+        }
+        self.still_stream.as_mut().unwrap().frame()
+    }
+
+    fn set_warmup_frames(&mut self, frames: u32) {
+        self.warmup_frames = frames;
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.path.exists()
+    }
+
+    fn capture_video_frame(&mut self) -> Result<image::RgbaImage, V4l2Error> {
+        if self.video_stream.is_none() {
+            self.still_stream = None; // drop the high-res still stream
+            self.video_stream = Some(V4l2Stream::open(&self.path, false, self.format)?);
+        }
+        let frame = self.video_stream.as_mut().unwrap().frame();
+        if frame.is_err() {
+            // the device may have been unplugged, so reopen it next time
+            self.video_stream = None;
+        }
+        frame
+    }
+}
+
+/// The first pixel format in `SUPPORTED_FOURCCS` the device offers.
+fn preferred_fourcc(device: &Device) -> Result<FourCC, V4l2Error> {
+    let descriptions = device.enum_formats()?;
+    SUPPORTED_FOURCCS
+        .iter()
+        .map(|repr| FourCC::new(repr))
+        .find(|fourcc| descriptions.iter().any(|desc| desc.fourcc == *fourcc))
+        .ok_or_else(|| V4l2Error("device supports neither MJPG nor YUYV".to_string()))
+}
+
+/// Applies the preferred pixel format to the device. If the operator
+/// `requested` a format, its size and frame rate are used. Otherwise, if
+/// `highest_resolution` is set, the largest frame size for that format is
+/// requested, and if not the driver's current size is kept, which is usually a
+/// sensible preview resolution.
+fn negotiate_format(
+    device: &Device,
+    highest_resolution: bool,
+    requested: Option<CameraFormat>,
+) -> Result<Format, V4l2Error> {
+    let fourcc = preferred_fourcc(device)?;
+
+    let mut format = device.format()?;
+    format.fourcc = fourcc;
+    if let Some(requested) = requested {
+        format.width = requested.width;
+        format.height = requested.height;
+    } else if highest_resolution {
+        let largest = device
+            .enum_framesizes(fourcc)?
+            .into_iter()
+            .flat_map(|size| size.size.to_discrete())
+            .max_by_key(|size| size.width * size.height);
+        if let Some(size) = largest {
+            format.width = size.width;
+            format.height = size.height;
+        }
+    }
+
+    let format = device.set_format(&format)?;
+    if format.fourcc != fourcc {
+        return Err(V4l2Error(format!(
+            "device refused pixel format {}, got {} instead",
+            fourcc, format.fourcc
+        )));
+    }
+    if let Some(requested) = requested {
+        device.set_params(&Parameters::with_fps(requested.frame_rate))?;
+    }
+    Ok(format)
+}
+
+fn decode_frame(data: &[u8], format: &Format) -> Result<image::RgbaImage, V4l2Error> {
+    match &format.fourcc.repr {
+        b"MJPG" => {
+            Ok(image::load_from_memory_with_format(data, image::ImageFormat::Jpeg)?.to_rgba8())
+        }
+        b"YUYV" => yuyv_to_rgba(data, format.width, format.height),
+        _ => Err(V4l2Error(format!(
+            "unsupported pixel format {}",
+            format.fourcc
+        ))),
+    }
+}
+
+/// Converts packed YUYV 4:2:2 to RGBA using the BT.601 coefficients.
+fn yuyv_to_rgba(data: &[u8], width: u32, height: u32) -> Result<image::RgbaImage, V4l2Error> {
+    let expected = (width * height * 2) as usize;
+    if data.len() < expected {
+        return Err(V4l2Error(format!(
+            "short YUYV frame: expected {} bytes, got {}",
+            expected,
+            data.len()
+        )));
+    }
+
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for chunk in data[..expected].chunks_exact(4) {
+        let u = chunk[1] as f32 - 128.0;
+        let v = chunk[3] as f32 - 128.0;
+        for y in [chunk[0], chunk[2]] {
+            let y = y as f32;
+            rgba.push((y + 1.402 * v).clamp(0.0, 255.0) as u8);
+            rgba.push((y - 0.344 * u - 0.714 * v).clamp(0.0, 255.0) as u8);
+            rgba.push((y + 1.772 * u).clamp(0.0, 255.0) as u8);
+            rgba.push(255);
+        }
+    }
+
+    image::RgbaImage::from_raw(width, height, rgba)
+        .ok_or_else(|| V4l2Error("YUYV frame has the wrong size".to_string()))
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use crate::{
+    backend::cameras::{CameraFormat, ControlId, ControlValue},
+    frontend::main_app::CaptureSettings,
+};
+
+/// What was picked in setup the last time the booth started, so it can start
+/// again without anyone touching it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SavedSetup {
+    /// Display name of the camera, since that's the only thing all backends
+    /// can compare across restarts.
+    pub camera: String,
+    pub secondary_camera: Option<String>,
+    pub format: Option<CameraFormat>,
+    #[serde(default)]
+    pub settings: CaptureSettings,
+    /// Fills in `{event}` on the strip. Older configs called it the caption.
+    #[serde(default, alias = "caption")]
+    pub event_name: String,
+    /// Name of the strip template.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Camera controls changed in setup's advanced section.
+    #[serde(default)]
+    pub controls: Vec<(ControlId, ControlValue)>,
+    /// Whether the booth goes fullscreen when it starts.
+    #[serde(default = "default_fullscreen")]
+    pub fullscreen: bool,
+}
+
+fn default_fullscreen() -> bool {
+    true
+}
+
+fn config_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("photo-booth").join("config.toml"))
+}
+
+/// Reads the saved setup, if there is one and it can be read.
+pub fn load() -> Option<SavedSetup> {
+    let path = config_path()?;
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            log::warn!("Failed to read {}: {}", path.display(), err);
+            return None;
+        }
+    };
+    toml::from_str(&contents)
+        .inspect_err(|err| log::warn!("Ignoring invalid config {}: {}", path.display(), err))
+        .ok()
+}
+
+/// Saves `setup` for the next start. Failures are only logged since the booth
+/// works fine without it.
+pub fn save(setup: &SavedSetup) {
+    let Some(path) = config_path() else {
+        log::warn!("No config directory, not saving setup");
+        return;
+    };
+    let result = toml::to_string(setup)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+            }
+            std::fs::write(&path, contents).map_err(|err| err.to_string())
+        });
+    match result {
+        Ok(()) => log::info!("Saved setup to {}", path.display()),
+        Err(err) => log::warn!("Failed to save setup to {}: {}", path.display(), err),
+    }
+}
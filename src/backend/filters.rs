@@ -0,0 +1,118 @@
+use image::RgbaImage;
+
+/// How much "vivid" pushes the colors away from gray.
+const VIVID_SATURATION: f32 = 1.4;
+/// How much "vivid" pushes the tones away from the middle.
+const VIVID_CONTRAST: f32 = 1.15;
+
+/// Color effect applied to every frame by the camera feed, or picked by the
+/// guests for their strip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum ColorFilter {
+    #[default]
+    None,
+    Grayscale,
+    Sepia,
+    Vivid,
+}
+
+impl ColorFilter {
+    pub const ALL: [ColorFilter; 4] = [Self::None, Self::Grayscale, Self::Sepia, Self::Vivid];
+}
+
+impl std::fmt::Display for ColorFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "No filter"),
+            Self::Grayscale => write!(f, "Black and white"),
+            Self::Sepia => write!(f, "Sepia"),
+            Self::Vivid => write!(f, "Vivid"),
+        }
+    }
+}
+
+/// A copy of `image` with `filter` applied.
+pub fn apply_filter(image: &RgbaImage, filter: ColorFilter) -> RgbaImage {
+    let mut image = image.clone();
+    apply_filter_in_place(&mut image, filter);
+    image
+}
+
+/// Applies `filter` to `image` itself, for when the original isn't needed.
+pub fn apply_filter_in_place(image: &mut RgbaImage, filter: ColorFilter) {
+    match filter {
+        ColorFilter::None => {}
+        ColorFilter::Grayscale => {
+            for pixel in image.pixels_mut() {
+                let [r, g, b, _] = pixel.0.map(|x| x as f32);
+                let luma = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
+                pixel.0[..3].fill(luma);
+            }
+        }
+        ColorFilter::Sepia => {
+            for pixel in image.pixels_mut() {
+                let [r, g, b, _] = pixel.0.map(|x| x as f32);
+                pixel.0[0] = (0.393 * r + 0.769 * g + 0.189 * b).min(255.0) as u8;
+                pixel.0[1] = (0.349 * r + 0.686 * g + 0.168 * b).min(255.0) as u8;
+                pixel.0[2] = (0.272 * r + 0.534 * g + 0.131 * b).min(255.0) as u8;
+            }
+        }
+        ColorFilter::Vivid => {
+            for pixel in image.pixels_mut() {
+                let [r, g, b, _] = pixel.0.map(|x| x as f32);
+                let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+                for channel in &mut pixel.0[..3] {
+                    let saturated = luma + (*channel as f32 - luma) * VIVID_SATURATION;
+                    let contrasted = 127.5 + (saturated - 127.5) * VIVID_CONTRAST;
+                    *channel = contrasted.clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(rgb: [u8; 3]) -> RgbaImage {
+        RgbaImage::from_pixel(1, 1, image::Rgba([rgb[0], rgb[1], rgb[2], 200]))
+    }
+
+    fn filtered(rgb: [u8; 3], filter: ColorFilter) -> [u8; 4] {
+        apply_filter(&pixel(rgb), filter).get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn no_filter_leaves_the_image_alone() {
+        assert_eq!(
+            filtered([10, 120, 240], ColorFilter::None),
+            [10, 120, 240, 200]
+        );
+    }
+
+    #[test]
+    fn grayscale_and_sepia_keep_the_alpha() {
+        let [r, g, b, a] = filtered([10, 120, 240], ColorFilter::Grayscale);
+        assert!(r == g && g == b);
+        assert_eq!(a, 200);
+        let [r, g, b, a] = filtered([10, 120, 240], ColorFilter::Sepia);
+        assert!(r >= g && g >= b);
+        assert_eq!(a, 200);
+    }
+
+    #[test]
+    fn vivid_pushes_colors_apart_and_keeps_grays() {
+        let [r, g, b, a] = filtered([100, 140, 180], ColorFilter::Vivid);
+        assert!(b - r > 80, "{:?}", [r, g, b]);
+        assert_eq!(a, 200);
+        // only the contrast applies to grays
+        assert_eq!(filtered([128, 128, 128], ColorFilter::Vivid)[..3], [128; 3]);
+        let [dark, ..] = filtered([60, 60, 60], ColorFilter::Vivid);
+        assert!(dark < 60);
+        assert_eq!(
+            filtered([255, 0, 255], ColorFilter::Vivid)[..3],
+            [255, 0, 255]
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt::Display;
+
+/// Names the CUPS printer strips are sent to. Printing is only offered if
+/// it's set.
+const PRINTER_VAR: &str = "PRINTER_NAME";
+
+/// The printer from `PRINTER_NAME`, if there is one.
+pub fn configured_printer() -> Option<String> {
+    std::env::var(PRINTER_VAR)
+        .ok()
+        .filter(|name| !name.trim().is_empty())
+}
+
+#[derive(Debug)]
+pub enum PrintError {
+    Io(std::io::Error),
+    /// `lp` ran but refused the job, with what it printed to stderr.
+    Rejected(String),
+    Unsupported,
+}
+
+impl Display for PrintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "i/o error: {}", err),
+            Self::Rejected(message) => write!(f, "lp rejected the job: {}", message),
+            Self::Unsupported => write!(f, "printing is only supported on linux"),
+        }
+    }
+}
+
+/// Queues `png`, a strip already at the printer's size, on `printer` with
+/// CUPS' `lp`, returning once the job is submitted rather than printed.
+#[cfg(target_os = "linux")]
+pub fn print_strip(png: &[u8], printer: &str) -> Result<(), PrintError> {
+    let path = std::env::temp_dir().join(format!(
+        "photo-booth-{}-{}.png",
+        std::process::id(),
+        chrono::offset::Local::now().format("%Y%m%d%H%M%S%3f")
+    ));
+    std::fs::write(&path, png).map_err(PrintError::Io)?;
+    // lp hands the file to the spooler before it exits, so it can go after
+    let output = std::process::Command::new("lp")
+        .arg("-d")
+        .arg(printer)
+        .arg(&path)
+        .output();
+    if let Err(err) = std::fs::remove_file(&path) {
+        log::warn!("Failed to remove {}: {}", path.display(), err);
+    }
+    let output = output.map_err(PrintError::Io)?;
+    if output.status.success() {
+        log::info!(
+            "Sent strip to {}: {}",
+            printer,
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+        Ok(())
+    } else {
+        Err(PrintError::Rejected(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn print_strip(_png: &[u8], _printer: &str) -> Result<(), PrintError> {
+    Err(PrintError::Unsupported)
+}
@@ -0,0 +1,461 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+
+use super::servers::{Progress, ServerBackend};
+
+/// Where sessions that couldn't be uploaded are kept, if not the default
+/// under the local data directory.
+const SPOOL_DIR_VAR: &str = "SPOOL_DIR";
+/// Added to a session's directory while the guests can still add emails to
+/// it, so it isn't uploaded halfway through.
+const OPEN_SUFFIX: &str = ".open";
+/// Added to a session's directory if it can't be read back, so it's set
+/// aside instead of being retried on every flush.
+const BAD_SUFFIX: &str = ".bad";
+const EMAILS_FILE: &str = "emails.txt";
+/// Where a session's upload handle is kept once it's uploaded, so only its
+/// emails are left to send.
+const UPLOADED_FILE: &str = "uploaded.json";
+/// The files of a session that are only needed until it's uploaded.
+const PHOTO_FILES: [&str; 3] = ["strip.png", "strip_print.png", "animation.gif"];
+
+/// A session waiting to be uploaded.
+#[derive(Debug, Clone)]
+pub struct QueuedSession {
+    pub strip: RgbaImage,
+    /// The strip as a print-ready PNG, if one was made.
+    pub print: Option<Vec<u8>>,
+    pub photos: Vec<RgbaImage>,
+    pub gif: Option<Vec<u8>>,
+    /// Addresses to email once it's uploaded.
+    pub emails: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum QueueError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    /// The upload handle couldn't be saved or read back.
+    Json(serde_json::Error),
+    /// There's no local data directory and `SPOOL_DIR` isn't set.
+    NoSpoolDir,
+}
+
+impl Display for QueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "i/o error: {}", err),
+            Self::Image(err) => write!(f, "image encode/decode error: {}", err),
+            Self::Json(err) => write!(f, "upload handle error: {}", err),
+            Self::NoSpoolDir => write!(f, "no directory to queue sessions in"),
+        }
+    }
+}
+
+/// Where sessions are queued: `SPOOL_DIR`, or the default under the local
+/// data directory.
+pub fn spool_dir() -> Result<PathBuf, QueueError> {
+    match std::env::var_os(SPOOL_DIR_VAR) {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(dirs::data_local_dir()
+            .ok_or(QueueError::NoSpoolDir)?
+            .join("photo-booth")
+            .join("spool")),
+    }
+}
+
+/// Writes `session` to its own directory in `spool` and returns it. The
+/// session stays open for `add_emails` until it's passed to `close`.
+pub fn enqueue_in(spool: &Path, session: &QueuedSession) -> Result<PathBuf, QueueError> {
+    let time = chrono::offset::Local::now()
+        .format("%Y%m%d-%H%M%S%.3f")
+        .to_string();
+    let dir = create_session_dir(spool, &time).map_err(QueueError::Io)?;
+    session
+        .strip
+        .save(dir.join("strip.png"))
+        .map_err(QueueError::Image)?;
+    for (i, photo) in session.photos.iter().enumerate() {
+        photo
+            .save(dir.join(format!("photo_{}.png", i + 1)))
+            .map_err(QueueError::Image)?;
+    }
+    if let Some(print) = &session.print {
+        std::fs::write(dir.join("strip_print.png"), print).map_err(QueueError::Io)?;
+    }
+    if let Some(gif) = &session.gif {
+        std::fs::write(dir.join("animation.gif"), gif).map_err(QueueError::Io)?;
+    }
+    add_emails(&dir, &session.emails)?;
+    log::info!("Queued session in {}", dir.display());
+    Ok(dir)
+}
+
+/// Creates a new, still open session directory in `spool` named `time`.
+/// Numbered if another session was queued at the same time, so it isn't
+/// written over.
+fn create_session_dir(spool: &Path, time: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(spool)?;
+    let mut name = time.to_string();
+    let mut n = 1;
+    loop {
+        let dir = spool.join(format!("{}{}", name, OPEN_SUFFIX));
+        // a closed session with the name would be in the way of closing it
+        let result = if spool.join(&name).exists() {
+            Err(std::io::ErrorKind::AlreadyExists.into())
+        } else {
+            std::fs::create_dir(&dir)
+        };
+        match result {
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                n += 1;
+                name = format!("{}-{}", time, n);
+            }
+            result => return result.map(|()| dir),
+        }
+    }
+}
+
+/// Adds `emails` to a queued session that's still open.
+pub fn add_emails(dir: &Path, emails: &[String]) -> Result<(), QueueError> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(EMAILS_FILE))
+        .map_err(QueueError::Io)?;
+    for email in emails {
+        writeln!(file, "{}", email).map_err(QueueError::Io)?;
+    }
+    Ok(())
+}
+
+/// Lets a queued session be uploaded now that no more emails will be added.
+pub fn close(dir: &Path) -> Result<(), QueueError> {
+    let name = dir.to_string_lossy();
+    let Some(closed) = name.strip_suffix(OPEN_SUFFIX) else {
+        return Ok(());
+    };
+    std::fs::rename(dir, closed).map_err(QueueError::Io)
+}
+
+/// Closes sessions left open when the booth last stopped. Only call it
+/// before any session could have been queued.
+pub fn recover() {
+    let Ok(spool) = spool_dir() else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(&spool) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.to_string_lossy().ends_with(OPEN_SUFFIX) {
+            log::info!("Closing session left open: {}", path.display());
+            if let Err(err) = close(&path) {
+                log::warn!("Failed to close {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// Closed sessions in `spool`, oldest first.
+fn pending(spool: &Path) -> Vec<PathBuf> {
+    let mut sessions: Vec<PathBuf> = match std::fs::read_dir(spool) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.to_string_lossy();
+                path.is_dir() && !name.ends_with(OPEN_SUFFIX) && !name.ends_with(BAD_SUFFIX)
+            })
+            .collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            log::warn!("Failed to read {}: {}", spool.display(), err);
+            Vec::new()
+        }
+    };
+    // named by when they were queued
+    sessions.sort();
+    sessions
+}
+
+/// Reads a queued session back. Fails once it's been uploaded, since its
+/// photos are gone by then.
+pub fn load(dir: &Path) -> Result<QueuedSession, QueueError> {
+    let read_optional = |name: &str| match std::fs::read(dir.join(name)) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(QueueError::Io(err)),
+    };
+    let strip = image::open(dir.join("strip.png"))
+        .map_err(QueueError::Image)?
+        .into_rgba8();
+    let mut photos = Vec::new();
+    loop {
+        let path = dir.join(format!("photo_{}.png", photos.len() + 1));
+        if !path.exists() {
+            break;
+        }
+        photos.push(image::open(path).map_err(QueueError::Image)?.into_rgba8());
+    }
+    Ok(QueuedSession {
+        strip,
+        print: read_optional("strip_print.png")?,
+        photos,
+        gif: read_optional("animation.gif")?,
+        emails: emails(dir)?,
+    })
+}
+
+/// The addresses a queued session is to be emailed to.
+fn emails(dir: &Path) -> Result<Vec<String>, QueueError> {
+    match std::fs::read_to_string(dir.join(EMAILS_FILE)) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|email| !email.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(QueueError::Io(err)),
+    }
+}
+
+/// Saves where a queued session was uploaded to and deletes its photos, so
+/// a failure emailing it doesn't upload it again.
+fn mark_uploaded<H: serde::Serialize>(dir: &Path, handle: &H) -> Result<(), QueueError> {
+    let handle = serde_json::to_vec(handle).map_err(QueueError::Json)?;
+    std::fs::write(dir.join(UPLOADED_FILE), handle).map_err(QueueError::Io)?;
+    let photos = (1..).map(|i| format!("photo_{}.png", i));
+    for name in PHOTO_FILES.into_iter().map(str::to_string).chain(photos) {
+        match std::fs::remove_file(dir.join(&name)) {
+            Ok(()) => {}
+            // the photos are numbered from 1 with no gaps
+            Err(err)
+                if err.kind() == std::io::ErrorKind::NotFound && name.starts_with("photo_") =>
+            {
+                break
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(QueueError::Io(err)),
+        }
+    }
+    Ok(())
+}
+
+/// Where a queued session was uploaded to, if it has been.
+fn uploaded<H: serde::de::DeserializeOwned>(dir: &Path) -> Result<Option<H>, QueueError> {
+    match std::fs::read(dir.join(UPLOADED_FILE)) {
+        Ok(handle) => serde_json::from_slice(&handle)
+            .map(Some)
+            .map_err(QueueError::Json),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(QueueError::Io(err)),
+    }
+}
+
+/// Uploads and emails every closed session, deleting each once it's done.
+/// Stops at the first failure, since the rest would most likely fail the
+/// same way. A session that was uploaded but couldn't be emailed is only
+/// emailed next time. Returns how many were sent.
+pub async fn flush<S: ServerBackend + 'static>(server_backend: S) -> Result<usize, String> {
+    let spool = spool_dir().map_err(|err| err.to_string())?;
+    flush_in(&spool, server_backend).await
+}
+
+async fn flush_in<S: ServerBackend + 'static>(
+    spool: &Path,
+    server_backend: S,
+) -> Result<usize, String> {
+    let mut sent = 0;
+    for dir in pending(spool) {
+        let handle = match upload(&dir, server_backend.clone()).await {
+            Ok(Some(handle)) => handle,
+            Ok(None) => continue,
+            Err(err) => return Err(err),
+        };
+        let emails = emails(&dir).map_err(|err| err.to_string())?;
+        if !emails.is_empty() {
+            let deliveries = server_backend
+                .clone()
+                .send_email(handle, emails, Progress::channel().0)
+                .await
+                .map_err(|err| err.to_string())?;
+            for delivery in deliveries.iter().filter(|delivery| !delivery.delivered) {
+                // the address itself was most likely the problem
+                log::warn!(
+                    "Couldn't email queued photos to {}, giving up",
+                    delivery.address
+                );
+            }
+        }
+        if let Err(err) = std::fs::remove_dir_all(&dir) {
+            log::warn!("Failed to remove {}: {}", dir.display(), err);
+        }
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+/// Uploads the session in `dir` unless that's been done already, and returns
+/// where it was uploaded to. `None` means it can't be read and was set
+/// aside.
+async fn upload<S: ServerBackend + 'static>(
+    dir: &Path,
+    server_backend: S,
+) -> Result<Option<S::UploadHandle>, String> {
+    if let Some(handle) = uploaded(dir).map_err(|err| err.to_string())? {
+        log::info!("Emailing queued session {}", dir.display());
+        return Ok(Some(handle));
+    }
+    let loaded = {
+        let dir = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || load(&dir))
+            .await
+            .map_err(|err| err.to_string())?
+    };
+    let session = match loaded {
+        Ok(session) => session,
+        Err(err) => {
+            // a broken session would otherwise block everything after it
+            log::error!(
+                "Setting aside unreadable session {}: {}",
+                dir.display(),
+                err
+            );
+            let bad = format!("{}{}", dir.display(), BAD_SUFFIX);
+            if let Err(err) = std::fs::rename(dir, bad) {
+                log::warn!("Failed to set aside {}: {}", dir.display(), err);
+            }
+            return Ok(None);
+        }
+    };
+    log::info!("Uploading queued session {}", dir.display());
+    // nobody is watching the progress of these
+    let handle = server_backend
+        .upload_photo(
+            session.strip,
+            session.print,
+            session.photos,
+            session.gif,
+            Progress::channel().0,
+        )
+        .await
+        .map_err(|err| err.to_string())?;
+    if let Err(err) = mark_uploaded(dir, &handle) {
+        // it's only uploaded again if the email fails too
+        log::warn!("Failed to mark {} as uploaded: {}", dir.display(), err);
+    }
+    Ok(Some(handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{frame, ScriptedServerBackend, ServerCall};
+
+    fn session() -> QueuedSession {
+        QueuedSession {
+            strip: frame(40, 120),
+            print: Some(b"print".to_vec()),
+            photos: vec![frame(30, 20), frame(30, 20), frame(30, 20)],
+            gif: Some(b"gif".to_vec()),
+            emails: vec!["a@example.com".to_string()],
+        }
+    }
+
+    /// Queues `session()` in `spool` and adds a second email before closing
+    /// it, like a guest typing one in after the upload failed.
+    fn queue(spool: &Path) -> PathBuf {
+        let dir = enqueue_in(spool, &session()).unwrap();
+        assert!(pending(spool).is_empty(), "open sessions aren't pending");
+        add_emails(&dir, &["b@example.com".to_string()]).unwrap();
+        close(&dir).unwrap();
+        let pending = pending(spool);
+        assert_eq!(pending.len(), 1);
+        pending[0].clone()
+    }
+
+    #[test]
+    fn queued_session_reads_back_the_same() {
+        let spool = tempfile::tempdir().unwrap();
+        let dir = queue(spool.path());
+        let loaded = load(&dir).unwrap();
+        let original = session();
+        assert_eq!(loaded.strip, original.strip);
+        assert_eq!(loaded.print, original.print);
+        assert_eq!(loaded.photos, original.photos);
+        assert_eq!(loaded.gif, original.gif);
+        assert_eq!(loaded.emails, ["a@example.com", "b@example.com"]);
+    }
+
+    #[test]
+    fn sessions_queued_at_the_same_time_get_their_own_directories() {
+        let spool = tempfile::tempdir().unwrap();
+        let time = "20250101-120000.000";
+        let closed = create_session_dir(spool.path(), time).unwrap();
+        close(&closed).unwrap();
+        let first = create_session_dir(spool.path(), time).unwrap();
+        let second = create_session_dir(spool.path(), time).unwrap();
+        assert_ne!(first, second);
+        close(&first).unwrap();
+        close(&second).unwrap();
+        assert_eq!(pending(spool.path()).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_email_is_retried_without_uploading_again() {
+        let spool = tempfile::tempdir().unwrap();
+        let dir = queue(spool.path());
+        let server = ScriptedServerBackend::default();
+        server.fail_uploads(1, "offline");
+        server.fail_emails(1, "offline");
+
+        assert!(flush_in(spool.path(), server.clone()).await.is_err());
+        assert!(load(&dir).is_ok(), "nothing was uploaded, so it's all kept");
+
+        assert!(flush_in(spool.path(), server.clone()).await.is_err());
+        assert!(load(&dir).is_err(), "the photos are gone once uploaded");
+        assert_eq!(uploaded::<usize>(&dir).unwrap(), Some(1));
+        assert_eq!(emails(&dir).unwrap(), ["a@example.com", "b@example.com"]);
+
+        assert_eq!(flush_in(spool.path(), server.clone()).await, Ok(1));
+        assert!(!dir.exists());
+        assert!(pending(spool.path()).is_empty());
+
+        let upload = ServerCall::Upload {
+            photos: 3,
+            print: true,
+            gif: true,
+        };
+        let email = ServerCall::Email {
+            handle: 1,
+            emails: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+        };
+        assert_eq!(
+            server.calls(),
+            [upload.clone(), upload, email.clone(), email]
+        );
+    }
+
+    #[tokio::test]
+    async fn unreadable_session_is_set_aside() {
+        let spool = tempfile::tempdir().unwrap();
+        queue(spool.path());
+        // sorts first, so it'd hold up the real one if it weren't skipped
+        let broken = spool.path().join("00000000-000000.000");
+        std::fs::create_dir(&broken).unwrap();
+        let server = ScriptedServerBackend::default();
+        assert_eq!(flush_in(spool.path(), server.clone()).await, Ok(1));
+        assert_eq!(server.calls().len(), 2);
+        assert!(pending(spool.path()).is_empty());
+        assert!(spool.path().join("00000000-000000.000.bad").is_dir());
+        assert_eq!(flush_in(spool.path(), server.clone()).await, Ok(0));
+    }
+}
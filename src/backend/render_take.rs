@@ -1,34 +1,808 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use ab_glyph::{Font, FontArc, PxScale};
 use image::GenericImage;
 
-pub fn render_take(photos: Vec<image::RgbaImage>) -> image::RgbaImage {
-    let mut strip = image::load_from_memory(include_bytes!("../../assets/template.png"))
-        .expect("Failed to load strip image")
-        .to_rgba8();
-
-    // All frames are 2000x1333
-    // First frame
-    // 134, 134
-    // 134, 1600
-    // 134, 3066
-    // 134, 4532
-
-    assert!(photos.len() == 4, "Expected 4 photos");
-
-    for (i, photo) in photos.iter().enumerate() {
-        let x = 134;
-        let y = 134 + (i as u32 * 1466);
-        let resized_photo =
-            image::imageops::resize(photo, 2000, 1333, image::imageops::FilterType::Lanczos3);
-        strip.copy_from(&resized_photo, x, y).unwrap();
-    }
-
-    // Resize the strip to 1/3 of the original size
-    let strip = image::imageops::resize(
-        &strip,
-        (strip.width() / 3) as u32,
-        (strip.height() / 3) as u32,
-        image::imageops::FilterType::Lanczos3,
-    );
-
-    strip
+const CAPTION_FONT: &[u8] = include_bytes!("../../assets/fonts/Montserrat/Montserrat-Medium.ttf");
+/// Environment variable naming a font for the characters Montserrat doesn't
+/// have, like Japanese. Tried before `FALLBACK_FONT_PATHS`.
+const FALLBACK_FONT_VAR: &str = "CAPTION_FALLBACK_FONT";
+/// Where common CJK fonts are installed, tried in order if
+/// `CAPTION_FALLBACK_FONT` isn't set. Only the first one found is used.
+const FALLBACK_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+];
+
+/// Directory searched for templates at startup, overridable with the
+/// `TEMPLATE_DIR` environment variable. Every image in it needs a `.toml`
+/// file with the same stem describing its `StripLayout`.
+const DEFAULT_TEMPLATE_DIR: &str = "assets/templates";
+
+/// Width the photos are shrunk to for the GIF. Full size frames would make it
+/// tens of megabytes and take ages to quantize.
+const GIF_WIDTH: u32 = 600;
+/// How hard the GIF encoder works on its palettes, from 1 (best) to 30.
+const GIF_QUANTIZE_SPEED: i32 = 10;
+/// Resolution of the dye-sub printer. Prints are rendered at exactly its
+/// pixel size for a 2x6" strip, so nothing resamples them afterwards.
+pub const PRINT_DPI: u32 = 300;
+pub const PRINT_WIDTH: u32 = 2 * PRINT_DPI;
+pub const PRINT_HEIGHT: u32 = 6 * PRINT_DPI;
+
+/// Where the photos go on a strip template, in the template's own pixels.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct StripLayout {
+    /// One slot per photo, filled in the order the photos are taken. Photos
+    /// are cropped to the shape of their slot rather than stretched.
+    pub slots: Vec<Rect>,
+    /// Text printed over the template once the photos are in, like the
+    /// event name and the date.
+    #[serde(default)]
+    pub texts: Vec<TemplateText>,
+    /// How much the finished strip is scaled by. Templates are drawn at print
+    /// resolution, which is far more than a phone screen needs.
+    #[serde(default = "default_output_scale")]
+    pub output_scale: f32,
+}
+
+fn default_output_scale() -> f32 {
+    1.0 / 3.0
+}
+
+impl StripLayout {
+    pub fn photo_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Catches layouts that would only fail once a strip is rendered.
+    fn validate(&self) -> Result<(), String> {
+        if self.slots.is_empty() {
+            return Err("layout has no photo slots".to_string());
+        }
+        if self
+            .slots
+            .iter()
+            .any(|slot| slot.width == 0 || slot.height == 0)
+        {
+            return Err("photo slots need a width and height".to_string());
+        }
+        if self
+            .texts
+            .iter()
+            .any(|text| text.area.width == 0 || text.area.height == 0)
+        {
+            return Err("text areas need a width and height".to_string());
+        }
+        if !(self.output_scale > 0.0 && self.output_scale <= 1.0) {
+            return Err(format!(
+                "output_scale must be above 0 and at most 1, not {}",
+                self.output_scale
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A box on the template, in the template's own pixels.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+pub struct Rect {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A line of text on the template. It's left aligned in `area` and as tall
+/// as the area allows, shrinking if it would be too wide.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct TemplateText {
+    /// What's printed, with `{event}` replaced by the event name from setup
+    /// and `{date}` by the date.
+    pub text: String,
+    pub area: Rect,
+    /// As `#rrggbb` or `#rrggbbaa`. White if left out.
+    #[serde(default = "default_text_color", deserialize_with = "deserialize_color")]
+    pub color: image::Rgba<u8>,
+}
+
+fn default_text_color() -> image::Rgba<u8> {
+    image::Rgba([0xff, 0xff, 0xff, 0xff])
+}
+
+fn deserialize_color<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<image::Rgba<u8>, D::Error> {
+    let hex = <String as serde::Deserialize>::deserialize(deserializer)?;
+    let digits = hex.strip_prefix('#').unwrap_or(&hex);
+    if !matches!(digits.len(), 6 | 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(serde::de::Error::custom(format!(
+            "expected a color like #rrggbb, not {:?}",
+            hex
+        )));
+    }
+    let value = u32::from_str_radix(digits, 16).map_err(serde::de::Error::custom)?;
+    // opaque unless the alpha is given
+    let value = if digits.len() == 6 {
+        value << 8 | 0xff
+    } else {
+        value
+    };
+    Ok(image::Rgba(value.to_be_bytes()))
+}
+
+/// What the placeholders in a template's texts are filled in with.
+#[derive(Debug, Clone)]
+pub struct Caption {
+    /// Blank if setup didn't name the event.
+    pub event: String,
+    pub date: String,
+}
+
+impl Caption {
+    fn fill_in(&self, text: &str) -> String {
+        text.replace("{event}", &self.event)
+            .replace("{date}", &self.date)
+            .trim()
+            .to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplateImage {
+    Bundled(&'static [u8]),
+    File(PathBuf),
+}
+
+/// A strip design that can be picked in setup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StripTemplate {
+    name: String,
+    image: TemplateImage,
+    pub layout: StripLayout,
+}
+
+/// What a template's `.toml` file contains.
+#[derive(serde::Deserialize)]
+struct TemplateFile {
+    /// Shown in setup. Defaults to the file stem.
+    name: Option<String>,
+    #[serde(flatten)]
+    layout: StripLayout,
+}
+
+impl StripTemplate {
+    /// The event's four photo strip, compiled in so the booth works even
+    /// without a template directory. All frames are 2000x1333, with the first
+    /// one at 134, 134 and the rest below it at 134, 1600; 134, 3066 and
+    /// 134, 4532. The event name and date go in the empty band of the footer
+    /// below the left of the last photo.
+    pub fn bundled() -> Self {
+        Self {
+            name: "75th anniversary".to_string(),
+            image: TemplateImage::Bundled(include_bytes!("../../assets/templates/classic.png")),
+            layout: StripLayout {
+                slots: (0..4)
+                    .map(|i| Rect {
+                        left: 134,
+                        top: 134 + i * 1466,
+                        width: 2000,
+                        height: 1333,
+                    })
+                    .collect(),
+                texts: vec![TemplateText {
+                    text: "{event} {date}".to_string(),
+                    area: Rect {
+                        left: 134,
+                        top: 5875,
+                        width: 1000,
+                        height: 72,
+                    },
+                    color: default_text_color(),
+                }],
+                output_scale: default_output_scale(),
+            },
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn load_image(&self) -> Result<image::RgbaImage, image::ImageError> {
+        Ok(match &self.image {
+            TemplateImage::Bundled(bytes) => image::load_from_memory(bytes)?,
+            TemplateImage::File(path) => image::open(path)?,
+        }
+        .to_rgba8())
+    }
+
+    /// Reads the layout next to `image`, if `image` is an image.
+    fn from_file(image: &Path) -> Option<Result<Self, String>> {
+        image::ImageFormat::from_path(image).ok()?;
+        let layout_path = image.with_extension("toml");
+        let layout = std::fs::read_to_string(&layout_path)
+            .map_err(|err| format!("{}: {}", layout_path.display(), err))
+            .and_then(|contents| {
+                toml::from_str::<TemplateFile>(&contents)
+                    .map_err(|err| format!("{}: {}", layout_path.display(), err))
+            })
+            .and_then(|file| {
+                file.layout
+                    .validate()
+                    .map(|()| file)
+                    .map_err(|err| format!("{}: {}", layout_path.display(), err))
+            });
+        Some(layout.map(|file| Self {
+            name: file.name.unwrap_or_else(|| {
+                image
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            }),
+            image: TemplateImage::File(image.to_path_buf()),
+            layout: file.layout,
+        }))
+    }
+}
+
+impl Display for StripTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({} photos)", self.name, self.layout.photo_count())
+    }
+}
+
+/// Loads every template in the template directory, sorted by name. Templates
+/// that can't be read are logged and skipped, and the bundled template is
+/// used if none are left.
+pub fn load_templates() -> Vec<StripTemplate> {
+    let dir = std::env::var("TEMPLATE_DIR").unwrap_or_else(|_| DEFAULT_TEMPLATE_DIR.to_string());
+    let mut templates = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| StripTemplate::from_file(&entry.ok()?.path()))
+            .filter_map(|template| {
+                template
+                    .inspect_err(|err| log::warn!("Skipping template: {}", err))
+                    .ok()
+            })
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            log::warn!("Failed to read templates from {}: {}", dir, err);
+            Vec::new()
+        }
+    };
+    if templates.is_empty() {
+        templates.push(StripTemplate::bundled());
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    TemplateDecode(image::ImageError),
+    PhotoCountMismatch { expected: usize, actual: usize },
+    CopyOutOfBounds(image::ImageError),
+    FontLoad(ab_glyph::InvalidFont),
+    NoPhotos,
+    GifEncode(gif::EncodingError),
+    PngEncode(png::EncodingError),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TemplateDecode(err) => write!(f, "failed to load strip template: {}", err),
+            Self::PhotoCountMismatch { expected, actual } => {
+                write!(f, "expected {} photos, got {}", expected, actual)
+            }
+            Self::CopyOutOfBounds(err) => write!(f, "photo doesn't fit on the strip: {}", err),
+            Self::FontLoad(err) => write!(f, "failed to load caption font: {}", err),
+            Self::NoPhotos => write!(f, "no photos to render"),
+            Self::GifEncode(err) => write!(f, "failed to encode gif: {}", err),
+            Self::PngEncode(err) => write!(f, "failed to encode png: {}", err),
+        }
+    }
+}
+
+/// A template decoded and ready to render strips on, so the slow decoding is
+/// done once rather than for every strip.
+pub struct RenderAssets {
+    template: image::RgbaImage,
+    layout: StripLayout,
+    /// Montserrat, then the fallback font if one was found.
+    fonts: Vec<FontArc>,
+}
+
+impl RenderAssets {
+    /// Decodes `template` and the caption fonts. Blocks for a while on big
+    /// templates.
+    pub fn load(template: &StripTemplate) -> Result<Self, RenderError> {
+        let started = Instant::now();
+        let image = template.load_image().map_err(RenderError::TemplateDecode)?;
+        let font = FontArc::try_from_slice(CAPTION_FONT).map_err(RenderError::FontLoad)?;
+        let fonts = std::iter::once(font).chain(load_fallback_font()).collect();
+        log::info!(
+            "Decoded template {} in {:?}",
+            template.name(),
+            started.elapsed()
+        );
+        Ok(Self {
+            template: image,
+            layout: template.layout.clone(),
+            fonts,
+        })
+    }
+}
+
+/// The first font in `CAPTION_FALLBACK_FONT` or `FALLBACK_FONT_PATHS` that
+/// can be read. Without one, characters Montserrat lacks are left out.
+fn load_fallback_font() -> Option<FontArc> {
+    let from_env = std::env::var(FALLBACK_FONT_VAR).ok();
+    let font = from_env
+        .iter()
+        .map(String::as_str)
+        .chain(FALLBACK_FONT_PATHS.iter().copied())
+        .find_map(|path| {
+            let bytes = std::fs::read(path).ok()?;
+            // the first face of a collection is the regular one
+            FontArc::try_from_vec(bytes)
+                .inspect_err(|err| log::warn!("Failed to load font {}: {}", path, err))
+                .ok()
+                .inspect(|_| log::info!("Using {} for characters Montserrat lacks", path))
+        });
+    if font.is_none() {
+        log::warn!(
+            "No fallback font found, so text like Japanese can't be printed. Set {} to a font with it.",
+            FALLBACK_FONT_VAR
+        );
+    }
+    font
+}
+
+/// The same strip at two sizes.
+pub struct RenderedStrip {
+    /// Scaled by the layout's output scale, for the screen and the upload.
+    pub screen: image::RgbaImage,
+    /// Exactly `PRINT_WIDTH` by `PRINT_HEIGHT`, for the printer.
+    pub print: image::RgbaImage,
+}
+
+pub fn render_take(
+    photos: Vec<image::RgbaImage>,
+    assets: &RenderAssets,
+    caption: Option<&Caption>,
+) -> Result<RenderedStrip, RenderError> {
+    let layout = &assets.layout;
+    // copying the pixels is much quicker than decoding them again
+    let mut strip = assets.template.clone();
+
+    if photos.len() != layout.photo_count() {
+        return Err(RenderError::PhotoCountMismatch {
+            expected: layout.photo_count(),
+            actual: photos.len(),
+        });
+    }
+
+    // each photo's resize is independent and slow, so they get a thread each
+    let resized_photos: Vec<_> = std::thread::scope(|scope| {
+        let workers: Vec<_> = photos
+            .iter()
+            .zip(&layout.slots)
+            .map(|(photo, slot)| {
+                scope.spawn(|| fill_slot(photo, slot, image::imageops::FilterType::Lanczos3))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("resizing a photo panicked"))
+            .collect()
+    });
+    for (resized_photo, slot) in resized_photos.iter().zip(&layout.slots) {
+        strip
+            .copy_from(resized_photo, slot.left, slot.top)
+            .map_err(RenderError::CopyOutOfBounds)?;
+    }
+
+    draw_caption(&mut strip, assets, caption);
+
+    let (width, height) = screen_size(&strip, layout);
+    let template_ratio = strip.width() as f32 / strip.height() as f32;
+    let print_ratio = PRINT_WIDTH as f32 / PRINT_HEIGHT as f32;
+    if (template_ratio / print_ratio - 1.0).abs() > 0.01 {
+        log::warn!("The template isn't 2x6, so the print of it will be stretched to fit the paper");
+    }
+    // both come from the full size strip so neither is resampled twice
+    let (screen, print) = std::thread::scope(|scope| {
+        let print = scope.spawn(|| downscale(&strip, PRINT_WIDTH, PRINT_HEIGHT));
+        let screen = downscale(&strip, width, height);
+        (
+            screen,
+            print.join().expect("downscaling the print panicked"),
+        )
+    });
+
+    Ok(RenderedStrip { screen, print })
+}
+
+/// `render_take` the way it was before the photos were resized in parallel
+/// and the downscale took a shortcut: one photo at a time, and Lanczos over
+/// the whole strip. Kept to check and benchmark `render_take` against.
+#[doc(hidden)]
+pub fn render_take_single_threaded(
+    photos: Vec<image::RgbaImage>,
+    assets: &RenderAssets,
+    caption: Option<&Caption>,
+) -> Result<RenderedStrip, RenderError> {
+    let layout = &assets.layout;
+    let mut strip = assets.template.clone();
+
+    if photos.len() != layout.photo_count() {
+        return Err(RenderError::PhotoCountMismatch {
+            expected: layout.photo_count(),
+            actual: photos.len(),
+        });
+    }
+
+    for (photo, slot) in photos.iter().zip(&layout.slots) {
+        let resized_photo = fill_slot(photo, slot, image::imageops::FilterType::Lanczos3);
+        strip
+            .copy_from(&resized_photo, slot.left, slot.top)
+            .map_err(RenderError::CopyOutOfBounds)?;
+    }
+
+    draw_caption(&mut strip, assets, caption);
+
+    let (width, height) = screen_size(&strip, layout);
+    let filter = image::imageops::FilterType::Lanczos3;
+    Ok(RenderedStrip {
+        screen: image::imageops::resize(&strip, width, height, filter),
+        print: image::imageops::resize(&strip, PRINT_WIDTH, PRINT_HEIGHT, filter),
+    })
+}
+
+/// Draws the layout's text at full size, so it's downscaled with everything
+/// else.
+fn draw_caption(strip: &mut image::RgbaImage, assets: &RenderAssets, caption: Option<&Caption>) {
+    let Some(caption) = caption else {
+        return;
+    };
+    for text in &assets.layout.texts {
+        draw_text(
+            strip,
+            &caption.fill_in(&text.text),
+            &text.area,
+            text.color,
+            &assets.fonts,
+        );
+    }
+}
+
+/// The size of the strip shown on screen and uploaded.
+fn screen_size(strip: &image::RgbaImage, layout: &StripLayout) -> (u32, u32) {
+    (
+        ((strip.width() as f32 * layout.output_scale).round() as u32).max(1),
+        ((strip.height() as f32 * layout.output_scale).round() as u32).max(1),
+    )
+}
+
+/// Resizes `strip` to exactly `width` by `height`. Lanczos over the whole
+/// strip is slow, so most of the way is done by averaging boxes of pixels
+/// and Lanczos only does the last halving, which is where it shows.
+fn downscale(strip: &image::RgbaImage, width: u32, height: u32) -> image::RgbaImage {
+    if width * 2 < strip.width() && height * 2 < strip.height() {
+        let strip = image::imageops::thumbnail(strip, width * 2, height * 2);
+        image::imageops::resize(&strip, width, height, image::imageops::FilterType::Lanczos3)
+    } else {
+        image::imageops::resize(strip, width, height, image::imageops::FilterType::Lanczos3)
+    }
+}
+
+/// Encodes a print as a PNG that says it's `PRINT_DPI`, so it comes out at
+/// its real size instead of whatever the printer assumes.
+pub fn encode_print_png(print: &image::RgbaImage) -> Result<Vec<u8>, RenderError> {
+    // PNG stores resolution per meter
+    let pixels_per_meter = (PRINT_DPI as f64 / 0.0254).round() as u32;
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, print.width(), print.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: pixels_per_meter,
+            yppu: pixels_per_meter,
+            unit: png::Unit::Meter,
+        }));
+        encoder
+            .write_header()
+            .and_then(|mut writer| {
+                writer.write_image_data(print.as_raw())?;
+                writer.finish()
+            })
+            .map_err(RenderError::PngEncode)?;
+    }
+    Ok(buffer)
+}
+
+/// A rough, captionless strip `width` pixels wide, for the guests to compare
+/// templates with. The photos should already be shrunk to about the size
+/// they'll be, since they're resized with a cheap filter.
+pub fn render_thumbnail(
+    photos: &[image::RgbaImage],
+    assets: &RenderAssets,
+    width: u32,
+) -> Result<image::RgbaImage, RenderError> {
+    let layout = &assets.layout;
+    if photos.len() != layout.photo_count() {
+        return Err(RenderError::PhotoCountMismatch {
+            expected: layout.photo_count(),
+            actual: photos.len(),
+        });
+    }
+
+    let scale = width as f32 / assets.template.width() as f32;
+    let scaled = |value: u32| ((value as f32 * scale).round() as u32).max(1);
+    let mut thumbnail =
+        image::imageops::thumbnail(&assets.template, width, scaled(assets.template.height()));
+    for (photo, slot) in photos.iter().zip(&layout.slots) {
+        let (left, top) = (scaled(slot.left), scaled(slot.top));
+        // rounding can push a slot at the edge a pixel off the thumbnail
+        let slot = Rect {
+            left,
+            top,
+            width: scaled(slot.width).min(thumbnail.width().saturating_sub(left).max(1)),
+            height: scaled(slot.height).min(thumbnail.height().saturating_sub(top).max(1)),
+        };
+        let resized_photo = fill_slot(photo, &slot, image::imageops::FilterType::Triangle);
+        thumbnail
+            .copy_from(&resized_photo, slot.left, slot.top)
+            .map_err(RenderError::CopyOutOfBounds)?;
+    }
+    Ok(thumbnail)
+}
+
+/// Crops the middle of `photo` to the shape of `slot`, then resizes it to fill
+/// the slot exactly.
+fn fill_slot(
+    photo: &image::RgbaImage,
+    slot: &Rect,
+    filter: image::imageops::FilterType,
+) -> image::RgbaImage {
+    // compared as cross products to stay in integers
+    let (crop_width, crop_height) =
+        if photo.width() as u64 * slot.height as u64 > slot.width as u64 * photo.height() as u64 {
+            // too wide, so the sides are cut off
+            (
+                (photo.height() as u64 * slot.width as u64 / slot.height as u64) as u32,
+                photo.height(),
+            )
+        } else {
+            // too tall, so the top and bottom are cut off
+            (
+                photo.width(),
+                (photo.width() as u64 * slot.height as u64 / slot.width as u64) as u32,
+            )
+        };
+    let cropped = image::imageops::crop_imm(
+        photo,
+        (photo.width() - crop_width) / 2,
+        (photo.height() - crop_height) / 2,
+        crop_width,
+        crop_height,
+    )
+    .to_image();
+    image::imageops::resize(&cropped, slot.width, slot.height, filter)
+}
+
+/// Encodes `photos` as a looping GIF, showing each for `frame_delay`. The
+/// photos are shrunk to the size of the first one scaled to `GIF_WIDTH`.
+pub fn render_gif(
+    photos: &[image::RgbaImage],
+    frame_delay: Duration,
+) -> Result<Vec<u8>, RenderError> {
+    let first = photos.first().ok_or(RenderError::NoPhotos)?;
+    let width = GIF_WIDTH.min(first.width());
+    let height = (first.height() as u64 * width as u64 / first.width() as u64) as u32;
+    // GIF delays are in hundredths of a second
+    let delay = (frame_delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut buffer, width as u16, height as u16, &[])
+            .map_err(RenderError::GifEncode)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(RenderError::GifEncode)?;
+        for photo in photos {
+            let mut pixels = image::imageops::resize(
+                photo,
+                width,
+                height,
+                image::imageops::FilterType::Triangle,
+            )
+            .into_raw();
+            let mut frame = gif::Frame::from_rgba_speed(
+                width as u16,
+                height as u16,
+                &mut pixels,
+                GIF_QUANTIZE_SPEED,
+            );
+            frame.delay = delay;
+            encoder
+                .write_frame(&frame)
+                .map_err(RenderError::GifEncode)?;
+        }
+    }
+    Ok(buffer)
+}
+
+fn draw_text(
+    strip: &mut image::RgbaImage,
+    text: &str,
+    area: &Rect,
+    color: image::Rgba<u8>,
+    fonts: &[FontArc],
+) {
+    let runs = font_runs(text, fonts);
+    let size = |scale: PxScale| {
+        runs.iter()
+            .map(|(font, run)| imageproc::drawing::text_size(scale, *font, run))
+            .fold((0, 0), |(width, height), (run_width, run_height)| {
+                (width + run_width, height.max(run_height))
+            })
+    };
+
+    // Shrink long text until it fits the width of the area
+    let mut scale = PxScale::from(area.height as f32);
+    let (width, _) = size(scale);
+    if width > area.width {
+        scale = PxScale::from(area.height as f32 * area.width as f32 / width as f32);
+    }
+    let (_, height) = size(scale);
+
+    let mut x = area.left as i32;
+    let y = (area.top + area.height.saturating_sub(height) / 2) as i32;
+    for (font, run) in runs {
+        imageproc::drawing::draw_text_mut(strip, color, x, y, scale, font, run);
+        x += imageproc::drawing::text_size(scale, font, run).0 as i32;
+    }
+}
+
+/// Splits `text` into the longest runs that one font can draw, picking the
+/// first of `fonts` that has each character. Spaces stay with the run they're
+/// in, since every font has them.
+fn font_runs<'a>(text: &'a str, fonts: &'a [FontArc]) -> Vec<(&'a FontArc, &'a str)> {
+    let mut runs = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    for (i, c) in text.char_indices() {
+        let font = match current {
+            Some((_, font)) if c.is_whitespace() => font,
+            _ => fonts
+                .iter()
+                .position(|font| font.glyph_id(c).0 != 0)
+                .unwrap_or_else(|| {
+                    log::warn!("No font can draw {:?}, leaving it out", c);
+                    0
+                }),
+        };
+        match current {
+            Some((_, current_font)) if current_font == font => {}
+            Some((start, current_font)) => {
+                runs.push((&fonts[current_font], &text[start..i]));
+                current = Some((i, font));
+            }
+            None => current = Some((i, font)),
+        }
+    }
+    if let Some((start, font)) = current {
+        runs.push((&fonts[font], &text[start..]));
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_is_exactly_the_paper_size() {
+        let template = StripTemplate::bundled();
+        let assets = RenderAssets::load(&template).unwrap();
+        let photos = (0..template.layout.photo_count())
+            .map(|_| image::RgbaImage::from_pixel(600, 400, image::Rgba([90, 120, 150, 255])))
+            .collect();
+        let caption = Caption {
+            event: "Winter Festival".to_string(),
+            date: "2025-01-01".to_string(),
+        };
+        let rendered = render_take(photos, &assets, Some(&caption)).unwrap();
+        assert_eq!(
+            (rendered.print.width(), rendered.print.height()),
+            (PRINT_WIDTH, PRINT_HEIGHT)
+        );
+    }
+
+    /// A camera-sized photo with gradients and hard edges, different for
+    /// each `seed`.
+    fn synthetic_photo(seed: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(4000, 2667, |x, y| {
+            let square = ((x + seed * 50) / 40 + y / 40) % 2;
+            image::Rgba([
+                (x * 255 / 4000) as u8,
+                (y * 255 / 2667) as u8,
+                (square * 200 + seed * 10) as u8,
+                255,
+            ])
+        })
+    }
+
+    /// The mean and the largest difference between the channels of two
+    /// images of the same size.
+    fn difference(a: &image::RgbaImage, b: &image::RgbaImage) -> (f64, u8) {
+        assert_eq!(a.dimensions(), b.dimensions());
+        let (sum, max) =
+            a.as_raw()
+                .iter()
+                .zip(b.as_raw())
+                .fold((0u64, 0u8), |(sum, max), (a, b)| {
+                    let diff = a.abs_diff(*b);
+                    (sum + diff as u64, max.max(diff))
+                });
+        (sum as f64 / a.as_raw().len() as f64, max)
+    }
+
+    #[test]
+    fn render_matches_the_single_threaded_render() {
+        let template = StripTemplate::bundled();
+        let assets = RenderAssets::load(&template).unwrap();
+        let photos: Vec<_> = (0..4).map(synthetic_photo).collect();
+        let caption = Caption {
+            event: "Winter Festival".to_string(),
+            date: "2025-01-01".to_string(),
+        };
+        let rendered = render_take(photos.clone(), &assets, Some(&caption)).unwrap();
+        let reference = render_take_single_threaded(photos, &assets, Some(&caption)).unwrap();
+        for (what, rendered, reference) in [
+            ("screen", &rendered.screen, &reference.screen),
+            ("print", &rendered.print, &reference.print),
+        ] {
+            // the hard edges between squares can be far off, but only there
+            let (mean, max) = difference(rendered, reference);
+            assert!(
+                mean < 3.0,
+                "{} is off by {:.2} on average, at most {}",
+                what,
+                mean,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn print_png_says_it_is_300_dpi() {
+        let print = image::RgbaImage::from_fn(PRINT_WIDTH, PRINT_HEIGHT, |x, y| {
+            image::Rgba([(x % 256) as u8, (y % 256) as u8, 64, 255])
+        });
+        let encoded = encode_print_png(&print).unwrap();
+
+        let reader = png::Decoder::new(std::io::Cursor::new(&encoded))
+            .read_info()
+            .unwrap();
+        let dims = reader.info().pixel_dims.expect("no pHYs chunk");
+        assert_eq!(dims.unit, png::Unit::Meter);
+        assert_eq!(dims.xppu, dims.yppu);
+        let dpi = dims.xppu as f64 * 0.0254;
+        assert_eq!(dpi.round() as u32, PRINT_DPI);
+
+        let decoded = image::load_from_memory(&encoded).unwrap().into_rgba8();
+        assert_eq!(decoded, print);
+    }
 }
@@ -1,28 +1,505 @@
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+    hash::{BuildHasher, Hasher, RandomState},
+    io::Cursor,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
 
+use iced::futures::channel::mpsc;
 use image::RgbaImage;
 
+#[cfg(feature = "server_local")]
+pub mod local;
+#[cfg(feature = "mock_backend")]
+pub mod mock;
+#[cfg(not(any(feature = "server_local", feature = "mock_backend")))]
 pub mod server;
 
+/// How many times a request is retried after a transient failure, if
+/// `UPLOAD_RETRIES` doesn't say.
+const DEFAULT_RETRY_LIMIT: u32 = 4;
+const RETRY_LIMIT_VAR: &str = "UPLOAD_RETRIES";
+/// Delay before the first retry, doubled for every retry after it.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Longest a retry waits, even if the server asks for longer, since the
+/// guests are waiting on it.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Chooses how the individual photos are encoded for upload, as `png`,
+/// `webp`, `jpeg` or `jpeg:<quality>`.
+const PHOTO_ENCODING_VAR: &str = "PHOTO_ENCODING";
+
+/// How an image is encoded before it's uploaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageEncoding {
+    Png,
+    /// `quality` is from 1 to 100.
+    Jpeg {
+        quality: u8,
+    },
+    /// Lossless, since that's all the image crate can write. Smaller than PNG
+    /// but much bigger than JPEG.
+    WebP,
+}
+
+impl ImageEncoding {
+    /// Small enough to upload quickly on slow Wi-Fi, with no artifacts anyone
+    /// would notice on a phone.
+    pub const DEFAULT_PHOTOS: Self = Self::Jpeg { quality: 85 };
+
+    /// The encoding in `PHOTO_ENCODING`, or `DEFAULT_PHOTOS` if it's unset
+    /// or can't be read.
+    pub fn for_photos() -> Self {
+        let Ok(value) = std::env::var(PHOTO_ENCODING_VAR) else {
+            return Self::DEFAULT_PHOTOS;
+        };
+        Self::parse(&value).unwrap_or_else(|| {
+            log::warn!(
+                "Ignoring {}={:?}, expected png, webp, jpeg or jpeg:<1-100>",
+                PHOTO_ENCODING_VAR,
+                value
+            );
+            Self::DEFAULT_PHOTOS
+        })
+    }
+
+    /// Reads an encoding written the way `PHOTO_ENCODING` takes it.
+    fn parse(value: &str) -> Option<Self> {
+        let normalized = value.trim().to_lowercase();
+        match normalized.split_once(':') {
+            None => match normalized.as_str() {
+                "png" => Some(Self::Png),
+                "webp" => Some(Self::WebP),
+                "jpeg" | "jpg" => Some(Self::DEFAULT_PHOTOS),
+                _ => None,
+            },
+            Some(("jpeg" | "jpg", quality)) => quality
+                .parse()
+                .ok()
+                .filter(|quality| (1..=100).contains(quality))
+                .map(|quality| Self::Jpeg { quality }),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg { .. } => "jpg",
+            Self::WebP => "webp",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg { .. } => "image/jpeg",
+            Self::WebP => "image/webp",
+        }
+    }
+
+    /// Encodes `image`, dropping the alpha channel for JPEG, which has none.
+    pub fn encode(self, image: &RgbaImage) -> Result<Vec<u8>, image::ImageError> {
+        let mut encoded = Vec::new();
+        match self {
+            Self::Png => image.write_to(&mut Cursor::new(&mut encoded), image::ImageFormat::Png)?,
+            Self::Jpeg { quality } => {
+                let rgb = image::DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut encoded, quality)
+                    .encode_image(&rgb)?;
+            }
+            Self::WebP => {
+                image.write_to(&mut Cursor::new(&mut encoded), image::ImageFormat::WebP)?
+            }
+        }
+        Ok(encoded)
+    }
+}
+
+/// Lets a backend report how far along an upload or email is. Clones share
+/// their count, so concurrent steps can each report when they finish.
+/// Backends that never report leave the UI to estimate instead.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    sender: mpsc::UnboundedSender<f32>,
+    done: Arc<AtomicUsize>,
+    steps: Arc<AtomicUsize>,
+}
+
+impl Progress {
+    /// A reporter and the fractions from 0.0 to 1.0 it sends.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<f32>) {
+        let (sender, receiver) = mpsc::unbounded();
+        (
+            Self {
+                sender,
+                done: Arc::new(AtomicUsize::new(0)),
+                steps: Arc::new(AtomicUsize::new(1)),
+            },
+            receiver,
+        )
+    }
+
+    /// Sets how many steps there are, before any of them are done.
+    pub fn set_steps(&self, steps: usize) {
+        self.steps.store(steps.max(1), Ordering::Relaxed);
+    }
+
+    /// Marks one more step as done.
+    pub fn step_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let steps = self.steps.load(Ordering::Relaxed).max(done);
+        // nobody is listening anymore if the UI moved on
+        let _ = self.sender.unbounded_send(done as f32 / steps as f32);
+    }
+}
+
+/// Whether the photos reached one of the addresses they were emailed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailDelivery {
+    pub address: String,
+    pub delivered: bool,
+}
+
+/// What to do after a failed attempt at a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retry {
+    /// Trying again won't help, or might do the same thing twice.
+    Never,
+    /// Try again after the usual exponential backoff.
+    Backoff,
+    /// Try again after the delay the server asked for.
+    After(Duration),
+}
+
+/// `UPLOAD_RETRIES`, or `DEFAULT_RETRY_LIMIT` if it's unset or invalid.
+fn retry_limit() -> u32 {
+    static LIMIT: OnceLock<u32> = OnceLock::new();
+    *LIMIT.get_or_init(|| match std::env::var(RETRY_LIMIT_VAR) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            log::warn!(
+                "Ignoring {}={:?}, expected a number of retries",
+                RETRY_LIMIT_VAR,
+                value
+            );
+            DEFAULT_RETRY_LIMIT
+        }),
+        Err(_) => DEFAULT_RETRY_LIMIT,
+    })
+}
+
+/// Runs `attempt` until it succeeds, `classify` says its error is final, or
+/// it has been retried `UPLOAD_RETRIES` times. The backoff doubles every
+/// retry and is jittered so parallel uploads don't all retry at once.
+pub async fn with_retries<T, E: Display, Fut: Future<Output = Result<T, E>>>(
+    what: &str,
+    classify: impl Fn(&E) -> Retry,
+    attempt: impl FnMut() -> Fut,
+) -> Result<T, E> {
+    with_retry_limit(retry_limit(), what, classify, attempt).await
+}
+
+/// `with_retries`, giving up after `limit` retries.
+async fn with_retry_limit<T, E: Display, Fut: Future<Output = Result<T, E>>>(
+    limit: u32,
+    what: &str,
+    classify: impl Fn(&E) -> Retry,
+    mut attempt: impl FnMut() -> Fut,
+) -> Result<T, E> {
+    let mut backoff = RETRY_BASE_DELAY;
+    let mut retries = 0;
+    loop {
+        let err = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        let delay = match classify(&err) {
+            _ if retries >= limit => return Err(err),
+            Retry::Never => return Err(err),
+            Retry::Backoff => jitter(backoff),
+            Retry::After(delay) => delay,
+        }
+        .min(RETRY_MAX_DELAY);
+        log::warn!("{} failed, retrying in {:?}: {}", what, delay, err);
+        tokio::time::sleep(delay).await;
+        backoff *= 2;
+        retries += 1;
+    }
+}
+
+/// Somewhere between half of `delay` and all of it.
+fn jitter(delay: Duration) -> Duration {
+    // every RandomState is seeded differently, which is random enough here
+    let random = RandomState::new().build_hasher().finish();
+    delay.mul_f64(0.5 + random as f64 / u64::MAX as f64 / 2.0)
+}
+
+/// How long a response's `Retry-After` header asks to wait, whether it's in
+/// seconds or an HTTP date.
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // a date in the past means right away
+    Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
 pub trait ServerBackend: Clone + Send {
     type Error: Debug + Display + Send;
-    type UploadHandle: Debug + Send + Clone;
+    /// Saved with sessions that were uploaded from the queue but not
+    /// emailed yet, so it has to survive a restart.
+    type UploadHandle: Debug + Send + Clone + serde::Serialize + serde::de::DeserializeOwned;
 
     fn new() -> Result<Self, Self::Error>;
 
+    /// Uploads a session. `print` is the strip as a print-ready PNG, if it
+    /// could be made.
     fn upload_photo(
         self,
         strip: RgbaImage,
+        print: Option<Vec<u8>>,
         photos: Vec<RgbaImage>,
+        gif: Option<Vec<u8>>,
+        progress: Progress,
     ) -> impl std::future::Future<Output = Result<Self::UploadHandle, Self::Error>> + Send;
 
     fn send_email(
         self,
         handle: Self::UploadHandle,
         emails: Vec<String>,
-    ) -> impl std::future::Future<Output = Result<bool, Self::Error>> + Send;
+        progress: Progress,
+    ) -> impl std::future::Future<Output = Result<Vec<EmailDelivery>, Self::Error>> + Send;
 
     fn get_link(self, handle: Self::UploadHandle) -> String;
 }
 
+#[cfg(all(feature = "server_local", feature = "mock_backend"))]
+compile_error!(
+    "feature \"server_local\" and feature \"mock_backend\" cannot be enabled at the same time"
+);
+
+#[cfg(not(any(feature = "server_local", feature = "mock_backend")))]
 pub type DefaultServerBackend = server::SupabaseBackend;
+#[cfg(feature = "server_local")]
+pub type DefaultServerBackend = local::LocalDiskBackend;
+#[cfg(feature = "mock_backend")]
+pub type DefaultServerBackend = mock::MockBackend;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A failed response, as a backend would see it.
+    #[derive(Debug)]
+    struct Failed(reqwest::StatusCode, Option<Duration>);
+
+    impl Display for Failed {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    fn retry_unavailable(err: &Failed) -> Retry {
+        match err {
+            Failed(reqwest::StatusCode::SERVICE_UNAVAILABLE, Some(delay)) => Retry::After(*delay),
+            Failed(reqwest::StatusCode::SERVICE_UNAVAILABLE, None) => Retry::Backoff,
+            _ => Retry::Never,
+        }
+    }
+
+    async fn get(client: &reqwest::Client, url: &str) -> Result<String, Failed> {
+        let response = client.get(url).send().await.unwrap();
+        if response.status().is_success() {
+            Ok(response.text().await.unwrap())
+        } else {
+            Err(Failed(response.status(), retry_after(response.headers())))
+        }
+    }
+
+    #[test]
+    fn photo_encoding_is_parsed() {
+        assert_eq!(ImageEncoding::parse("png"), Some(ImageEncoding::Png));
+        assert_eq!(ImageEncoding::parse(" WebP "), Some(ImageEncoding::WebP));
+        for jpeg in ["jpeg", "jpg", "JPEG"] {
+            assert_eq!(
+                ImageEncoding::parse(jpeg),
+                Some(ImageEncoding::DEFAULT_PHOTOS)
+            );
+        }
+        assert_eq!(
+            ImageEncoding::parse("jpeg:1"),
+            Some(ImageEncoding::Jpeg { quality: 1 })
+        );
+        assert_eq!(
+            ImageEncoding::parse("jpg:100"),
+            Some(ImageEncoding::Jpeg { quality: 100 })
+        );
+    }
+
+    #[test]
+    fn photo_encoding_rejects_anything_else() {
+        for value in [
+            "",
+            "gif",
+            "jpeg:0",
+            "jpeg:101",
+            "jpeg:",
+            "jpeg:high",
+            "jpeg:-5",
+            "png:90",
+        ] {
+            assert_eq!(ImageEncoding::parse(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn encoded_photos_decode_at_the_same_size() {
+        let photo = RgbaImage::from_fn(64, 48, |x, y| {
+            image::Rgba([(x * 4) as u8, (y * 5) as u8, 128, 255])
+        });
+        for (encoding, format) in [
+            (ImageEncoding::Png, image::ImageFormat::Png),
+            (
+                ImageEncoding::Jpeg { quality: 85 },
+                image::ImageFormat::Jpeg,
+            ),
+            (ImageEncoding::WebP, image::ImageFormat::WebP),
+        ] {
+            let encoded = encoding.encode(&photo).unwrap();
+            assert_eq!(image::guess_format(&encoded).unwrap(), format);
+            assert_eq!(
+                image::ImageFormat::from_extension(encoding.extension()),
+                Some(format)
+            );
+            assert_eq!(encoding.mime_type(), format.to_mime_type());
+            let decoded = image::load_from_memory_with_format(&encoded, format).unwrap();
+            assert_eq!(
+                (decoded.width(), decoded.height()),
+                (64, 48),
+                "{:?}",
+                encoding
+            );
+            if encoding != (ImageEncoding::Jpeg { quality: 85 }) {
+                assert_eq!(decoded.into_rgba8(), photo, "{:?} is lossless", encoding);
+            }
+        }
+    }
+
+    fn headers(retry_after: &str) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, retry_after.parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn request_is_retried_until_it_succeeds() {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("uploaded"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let url = server.uri();
+        let result = with_retries("Uploading", retry_unavailable, || get(&client, &url)).await;
+        assert_eq!(result.unwrap(), "uploaded");
+    }
+
+    #[tokio::test]
+    async fn retries_stop_at_the_limit() {
+        let mut attempts = 0;
+        let result: Result<(), _> = with_retry_limit(3, "Uploading", retry_unavailable, || {
+            attempts += 1;
+            async {
+                Err(Failed(
+                    reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                    Some(Duration::ZERO),
+                ))
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 4, "the first attempt and 3 retries");
+    }
+
+    #[tokio::test]
+    async fn final_errors_are_not_retried() {
+        let mut attempts = 0;
+        let result: Result<(), _> = with_retry_limit(3, "Uploading", retry_unavailable, || {
+            attempts += 1;
+            async { Err(Failed(reqwest::StatusCode::FORBIDDEN, None)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn retry_after_reads_seconds() {
+        assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&headers(" 0 ")), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_reads_http_dates() {
+        let date = chrono::Utc::now() + chrono::TimeDelta::seconds(90);
+        let delay = retry_after(&headers(
+            &date.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        ))
+        .unwrap();
+        // the date is only to the second
+        assert!(
+            (Duration::from_secs(88)..=Duration::from_secs(90)).contains(&delay),
+            "{:?}",
+            delay
+        );
+
+        let past = chrono::Utc::now() - chrono::TimeDelta::seconds(90);
+        assert_eq!(
+            retry_after(&headers(
+                &past.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+            )),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn retry_after_ignores_anything_else() {
+        assert_eq!(retry_after(&reqwest::header::HeaderMap::new()), None);
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&headers("-5")), None);
+    }
+
+    #[test]
+    fn jitter_stays_between_half_and_all_of_the_delay() {
+        let delay = Duration::from_secs(2);
+        for _ in 0..100 {
+            let jittered = jitter(delay);
+            assert!((delay / 2..=delay).contains(&jittered), "{:?}", jittered);
+        }
+    }
+}
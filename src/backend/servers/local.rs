@@ -0,0 +1,285 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+
+use super::{EmailDelivery, Progress};
+
+/// Folder created in the pictures directory if `LOCAL_OUTPUT_DIR` isn't set.
+const DEFAULT_OUTPUT_FOLDER: &str = "photo-booth";
+
+/// Saves every session into its own timestamped folder on disk, for venues
+/// without internet. The folder is `LOCAL_OUTPUT_DIR` if it's set, and
+/// `DEFAULT_OUTPUT_FOLDER` in the pictures directory otherwise.
+#[derive(Debug, Clone)]
+pub struct LocalDiskBackend {
+    root: PathBuf,
+}
+
+/// The folder a session was saved in.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UploadHandle {
+    pub dir: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum LocalDiskBackendError {
+    NoOutputDir,
+    Io(std::io::Error),
+    ImageEncode(image::ImageError),
+}
+
+impl Display for LocalDiskBackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoOutputDir => write!(
+                f,
+                "no pictures directory to save to, set LOCAL_OUTPUT_DIR instead"
+            ),
+            Self::Io(err) => write!(f, "i/o error: {}", err),
+            Self::ImageEncode(err) => write!(f, "image encode error: {}", err),
+        }
+    }
+}
+
+impl super::ServerBackend for LocalDiskBackend {
+    type Error = LocalDiskBackendError;
+    type UploadHandle = UploadHandle;
+
+    fn new() -> Result<Self, Self::Error> {
+        let root = match std::env::var_os("LOCAL_OUTPUT_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::picture_dir()
+                .ok_or(LocalDiskBackendError::NoOutputDir)?
+                .join(DEFAULT_OUTPUT_FOLDER),
+        };
+        log::info!("Saving photos to {}", root.display());
+        Ok(LocalDiskBackend { root })
+    }
+
+    /// Saves the strip as strip.png, the print (if any) as strip_print.png,
+    /// the GIF (if any) as animation.gif, and the individual photos as
+    /// photo_1.png, photo_2.png, etc. in a new folder named after the
+    /// current time. See `create_session_dir`.
+    async fn upload_photo(
+        self,
+        strip: RgbaImage,
+        print: Option<Vec<u8>>,
+        photos: Vec<RgbaImage>,
+        gif: Option<Vec<u8>>,
+        progress: Progress,
+    ) -> Result<UploadHandle, Self::Error> {
+        // one step per file
+        progress.set_steps(
+            1 + usize::from(print.is_some()) + usize::from(gif.is_some()) + photos.len(),
+        );
+        let root = self.root;
+        // encoding the PNGs takes a while
+        tokio::task::spawn_blocking(move || {
+            let dir = create_session_dir(&root).map_err(LocalDiskBackendError::Io)?;
+            save_png(&strip, &dir.join("strip.png"))?;
+            progress.step_done();
+            if let Some(print) = print {
+                std::fs::write(dir.join("strip_print.png"), print)
+                    .map_err(LocalDiskBackendError::Io)?;
+                progress.step_done();
+            }
+            if let Some(gif) = gif {
+                std::fs::write(dir.join("animation.gif"), gif)
+                    .map_err(LocalDiskBackendError::Io)?;
+                progress.step_done();
+            }
+            for (i, photo) in photos.iter().enumerate() {
+                save_png(photo, &dir.join(format!("photo_{}.png", i + 1)))?;
+                progress.step_done();
+            }
+            log::debug!("Saved photos to {}", dir.display());
+            Ok(UploadHandle { dir })
+        })
+        .await
+        .expect("saving task terminated unexpectedly")
+    }
+
+    /// Adds the addresses to emails.txt in the session's folder, so they can
+    /// be sent the photos once there's internet again.
+    async fn send_email(
+        self,
+        handle: UploadHandle,
+        emails: Vec<String>,
+        progress: Progress,
+    ) -> Result<Vec<EmailDelivery>, Self::Error> {
+        progress.set_steps(1);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(handle.dir.join("emails.txt"))
+            .map_err(LocalDiskBackendError::Io)?;
+        for email in &emails {
+            writeln!(file, "{}", email).map_err(LocalDiskBackendError::Io)?;
+        }
+        progress.step_done();
+        Ok(emails
+            .into_iter()
+            .map(|address| EmailDelivery {
+                address,
+                delivered: true,
+            })
+            .collect())
+    }
+
+    fn get_link(self, handle: UploadHandle) -> String {
+        format!("file://{}", handle.dir.join("strip.png").display())
+    }
+}
+
+/// Creates a new folder in `root` named after the current time, down to the
+/// millisecond. A queued session can be saved at the same moment as a live
+/// one, so a number is added if the name is taken anyway.
+fn create_session_dir(root: &Path) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(root)?;
+    let name = chrono::offset::Local::now()
+        .format("%Y-%m-%d %H-%M-%S%.3f")
+        .to_string();
+    let mut dir = root.join(&name);
+    let mut n = 1;
+    loop {
+        match std::fs::create_dir(&dir) {
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                n += 1;
+                dir = root.join(format!("{} ({})", name, n));
+            }
+            result => return result.map(|()| dir),
+        }
+    }
+}
+
+fn save_png(image: &RgbaImage, path: &Path) -> Result<(), LocalDiskBackendError> {
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(LocalDiskBackendError::ImageEncode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::servers::ServerBackend;
+
+    fn image(width: u32, height: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, image::Rgba([value, value, value, 255]))
+    }
+
+    #[tokio::test]
+    async fn session_is_saved_with_its_emails() {
+        let root = tempfile::tempdir().unwrap();
+        let backend = LocalDiskBackend {
+            root: root.path().join("photos"),
+        };
+        let strip = image(40, 120, 10);
+        let photos = vec![image(30, 20, 20), image(30, 20, 30), image(30, 20, 40)];
+        let handle = backend
+            .clone()
+            .upload_photo(
+                strip.clone(),
+                Some(b"print".to_vec()),
+                photos.clone(),
+                Some(b"gif".to_vec()),
+                Progress::channel().0,
+            )
+            .await
+            .unwrap();
+        for batch in [&["a@example.com"][..], &["b@example.com", "c@example.com"]] {
+            let emails: Vec<String> = batch.iter().map(|email| email.to_string()).collect();
+            let deliveries = backend
+                .clone()
+                .send_email(handle.clone(), emails.clone(), Progress::channel().0)
+                .await
+                .unwrap();
+            assert!(deliveries.iter().all(|delivery| delivery.delivered));
+            assert_eq!(
+                deliveries
+                    .into_iter()
+                    .map(|delivery| delivery.address)
+                    .collect::<Vec<_>>(),
+                emails
+            );
+        }
+
+        let sessions: Vec<_> = std::fs::read_dir(root.path().join("photos"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(sessions, std::slice::from_ref(&handle.dir));
+        let mut files: Vec<_> = std::fs::read_dir(&handle.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "animation.gif",
+                "emails.txt",
+                "photo_1.png",
+                "photo_2.png",
+                "photo_3.png",
+                "strip.png",
+                "strip_print.png",
+            ]
+        );
+        let open = |name: &str| image::open(handle.dir.join(name)).unwrap().into_rgba8();
+        assert_eq!(open("strip.png"), strip);
+        for (i, photo) in photos.iter().enumerate() {
+            assert_eq!(&open(&format!("photo_{}.png", i + 1)), photo);
+        }
+        let read = |name: &str| std::fs::read(handle.dir.join(name)).unwrap();
+        assert_eq!(read("strip_print.png"), b"print");
+        assert_eq!(read("animation.gif"), b"gif");
+        assert_eq!(
+            read("emails.txt"),
+            b"a@example.com\nb@example.com\nc@example.com\n"
+        );
+        assert_eq!(
+            backend.get_link(handle.clone()),
+            format!("file://{}", handle.dir.join("strip.png").display())
+        );
+    }
+
+    #[tokio::test]
+    async fn session_without_a_print_or_gif_leaves_them_out() {
+        let root = tempfile::tempdir().unwrap();
+        let backend = LocalDiskBackend {
+            root: root.path().to_path_buf(),
+        };
+        let handle = backend
+            .upload_photo(
+                image(40, 120, 10),
+                None,
+                vec![image(30, 20, 20)],
+                None,
+                Progress::channel().0,
+            )
+            .await
+            .unwrap();
+        let mut files: Vec<_> = std::fs::read_dir(&handle.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["photo_1.png", "strip.png"]);
+    }
+
+    #[test]
+    fn sessions_saved_at_once_get_their_own_folders() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs: Vec<_> = (0..5)
+            .map(|_| create_session_dir(root.path()).unwrap())
+            .collect();
+        for (i, dir) in dirs.iter().enumerate() {
+            assert!(dir.is_dir());
+            assert!(!dirs[..i].contains(dir), "{} was reused", dir.display());
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use image::RgbaImage;
+
+use super::{EmailDelivery, Progress};
+
+/// Roughly how long the Drive backend takes to upload one file.
+const UPLOAD_TIME: Duration = Duration::from_millis(400);
+/// Roughly how long the Drive backend takes to send the email.
+const EMAIL_TIME: Duration = Duration::from_secs(1);
+
+/// Pretends to upload and email without touching the network or the disk,
+/// slowly enough that the loading states can be seen.
+#[derive(Debug, Clone)]
+pub struct MockBackend {
+    uploads: Arc<AtomicUsize>,
+}
+
+/// Which fake upload this was, counting from 1.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UploadHandle {
+    pub id: usize,
+}
+
+impl super::ServerBackend for MockBackend {
+    type Error = Infallible;
+    type UploadHandle = UploadHandle;
+
+    fn new() -> Result<Self, Self::Error> {
+        log::info!("Using the mock server backend, photos won't be saved");
+        Ok(MockBackend {
+            uploads: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    async fn upload_photo(
+        self,
+        _strip: RgbaImage,
+        print: Option<Vec<u8>>,
+        photos: Vec<RgbaImage>,
+        gif: Option<Vec<u8>>,
+        progress: Progress,
+    ) -> Result<UploadHandle, Self::Error> {
+        // one step per file, like the real backends
+        let steps = 1 + usize::from(print.is_some()) + usize::from(gif.is_some()) + photos.len();
+        progress.set_steps(steps);
+        for _ in 0..steps {
+            tokio::time::sleep(UPLOAD_TIME).await;
+            progress.step_done();
+        }
+        let id = self.uploads.fetch_add(1, Ordering::Relaxed) + 1;
+        log::debug!("Pretended to upload photos as #{}", id);
+        Ok(UploadHandle { id })
+    }
+
+    async fn send_email(
+        self,
+        handle: UploadHandle,
+        emails: Vec<String>,
+        progress: Progress,
+    ) -> Result<Vec<EmailDelivery>, Self::Error> {
+        progress.set_steps(1);
+        tokio::time::sleep(EMAIL_TIME).await;
+        progress.step_done();
+        log::debug!(
+            "Pretended to email upload #{} to {}",
+            handle.id,
+            emails.join(", ")
+        );
+        Ok(emails
+            .into_iter()
+            .map(|address| EmailDelivery {
+                address,
+                delivered: true,
+            })
+            .collect())
+    }
+
+    fn get_link(self, handle: UploadHandle) -> String {
+        format!("https://example.com/photo-booth/{}", handle.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::futures::StreamExt;
+
+    use super::*;
+    use crate::backend::servers::ServerBackend;
+
+    #[tokio::test]
+    async fn upload_progress_never_goes_back_and_ends_at_1() {
+        let backend = MockBackend::new().unwrap();
+        let (progress, receiver) = Progress::channel();
+        let photos = vec![RgbaImage::new(4, 4); 3];
+        backend
+            .upload_photo(
+                RgbaImage::new(4, 4),
+                Some(Vec::new()),
+                photos,
+                None,
+                progress,
+            )
+            .await
+            .unwrap();
+        // the reporter was dropped with the upload, so this ends
+        let fractions = receiver.collect::<Vec<_>>().await;
+        assert_eq!(fractions.len(), 5);
+        assert!(
+            fractions.windows(2).all(|pair| pair[0] <= pair[1]),
+            "{:?}",
+            fractions
+        );
+        assert_eq!(fractions.last(), Some(&1.0));
+    }
+}
@@ -1,7 +1,16 @@
-use std::{fmt::Display, io::Cursor};
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
 use dotenv_codegen::dotenv;
 use gcp_auth::TokenProvider;
+use iced::futures::future::try_join_all;
 use image::RgbaImage;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
@@ -11,6 +20,35 @@ use reqwest::{
 use serde_json::json;
 use tokio::try_join;
 
+use super::{retry_after, with_retries, EmailDelivery, ImageEncoding, Progress, Retry};
+
+/// How long to wait for a connection to a server before giving up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a whole request, including uploading the body and reading the
+/// response, may take. Long enough for a full size strip on slow Wi-Fi, short
+/// enough that a stalled upload fails instead of spinning forever.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How many photos are uploaded at once. More than this mostly competes for
+/// the same Wi-Fi.
+const PHOTO_UPLOAD_CONCURRENCY: usize = 3;
+
+/// Files at least this big are uploaded in chunks, so a dropped connection
+/// only loses the chunk it was sending.
+const RESUMABLE_THRESHOLD: usize = 1024 * 1024;
+/// Drive wants every chunk but the last to be a multiple of this.
+const CHUNK_GRANULARITY: usize = 256 * 1024;
+/// Chunks in a row Drive may answer without taking any more of the file
+/// before the upload is given up on.
+const MAX_STALLED_CHUNKS: u32 = 3;
+const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+/// Chunk size for resumable uploads, in KiB.
+const CHUNK_SIZE_VAR: &str = "UPLOAD_CHUNK_SIZE";
+
+const DRIVE_UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3/files";
+
+const DRIVE_SCOPES: &[&str] = &["https://www.googleapis.com/auth/drive"];
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct PartialFileMetadata {
     id: String,
@@ -19,38 +57,159 @@ struct PartialFileMetadata {
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct PartialEmailMetadata {
     status: String,
+    /// How each address fared, if the endpoint says.
+    #[serde(default)]
+    results: Vec<EmailStatus>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct EmailStatus {
+    email: String,
+    status: String,
 }
 
 impl PartialEmailMetadata {
     fn is_success(&self) -> bool {
         self.status == "success"
     }
+
+    /// Whether each of `emails` was delivered. Addresses the endpoint didn't
+    /// report on go by the overall status.
+    fn deliveries(&self, emails: Vec<String>) -> Vec<EmailDelivery> {
+        emails
+            .into_iter()
+            .map(|address| {
+                let delivered = match self.results.iter().find(|result| result.email == address) {
+                    Some(result) => result.status == "success",
+                    None => self.is_success(),
+                };
+                EmailDelivery { address, delivered }
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct UploadHandle {
     pub strip_id: String,
+    /// `None` if there was no GIF to upload.
+    pub gif_id: Option<String>,
     pub folder_id: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct SupabaseBackend {
     client: reqwest::Client,
+    credentials: Credentials,
+    /// How the individual photos are encoded. The strip is always PNG, since
+    /// JPEG smears its flat template colors.
+    photo_encoding: ImageEncoding,
+}
+
+/// Where `Credentials` gets its tokens from. The service account, except in
+/// tests.
+trait TokenSource: Send + Sync + 'static {
+    type Token: Send + Sync;
+
+    fn fetch(&self) -> impl Future<Output = Result<Arc<Self::Token>, SupabaseBackendError>> + Send;
+
+    fn has_expired(token: &Self::Token) -> bool;
+}
+
+impl TokenSource for gcp_auth::CustomServiceAccount {
+    type Token = gcp_auth::Token;
+
+    async fn fetch(&self) -> Result<Arc<gcp_auth::Token>, SupabaseBackendError> {
+        self.token(DRIVE_SCOPES)
+            .await
+            .map_err(SupabaseBackendError::GcpAuth)
+    }
+
+    fn has_expired(token: &gcp_auth::Token) -> bool {
+        token.has_expired()
+    }
+}
+
+/// The service account and the last token it handed out. Shared by every
+/// clone of the backend, so a string of uploads and emails reuses one token
+/// until it expires instead of fetching a new one each time.
+struct Credentials<P: TokenSource = gcp_auth::CustomServiceAccount> {
+    source: Arc<P>,
+    token: Arc<tokio::sync::Mutex<Option<Arc<P::Token>>>>,
+}
+
+impl<P: TokenSource> Clone for Credentials<P> {
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            token: self.token.clone(),
+        }
+    }
+}
+
+impl<P: TokenSource> std::fmt::Debug for Credentials<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Credentials")
+    }
+}
+
+impl Credentials {
+    fn new() -> Result<Self, SupabaseBackendError> {
+        let service_account = gcp_auth::CustomServiceAccount::from_json(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/service_account_key.json"
+        )))
+        .map_err(SupabaseBackendError::GcpAuth)?;
+        Ok(Self::with_source(service_account))
+    }
+}
+
+impl<P: TokenSource> Credentials<P> {
+    fn with_source(source: P) -> Self {
+        Self {
+            source: Arc::new(source),
+            token: Arc::new(tokio::sync::Mutex::new(None)),
+        }
+    }
+
+    /// The cached token, or a new one if it has expired. The lock is held
+    /// while fetching so concurrent requests don't all refresh at once.
+    async fn token(&self) -> Result<Arc<P::Token>, SupabaseBackendError> {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref().filter(|token| !P::has_expired(token)) {
+            return Ok(token.clone());
+        }
+        log::debug!("Fetching a new service account token");
+        let token = self.source.fetch().await?;
+        *cached = Some(token.clone());
+        Ok(token)
+    }
 }
 
 #[derive(Debug)]
 pub enum SupabaseBackendError {
     Reqwest(reqwest::Error),
+    /// An error status from a server that said when to try again.
+    RetryAfter(reqwest::Error, Duration),
     GcpAuth(gcp_auth::Error),
     ImageEncodeDecode(image::ImageError),
+    /// Drive accepted a resumable upload without saying where to send it.
+    NoUploadSession,
+    /// Drive kept answering chunks without taking any more than this many
+    /// bytes of the file.
+    UploadStalled(usize),
 }
 
 impl Display for SupabaseBackendError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Reqwest(err) => write!(f, "reqwest error: {}", err),
+            Self::Reqwest(err) | Self::RetryAfter(err, _) => write!(f, "reqwest error: {}", err),
             Self::GcpAuth(err) => write!(f, "service account authorization error: {}", err),
             Self::ImageEncodeDecode(err) => write!(f, "image encode/decode error: {}", err),
+            Self::NoUploadSession => write!(f, "no upload session in Drive's response"),
+            Self::UploadStalled(received) => {
+                write!(f, "upload stopped making progress at byte {}", received)
+            }
         }
     }
 }
@@ -61,32 +220,40 @@ impl super::ServerBackend for SupabaseBackend {
 
     fn new() -> Result<Self, Self::Error> {
         let client = reqwest::ClientBuilder::new()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
             .build()
             .map_err(SupabaseBackendError::Reqwest)?;
 
-        Ok(SupabaseBackend { client })
+        Ok(SupabaseBackend {
+            client,
+            credentials: Credentials::new()?,
+            photo_encoding: ImageEncoding::for_photos(),
+        })
     }
 
     /// Uploads a photo to Google Drive and returns the URL of the strip.
     ///
     /// Creates a new folder within the specified folder in Google Drive,
-    /// uploads the strip as strip.png, and uploads the individual photos as
-    /// photo_1.png, photo_2.png, etc.
+    /// uploads the strip as strip.png, the print (if any) as strip_print.png,
+    /// the GIF (if any) as animation.gif, and uploads the individual photos
+    /// as photo_1.jpg, photo_2.jpg, etc., or whatever `PHOTO_ENCODING` says.
+    /// The strip and the GIF are made publicly accessible.
     /// Uploads the emails in a newline-separated text file called emails.txt.
     async fn upload_photo(
         self,
         strip: RgbaImage,
+        print: Option<Vec<u8>>,
         photos: Vec<RgbaImage>,
+        gif: Option<Vec<u8>>,
+        progress: Progress,
     ) -> Result<UploadHandle, Self::Error> {
-        let service_account = gcp_auth::CustomServiceAccount::from_json(include_str!(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/service_account_key.json"
-        )))
-        .map_err(SupabaseBackendError::GcpAuth)?;
-        let token = service_account
-            .token(&["https://www.googleapis.com/auth/drive"])
-            .await
-            .map_err(SupabaseBackendError::GcpAuth)?;
+        // one step per file
+        progress.set_steps(
+            1 + usize::from(print.is_some()) + usize::from(gif.is_some()) + photos.len(),
+        );
+        let started = Instant::now();
+        let token = self.credentials.token().await?;
         let now = chrono::offset::Local::now().to_string();
 
         // Create a new folder in Google Drive
@@ -101,38 +268,41 @@ impl super::ServerBackend for SupabaseBackend {
             "parents": [dotenv!("DRIVE_FOLDER_ID")],
             "description": format!("Uploaded at {} by photo-booth-v2", now.clone())
         });
-        let request = self
-            .client
-            .post("https://www.googleapis.com/drive/v3/files")
-            .query(&[("supportsAllDrives", "true")])
-            .body(folder_metadata.to_string())
-            .header(
-                "Content-Type",
-                HeaderValue::from_static("application/json;charset=UTF-8"),
-            )
-            .header("Authorization", format!("Bearer {}", token.as_str()));
-        let folder: PartialFileMetadata = request
-            .send()
-            .await
-            .map_err(SupabaseBackendError::Reqwest)?
-            .error_for_status()
-            .map_err(SupabaseBackendError::Reqwest)?
-            .json()
-            .await
-            .map_err(SupabaseBackendError::Reqwest)?;
+        let folder_metadata = folder_metadata.to_string();
+        let (client, folder_metadata, token_ref) = (&self.client, &folder_metadata, &token);
+        // a retry could otherwise create a second folder
+        let folder: PartialFileMetadata =
+            with_retries("Creating folder", retry_unprocessed, move || async move {
+                client
+                    .post("https://www.googleapis.com/drive/v3/files")
+                    .query(&[("supportsAllDrives", "true")])
+                    .body(folder_metadata.clone())
+                    .header(
+                        "Content-Type",
+                        HeaderValue::from_static("application/json;charset=UTF-8"),
+                    )
+                    .header("Authorization", format!("Bearer {}", token_ref.as_str()))
+                    .send()
+                    .await
+                    .map_err(SupabaseBackendError::Reqwest)
+                    .and_then(check_status)?
+                    .json()
+                    .await
+                    .map_err(SupabaseBackendError::Reqwest)
+            })
+            .await?;
         let folder_id = folder.id;
 
         log::debug!("Uploaded folder");
         log::debug!("Folder ID: {}", folder_id);
 
-        let (strip_id, _) = try_join!(
+        let (strip_id, _, gif_id, _) = try_join!(
             async {
                 // Upload the strip
-                let mut encoded = Vec::new();
-                let mut encoded_cursor = Cursor::new(&mut encoded);
-                strip
-                    .write_to(&mut encoded_cursor, image::ImageFormat::Png)
+                let encoded = ImageEncoding::Png
+                    .encode(&strip)
                     .map_err(SupabaseBackendError::ImageEncodeDecode)?;
+                log::info!("Encoded strip as {} KB", encoded.len() / 1024);
                 let file = upload_file(
                     encoded,
                     "strip.png".to_string(),
@@ -144,73 +314,101 @@ impl super::ServerBackend for SupabaseBackend {
                 .await?;
 
                 // Make the strip publicly accessible
-                let strip_id = file.id;
-                let res = self
-                    .client
-                    .post(format!(
-                        "https://www.googleapis.com/drive/v3/files/{}/permissions",
-                        strip_id
-                    ))
-                    .body(
-                        json!({
-                            "type": "anyone",
-                            "role": "reader"
-                        })
-                        .to_string(),
-                    )
-                    .header(
-                        "Content-Type",
-                        HeaderValue::from_static("application/json;charset=UTF-8"),
-                    )
-                    .header("Authorization", format!("Bearer {}", token.as_str()))
-                    .send()
-                    .await
-                    .map_err(SupabaseBackendError::Reqwest)?;
-                log::debug!("Permissions res: {:?}", res.text().await);
+                make_public(&file.id, self.client.clone(), token.clone()).await?;
                 log::debug!("Uploaded strip and permissions");
-                Ok(strip_id)
+                progress.step_done();
+                Ok(file.id)
+            },
+            async {
+                // Upload the print, which only staff need so it stays private
+                let Some(print) = print else {
+                    return Ok::<_, SupabaseBackendError>(());
+                };
+                upload_file(
+                    print,
+                    "strip_print.png".to_string(),
+                    "image/png",
+                    folder_id.clone(),
+                    self.client.clone(),
+                    token.clone(),
+                )
+                .await?;
+                log::debug!("Uploaded print");
+                progress.step_done();
+                Ok(())
+            },
+            async {
+                // Upload the gif
+                let Some(gif) = gif else {
+                    return Ok::<_, SupabaseBackendError>(None);
+                };
+                let file = upload_file(
+                    gif,
+                    "animation.gif".to_string(),
+                    "image/gif",
+                    folder_id.clone(),
+                    self.client.clone(),
+                    token.clone(),
+                )
+                .await?;
+                make_public(&file.id, self.client.clone(), token.clone()).await?;
+                log::debug!("Uploaded gif and permissions");
+                progress.step_done();
+                Ok(Some(file.id))
             },
             async {
-                // Upload the photos in parallel
-                let futures = photos.into_iter().enumerate().map(|(i, photo)| {
+                // Upload the photos in parallel, a few at a time. Encoding is
+                // CPU-bound so it runs on the blocking pool. The first failure
+                // drops the other uploads, cancelling them.
+                let photo_count = photos.len();
+                let photos_started = Instant::now();
+                let permits = tokio::sync::Semaphore::new(PHOTO_UPLOAD_CONCURRENCY);
+                let permits = &permits;
+                try_join_all(photos.into_iter().enumerate().map(|(i, photo)| {
                     let folder_id = folder_id.clone();
                     let client = self.client.clone();
                     let token = token.clone();
+                    let progress = progress.clone();
+                    let encoding = self.photo_encoding;
                     async move {
-                        let mut encoded = Vec::new();
-                        let mut encoded_cursor = Cursor::new(&mut encoded);
-                        photo
-                            .write_to(&mut encoded_cursor, image::ImageFormat::Png)
+                        let encoded = tokio::task::spawn_blocking(move || encoding.encode(&photo))
+                            .await
+                            .expect("photo encoding panicked")
                             .map_err(SupabaseBackendError::ImageEncodeDecode)?;
+                        log::info!(
+                            "Encoded photo {} as {:?} in {} KB",
+                            i + 1,
+                            encoding,
+                            encoded.len() / 1024
+                        );
+                        let _permit = permits.acquire().await.expect("never closed");
                         upload_file(
                             encoded,
-                            format!("photo_{}.png", i + 1),
-                            "image/png",
+                            format!("photo_{}.{}", i + 1, encoding.extension()),
+                            encoding.mime_type(),
                             folder_id,
                             client,
                             token,
                         )
                         .await?;
-                        Ok(())
+                        progress.step_done();
+                        Ok::<_, SupabaseBackendError>(())
                     }
-                });
-
-                let mut handles = Vec::with_capacity(futures.len());
-
-                for fut in futures {
-                    handles.push(tokio::spawn(fut));
-                }
-
-                let mut results = Vec::with_capacity(handles.len());
-                for handle in handles {
-                    results.push(handle.await.unwrap()?);
-                }
+                }))
+                .await?;
+                log::info!(
+                    "Uploaded {} photos in {:?}",
+                    photo_count,
+                    photos_started.elapsed()
+                );
                 Ok(())
             }
         )?;
+        log::info!("Upload finished in {:?}", started.elapsed());
 
         Ok(UploadHandle {
             strip_id,
+            gif_id,
             folder_id,
         })
     }
@@ -219,16 +417,11 @@ impl super::ServerBackend for SupabaseBackend {
         self,
         handle: Self::UploadHandle,
         emails: Vec<String>,
-    ) -> Result<bool, Self::Error> {
-        let service_account = gcp_auth::CustomServiceAccount::from_json(include_str!(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/service_account_key.json"
-        )))
-        .map_err(SupabaseBackendError::GcpAuth)?;
-        let token = service_account
-            .token(&["https://www.googleapis.com/auth/drive"])
-            .await
-            .map_err(SupabaseBackendError::GcpAuth)?;
+        progress: Progress,
+    ) -> Result<Vec<EmailDelivery>, Self::Error> {
+        // the email list, then the email itself
+        progress.set_steps(2);
+        let token = self.credentials.token().await?;
         let emails_content = emails.join("\n");
         upload_file(
             emails_content.as_bytes().to_vec(),
@@ -239,41 +432,180 @@ impl super::ServerBackend for SupabaseBackend {
             token.clone(),
         )
         .await?;
+        progress.step_done();
 
         // send a POST request to ENDPOINT_URL with the folderId in JSON in the body
         let endpoint_url = dotenv!("ENDPOINT_URL");
         let body = json!({
             "folderId": handle.folder_id,
+            "gifLink": handle.gif_id.as_ref().map(|id| download_link(id)),
         });
 
-        let client = reqwest::Client::new();
-        let res = client
-            .post(endpoint_url)
-            .json(&body)
-            .send()
-            .await
-            .map_err(SupabaseBackendError::Reqwest)?;
+        let client = &self.client;
+        // a retry after the endpoint got the request could send the emails
+        // twice
         let email_response: PartialEmailMetadata =
-            res.json().await.map_err(SupabaseBackendError::Reqwest)?;
+            with_retries("Sending emails", retry_unprocessed, || async {
+                client
+                    .post(endpoint_url)
+                    .json(&body)
+                    .send()
+                    .await
+                    .map_err(SupabaseBackendError::Reqwest)
+                    .and_then(check_status)?
+                    .json()
+                    .await
+                    .map_err(SupabaseBackendError::Reqwest)
+            })
+            .await?;
 
-        Ok(email_response.is_success())
+        progress.step_done();
+
+        Ok(email_response.deliveries(emails))
     }
 
     fn get_link(self, handle: Self::UploadHandle) -> String {
-        format!(
-            "https://drive.google.com/uc?id={}&export=download",
-            handle.strip_id
-        )
+        download_link(&handle.strip_id)
     }
 }
 
+fn download_link(file_id: &str) -> String {
+    format!("https://drive.google.com/uc?id={}&export=download", file_id)
+}
+
+/// Whether a failed request is worth retrying: it timed out, couldn't
+/// connect, or the server had a problem or asked us to slow down.
+fn is_transient(err: &reqwest::Error) -> bool {
+    err.is_timeout()
+        || err.is_connect()
+        || err.status().is_some_and(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
+/// Retries transient failures, as long as the server asked for.
+fn retry_transient(err: &SupabaseBackendError) -> Retry {
+    match err {
+        SupabaseBackendError::Reqwest(err) if is_transient(err) => Retry::Backoff,
+        SupabaseBackendError::RetryAfter(err, delay) if is_transient(err) => Retry::After(*delay),
+        _ => Retry::Never,
+    }
+}
+
+/// Only retries failures the server can't have acted on, for requests that
+/// would do something twice if repeated: it couldn't be reached, or it
+/// turned the request away.
+fn retry_unprocessed(err: &SupabaseBackendError) -> Retry {
+    let turned_away = |err: &reqwest::Error| {
+        err.is_connect()
+            || err.status().is_some_and(|status| {
+                status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+            })
+    };
+    match err {
+        SupabaseBackendError::Reqwest(err) if turned_away(err) => Retry::Backoff,
+        SupabaseBackendError::RetryAfter(err, delay) if turned_away(err) => Retry::After(*delay),
+        _ => Retry::Never,
+    }
+}
+
+/// Like `error_for_status`, but keeps the server's `Retry-After` so a retry
+/// can wait as long as it asked.
+fn check_status(res: reqwest::Response) -> Result<reqwest::Response, SupabaseBackendError> {
+    let delay = retry_after(res.headers());
+    res.error_for_status().map_err(|err| match delay {
+        Some(delay) => SupabaseBackendError::RetryAfter(err, delay),
+        None => SupabaseBackendError::Reqwest(err),
+    })
+}
+
+/// Lets anyone with the link download the file.
+async fn make_public(
+    file_id: &str,
+    client: Client,
+    token: Arc<gcp_auth::Token>,
+) -> Result<(), SupabaseBackendError> {
+    // granting the same permission twice is harmless
+    with_retries("Sharing file", retry_transient, || {
+        make_public_once(file_id.to_string(), client.clone(), token.clone())
+    })
+    .await
+}
+
+async fn make_public_once(
+    file_id: String,
+    client: Client,
+    token: Arc<gcp_auth::Token>,
+) -> Result<(), SupabaseBackendError> {
+    let res = client
+        .post(format!(
+            "https://www.googleapis.com/drive/v3/files/{}/permissions",
+            file_id
+        ))
+        .body(
+            json!({
+                "type": "anyone",
+                "role": "reader"
+            })
+            .to_string(),
+        )
+        .header(
+            "Content-Type",
+            HeaderValue::from_static("application/json;charset=UTF-8"),
+        )
+        .header("Authorization", format!("Bearer {}", token.as_str()))
+        .send()
+        .await
+        .map_err(SupabaseBackendError::Reqwest)
+        .and_then(check_status)?;
+    log::debug!("Permissions res: {:?}", res.text().await);
+    Ok(())
+}
+
+/// Uploads `content` to Drive, in resumable chunks if it's big enough to be
+/// worth it, retrying transient failures. Retrying a small file after a
+/// timeout may leave a duplicate of it in the folder, which is better than
+/// losing the take.
 async fn upload_file(
     content: Vec<u8>,
     name: String,
     content_type: &'static str,
     parent_folder_id: String,
     client: Client,
-    token: std::sync::Arc<gcp_auth::Token>,
+    token: Arc<gcp_auth::Token>,
+) -> Result<PartialFileMetadata, SupabaseBackendError> {
+    if content.len() >= RESUMABLE_THRESHOLD {
+        return upload_file_resumable(
+            &content,
+            &name,
+            content_type,
+            &parent_folder_id,
+            &client,
+            token.as_str(),
+        )
+        .await;
+    }
+    with_retries(&format!("Uploading {}", name), retry_transient, || {
+        upload_file_once(
+            content.clone(),
+            name.clone(),
+            content_type,
+            parent_folder_id.clone(),
+            client.clone(),
+            token.clone(),
+        )
+    })
+    .await
+}
+
+async fn upload_file_once(
+    content: Vec<u8>,
+    name: String,
+    content_type: &'static str,
+    parent_folder_id: String,
+    client: Client,
+    token: Arc<gcp_auth::Token>,
 ) -> Result<PartialFileMetadata, SupabaseBackendError> {
     log::trace!("Uploading file: {}", name);
     log::trace!("Content type: {}", content_type);
@@ -286,14 +618,13 @@ async fn upload_file(
     let mut content_headers = HeaderMap::with_capacity(1);
     content_headers.append("Content-Type", HeaderValue::from_static(content_type));
     let form = reqwest::multipart::Form::new()
-            .part("", Part::text(json!({
-            "parents": [parent_folder_id],
-            "name": name,
-            "description": format!("Uploaded at {} by photo-booth-v2", chrono::offset::Local::now())
-            }).to_string()).headers(metadata_headers))
-            .part("", Part::bytes(content).headers(content_headers));
+        .part(
+            "",
+            Part::text(file_metadata(&name, &parent_folder_id)).headers(metadata_headers),
+        )
+        .part("", Part::bytes(content).headers(content_headers));
     let request = client
-        .post("https://www.googleapis.com/upload/drive/v3/files")
+        .post(DRIVE_UPLOAD_URL)
         .query(&[("uploadType", "multipart")])
         .multipart(form)
         .header(
@@ -304,9 +635,8 @@ async fn upload_file(
     let file: PartialFileMetadata = request
         .send()
         .await
-        .map_err(SupabaseBackendError::Reqwest)?
-        .error_for_status()
-        .map_err(SupabaseBackendError::Reqwest)?
+        .map_err(SupabaseBackendError::Reqwest)
+        .and_then(check_status)?
         .json()
         .await
         .map_err(SupabaseBackendError::Reqwest)?;
@@ -316,3 +646,443 @@ async fn upload_file(
 
     Ok(file)
 }
+
+fn file_metadata(name: &str, parent_folder_id: &str) -> String {
+    json!({
+        "parents": [parent_folder_id],
+        "name": name,
+        "description": format!("Uploaded at {} by photo-booth-v2", chrono::offset::Local::now())
+    })
+    .to_string()
+}
+
+/// `UPLOAD_CHUNK_SIZE`, rounded down to what Drive accepts, or
+/// `DEFAULT_CHUNK_SIZE` if it's unset or invalid.
+fn chunk_size() -> usize {
+    static SIZE: OnceLock<usize> = OnceLock::new();
+    *SIZE.get_or_init(|| {
+        let Ok(value) = std::env::var(CHUNK_SIZE_VAR) else {
+            return DEFAULT_CHUNK_SIZE;
+        };
+        match value.trim().parse::<usize>() {
+            Ok(kib) if kib * 1024 >= CHUNK_GRANULARITY => {
+                kib * 1024 / CHUNK_GRANULARITY * CHUNK_GRANULARITY
+            }
+            _ => {
+                log::warn!(
+                    "Ignoring {}={:?}, expected at least {} KiB",
+                    CHUNK_SIZE_VAR,
+                    value,
+                    CHUNK_GRANULARITY / 1024
+                );
+                DEFAULT_CHUNK_SIZE
+            }
+        }
+    })
+}
+
+/// Where a resumable upload stands according to Drive.
+enum UploadStatus {
+    /// Drive has the bytes before this offset.
+    Incomplete(usize),
+    Done(PartialFileMetadata),
+}
+
+/// Uploads `content` with Drive's resumable protocol.
+async fn upload_file_resumable(
+    content: &[u8],
+    name: &str,
+    content_type: &'static str,
+    parent_folder_id: &str,
+    client: &Client,
+    token: &str,
+) -> Result<PartialFileMetadata, SupabaseBackendError> {
+    log::trace!("Uploading file in chunks: {}", name);
+    let session = with_retries(
+        &format!("Starting upload of {}", name),
+        retry_transient,
+        || {
+            start_resumable_upload(
+                content.len(),
+                name,
+                content_type,
+                parent_folder_id,
+                client,
+                token,
+            )
+        },
+    )
+    .await?;
+    upload_chunks(&session, content, name, chunk_size(), client, token).await
+}
+
+/// Sends `content` to a resumable upload `session`, `chunk_size` bytes at a
+/// time. Each chunk is retried on its own, and a retry first asks Drive how
+/// much it already has so nothing it received is sent again. Gives up if
+/// Drive stops taking more of the file.
+async fn upload_chunks(
+    session: &str,
+    content: &[u8],
+    name: &str,
+    chunk_size: usize,
+    client: &Client,
+    token: &str,
+) -> Result<PartialFileMetadata, SupabaseBackendError> {
+    let mut offset = 0;
+    let mut stalled = 0;
+    loop {
+        let retrying = AtomicBool::new(false);
+        let status = with_retries(
+            &format!("Uploading {} from byte {}", name, offset),
+            retry_transient,
+            || {
+                let retrying = &retrying;
+                async move {
+                    let mut start = offset;
+                    if retrying.swap(true, Ordering::Relaxed) {
+                        match upload_status(session, content.len(), client, token).await? {
+                            UploadStatus::Incomplete(received) => start = received,
+                            done => return Ok(done),
+                        }
+                    }
+                    let end = (start + chunk_size).min(content.len());
+                    let res = client
+                        .put(session)
+                        .header(
+                            reqwest::header::CONTENT_RANGE,
+                            format!("bytes {}-{}/{}", start, end - 1, content.len()),
+                        )
+                        .header("Authorization", format!("Bearer {}", token))
+                        .body(content[start..end].to_vec())
+                        .send()
+                        .await
+                        .map_err(SupabaseBackendError::Reqwest)?;
+                    parse_upload_status(res).await
+                }
+            },
+        )
+        .await?;
+        match status {
+            UploadStatus::Incomplete(received) => {
+                if received > offset {
+                    stalled = 0;
+                } else {
+                    stalled += 1;
+                    log::warn!(
+                        "Drive took none of {} from byte {} ({} in a row)",
+                        name,
+                        offset,
+                        stalled
+                    );
+                    if stalled >= MAX_STALLED_CHUNKS {
+                        return Err(SupabaseBackendError::UploadStalled(received));
+                    }
+                }
+                offset = received;
+            }
+            UploadStatus::Done(file) => {
+                log::debug!("Uploaded file");
+                log::debug!("File ID: {}", file.id);
+                return Ok(file);
+            }
+        }
+    }
+}
+
+/// Opens a resumable upload session and returns its URL.
+async fn start_resumable_upload(
+    len: usize,
+    name: &str,
+    content_type: &'static str,
+    parent_folder_id: &str,
+    client: &Client,
+    token: &str,
+) -> Result<String, SupabaseBackendError> {
+    let res = client
+        .post(DRIVE_UPLOAD_URL)
+        .query(&[("uploadType", "resumable")])
+        .header(
+            "Content-Type",
+            HeaderValue::from_static("application/json;charset=UTF-8"),
+        )
+        .header("X-Upload-Content-Type", content_type)
+        .header("X-Upload-Content-Length", len)
+        .header("Authorization", format!("Bearer {}", token))
+        .body(file_metadata(name, parent_folder_id))
+        .send()
+        .await
+        .map_err(SupabaseBackendError::Reqwest)
+        .and_then(check_status)?;
+    res.headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string)
+        .ok_or(SupabaseBackendError::NoUploadSession)
+}
+
+/// Asks Drive how much of a resumable upload it has.
+async fn upload_status(
+    session: &str,
+    len: usize,
+    client: &Client,
+    token: &str,
+) -> Result<UploadStatus, SupabaseBackendError> {
+    let res = client
+        .put(session)
+        .header(reqwest::header::CONTENT_RANGE, format!("bytes */{}", len))
+        .header(reqwest::header::CONTENT_LENGTH, 0)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(SupabaseBackendError::Reqwest)?;
+    parse_upload_status(res).await
+}
+
+async fn parse_upload_status(res: reqwest::Response) -> Result<UploadStatus, SupabaseBackendError> {
+    // Drive's "Resume Incomplete". It has no Location, so reqwest doesn't
+    // follow it as a redirect.
+    if res.status() == reqwest::StatusCode::PERMANENT_REDIRECT {
+        // `Range: bytes=0-<last>`, or missing if nothing has arrived yet
+        let received = res
+            .headers()
+            .get(reqwest::header::RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(|range| range.rsplit('-').next()?.parse::<usize>().ok())
+            .map_or(0, |last| last + 1);
+        return Ok(UploadStatus::Incomplete(received));
+    }
+    let file = check_status(res)?
+        .json()
+        .await
+        .map_err(SupabaseBackendError::Reqwest)?;
+    Ok(UploadStatus::Done(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use wiremock::{matchers::method, Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    use super::*;
+
+    const CONTENT_LEN: usize = 40;
+    const CHUNK_SIZE: usize = 10;
+
+    #[derive(Debug, Default)]
+    struct SessionState {
+        /// What Drive has of the file so far.
+        received: Vec<u8>,
+        dropped: bool,
+        /// The `Content-Range` of every request, in order.
+        ranges: Vec<String>,
+    }
+
+    /// A resumable upload session that behaves like Drive's.
+    struct Session {
+        state: Arc<Mutex<SessionState>>,
+        /// The connection drops halfway through the first chunk sent from
+        /// here, after Drive got the first half of it.
+        drop_at: Option<usize>,
+        /// Drive never takes any bytes past here.
+        stall_at: Option<usize>,
+    }
+
+    impl Session {
+        /// Serves the session on `server` and returns what it gets.
+        async fn start(
+            server: &MockServer,
+            drop_at: Option<usize>,
+            stall_at: Option<usize>,
+        ) -> Arc<Mutex<SessionState>> {
+            let state = Arc::new(Mutex::new(SessionState::default()));
+            Mock::given(method("PUT"))
+                .respond_with(Session {
+                    state: state.clone(),
+                    drop_at,
+                    stall_at,
+                })
+                .mount(server)
+                .await;
+            state
+        }
+    }
+
+    impl Respond for Session {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let mut state = self.state.lock().unwrap();
+            let Some(range) = request
+                .headers
+                .get("content-range")
+                .and_then(|range| range.to_str().ok())
+            else {
+                return ResponseTemplate::new(400);
+            };
+            state.ranges.push(range.to_string());
+            let Some((span, total)) = range
+                .strip_prefix("bytes ")
+                .and_then(|range| range.split_once('/'))
+            else {
+                return ResponseTemplate::new(400);
+            };
+            let total: usize = total.parse().unwrap();
+            if let Some((start, _)) = span.split_once('-') {
+                let start: usize = start.parse().unwrap();
+                // Drive turns away chunks that leave a gap or overlap
+                if start != state.received.len() {
+                    return ResponseTemplate::new(400);
+                }
+                let mut body = request.body.as_slice();
+                if self.drop_at == Some(start) && !state.dropped {
+                    state.dropped = true;
+                    state.received.extend(&body[..body.len() / 2]);
+                    return ResponseTemplate::new(503);
+                }
+                if let Some(stall_at) = self.stall_at {
+                    body = &body[..stall_at.saturating_sub(start).min(body.len())];
+                }
+                state.received.extend(body);
+            }
+            if state.received.len() == total {
+                return ResponseTemplate::new(200).set_body_json(json!({ "id": "uploaded" }));
+            }
+            match state.received.len() {
+                0 => ResponseTemplate::new(308),
+                received => ResponseTemplate::new(308)
+                    .insert_header("Range", format!("bytes=0-{}", received - 1).as_str()),
+            }
+        }
+    }
+
+    fn content() -> Vec<u8> {
+        (0..CONTENT_LEN as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn resumable_upload_carries_on_from_a_dropped_chunk() {
+        let server = MockServer::start().await;
+        let state = Session::start(&server, Some(CHUNK_SIZE), None).await;
+        let content = content();
+        let file = upload_chunks(
+            &server.uri(),
+            &content,
+            "test.bin",
+            CHUNK_SIZE,
+            &Client::new(),
+            "token",
+        )
+        .await
+        .unwrap();
+        assert_eq!(file.id, "uploaded");
+        let state = state.lock().unwrap();
+        assert_eq!(state.received, content);
+        // the first chunk isn't sent again, and the second only from where
+        // the dropped connection left off
+        assert_eq!(
+            state.ranges,
+            [
+                "bytes 0-9/40",
+                "bytes 10-19/40",
+                "bytes */40",
+                "bytes 15-24/40",
+                "bytes 25-34/40",
+                "bytes 35-39/40",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn resumable_upload_gives_up_when_drive_stops_taking_chunks() {
+        let server = MockServer::start().await;
+        let state = Session::start(&server, None, Some(CHUNK_SIZE)).await;
+        let result = upload_chunks(
+            &server.uri(),
+            &content(),
+            "test.bin",
+            CHUNK_SIZE,
+            &Client::new(),
+            "token",
+        )
+        .await;
+        assert!(
+            matches!(result, Err(SupabaseBackendError::UploadStalled(CHUNK_SIZE))),
+            "{:?}",
+            result
+        );
+        let state = state.lock().unwrap();
+        assert_eq!(state.ranges.len(), 1 + MAX_STALLED_CHUNKS as usize);
+        assert!(state.ranges[1..]
+            .iter()
+            .all(|range| range == "bytes 10-19/40"));
+    }
+
+    /// Hands out numbered tokens, already expired if told to, and counts how
+    /// many it has fetched.
+    #[derive(Default)]
+    struct CountingSource {
+        fetches: std::sync::atomic::AtomicUsize,
+        expired: bool,
+    }
+
+    struct CountedToken {
+        number: usize,
+        expired: bool,
+    }
+
+    impl TokenSource for CountingSource {
+        type Token = CountedToken;
+
+        fn fetch(
+            &self,
+        ) -> impl Future<Output = Result<Arc<CountedToken>, SupabaseBackendError>> + Send {
+            let number = self.fetches.fetch_add(1, Ordering::SeqCst) + 1;
+            let expired = self.expired;
+            async move {
+                // slow enough for concurrent callers to pile up on the lock
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(Arc::new(CountedToken { number, expired }))
+            }
+        }
+
+        fn has_expired(token: &CountedToken) -> bool {
+            token.expired
+        }
+    }
+
+    impl Credentials<CountingSource> {
+        fn fetches(&self) -> usize {
+            self.source.fetches.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test]
+    async fn token_is_fetched_once_for_two_uploads() {
+        let credentials = Credentials::with_source(CountingSource::default());
+        // each upload takes a token from its own clone of the backend
+        let first = credentials.clone().token().await.unwrap();
+        let second = credentials.clone().token().await.unwrap();
+        assert_eq!(credentials.fetches(), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(second.number, 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_one_fetch() {
+        let credentials = Credentials::with_source(CountingSource::default());
+        let (first, second) = tokio::join!(credentials.token(), credentials.token());
+        assert_eq!(credentials.fetches(), 1);
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_fetched_again() {
+        let credentials = Credentials::with_source(CountingSource {
+            expired: true,
+            ..Default::default()
+        });
+        credentials.token().await.unwrap();
+        let token = credentials.token().await.unwrap();
+        assert_eq!(credentials.fetches(), 2);
+        assert_eq!(token.number, 2);
+    }
+}
@@ -1,23 +1,145 @@
+pub mod blur;
 mod border_radius;
+mod crop_guide;
+mod histogram;
 
+pub use histogram::{Clipping, Histogram};
+
+use crate::backend::{
+    cameras::{Frame, VideoFrame},
+    filters::{apply_filter_in_place, ColorFilter},
+};
 use iced::border::Radius;
+use iced::futures::SinkExt;
 use iced::widget::image::Handle;
-use iced::Task;
+use iced::Subscription;
 use image::RgbaImage;
-use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+/// Delay before the first retry after a failed capture. Doubles with every
+/// consecutive failure up to `MAX_RETRY_DELAY`.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How often the capture loop logs how long frames are taking.
+const TIMING_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often a paused capture loop checks whether it should resume.
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Every how many video frames the histogram is measured.
+const HISTOGRAM_INTERVAL: u32 = 10;
+/// Only every this many pixels in each direction are counted.
+const HISTOGRAM_DECIMATION: u32 = 8;
+
+pub const DEFAULT_TARGET_FPS: f32 = 30.0;
+
+/// Shown until the first frame arrives. Shared so every view before then uses
+/// the same handle instead of uploading a new empty image each time.
+static EMPTY_FRAME: Lazy<Handle> = Lazy::new(|| Handle::from_rgba(0, 0, vec![]));
+
+/// Gives every feed its own subscription, even if two are open at once.
+static NEXT_FEED_ID: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Debug, Clone)]
 pub enum CameraMessage {
-    CaptureFrame,
-    NewFrame(Handle),
+    NewFrame(FrameHandle),
+    /// A capture failed. The capture loop has already logged why.
+    CaptureFailed,
+    /// A capture failed and the camera reports it's no longer connected.
+    Disconnected,
 }
 
-/// Camera feed.
+/// A frame ready to show, with its size, which a handle to an encoded frame
+/// doesn't know until iced decodes it.
 #[derive(Debug, Clone)]
+pub struct FrameHandle {
+    pub handle: Handle,
+    pub size: iced::Size<u32>,
+}
+
+/// Opens the camera again after it was disconnected.
+type Reopen<C> = Arc<Mutex<dyn FnMut() -> Result<C, String> + Send>>;
+
+/// Handed from one run of the capture loop to the next, which keeps it
+/// locked while it runs. Everything else goes through `Request`s.
+type SharedCamera<C> = Arc<tokio::sync::Mutex<C>>;
+
+/// Requests for the capture loop. Queued while no loop is running.
+type Requests<C> = Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<Request<C>>>>;
+
+/// How long `CameraFeed::closed` waits for the capture loop to let go of the
+/// camera.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Camera feed.
 pub struct CameraFeed<C: crate::backend::cameras::CameraBackendCamera + 'static> {
-    camera: Arc<Mutex<C>>,
-    current_frame: Arc<Mutex<Option<Handle>>>,
-    options: CameraFeedOptions,
+    id: u64,
+    camera: SharedCamera<C>,
+    request_sender: mpsc::UnboundedSender<Request<C>>,
+    requests: Requests<C>,
+    /// Only ever replaced by a successfully captured frame, so failed captures
+    /// and option changes keep showing the previous picture.
+    last_good_frame: Arc<Mutex<Option<FrameHandle>>>,
+    /// Measured now and then by the capture loop, from the frames as the
+    /// camera delivers them.
+    last_histogram: Arc<Mutex<Option<Histogram>>>,
+    /// Shared with the capture loop so option changes apply to the next frame.
+    options: Arc<Mutex<CameraFeedOptions>>,
+    target_fps: f32,
+    /// Number of video captures that have failed in a row.
+    consecutive_failures: u32,
+    connected: bool,
+    reopen: Option<Reopener<C>>,
+}
+
+// Written out so cameras don't have to be `Clone` or `Debug` themselves.
+impl<C: crate::backend::cameras::CameraBackendCamera + 'static> Clone for CameraFeed<C> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            camera: self.camera.clone(),
+            request_sender: self.request_sender.clone(),
+            requests: self.requests.clone(),
+            last_good_frame: self.last_good_frame.clone(),
+            last_histogram: self.last_histogram.clone(),
+            options: self.options.clone(),
+            target_fps: self.target_fps,
+            consecutive_failures: self.consecutive_failures,
+            connected: self.connected,
+            reopen: self.reopen.clone(),
+        }
+    }
+}
+
+impl<C: crate::backend::cameras::CameraBackendCamera + 'static> std::fmt::Debug for CameraFeed<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CameraFeed")
+            .field("id", &self.id)
+            .field("target_fps", &self.target_fps)
+            .field("consecutive_failures", &self.consecutive_failures)
+            .field("connected", &self.connected)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Wrapper so `CameraFeed` can stay `Debug`.
+struct Reopener<C>(Reopen<C>);
+
+impl<C> Clone for Reopener<C> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<C> std::fmt::Debug for Reopener<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Reopener")
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -25,7 +147,25 @@ pub struct CameraFeedOptions {
     pub radius: Radius,
     pub mirror: bool,
     pub aspect_ratio: Option<f32>,
+    /// Cheap blur that shrinks the frame by this factor and lets it be
+    /// scaled back up when drawn. Looks pixelated, but costs next to nothing.
     pub blur: f32,
+    /// Standard deviation in pixels of a smooth, gaussian-like blur. Slower
+    /// than `blur`, so only for backgrounds. 0.0 turns it off.
+    pub blur_sigma: f32,
+    /// Longest side the frame is shrunk to fit as the last postprocessing
+    /// step. `None` keeps whatever size the earlier steps left it at.
+    pub max_dimension: Option<u32>,
+    pub filter: ColorFilter,
+    pub rotation: Rotation,
+    /// Digital zoom factor. 1.0 (or anything below it) shows the whole frame.
+    pub zoom: f32,
+    /// Point to zoom in on, as fractions of the frame's width and height.
+    /// Defaults to the middle of the frame.
+    pub center: Option<(f32, f32)>,
+    /// Stops capturing video frames and keeps showing the last one, for when
+    /// something covers the feed. Stills can still be taken.
+    pub paused: bool,
 }
 
 impl Default for CameraFeedOptions {
@@ -35,151 +175,762 @@ impl Default for CameraFeedOptions {
             mirror: false,
             aspect_ratio: None,
             blur: 0.0,
+            blur_sigma: 0.0,
+            max_dimension: None,
+            filter: ColorFilter::None,
+            rotation: Rotation::None,
+            zoom: 1.0,
+            center: None,
+            paused: false,
+        }
+    }
+}
+
+impl CameraFeedOptions {
+    /// Whether postprocessing has to touch the pixels. If not, frames the
+    /// camera sends encoded are handed to iced as they are. `max_dimension`
+    /// doesn't count, since iced scales those when it draws them.
+    fn needs_pixels(&self) -> bool {
+        self.radius != Radius::from(0)
+            || self.mirror
+            || self.aspect_ratio.is_some()
+            || self.blur > 0.0
+            || self.blur_sigma > 0.0
+            || self.filter != ColorFilter::None
+            || self.rotation != Rotation::None
+            || self.zoom > 1.0
+    }
+}
+
+/// Clockwise rotation applied to every frame before any cropping, for cameras
+/// that are mounted sideways or upside down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Rotation {
+    #[default]
+    None,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [Self::None, Self::Rotate90, Self::Rotate180, Self::Rotate270];
+}
+
+impl std::fmt::Display for Rotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "No rotation"),
+            Self::Rotate90 => write!(f, "Rotate 90° clockwise"),
+            Self::Rotate180 => write!(f, "Rotate 180°"),
+            Self::Rotate270 => write!(f, "Rotate 90° counterclockwise"),
         }
     }
 }
 
 #[allow(unused)]
 impl<C: crate::backend::cameras::CameraBackendCamera + 'static> CameraFeed<C> {
-    pub fn new(camera: C, options: CameraFeedOptions) -> (Self, Task<CameraMessage>) {
-        (
-            CameraFeed {
-                camera: Arc::new(Mutex::new(camera)),
-                current_frame: Arc::new(Mutex::new(None)),
-                options,
-            },
-            Task::done(CameraMessage::CaptureFrame),
-        )
+    pub fn new(camera: C, options: CameraFeedOptions) -> Self {
+        let (request_sender, requests) = mpsc::unbounded_channel();
+        CameraFeed {
+            id: NEXT_FEED_ID.fetch_add(1, Ordering::Relaxed),
+            camera: Arc::new(tokio::sync::Mutex::new(camera)),
+            request_sender,
+            requests: Arc::new(tokio::sync::Mutex::new(requests)),
+            last_good_frame: Arc::new(Mutex::new(None)),
+            last_histogram: Arc::new(Mutex::new(None)),
+            options: Arc::new(Mutex::new(options)),
+            target_fps: DEFAULT_TARGET_FPS,
+            consecutive_failures: 0,
+            connected: true,
+            reopen: None,
+        }
+    }
+
+    /// Lets the feed reconnect on its own by calling `reopen` after the camera
+    /// has been disconnected, e.g. to call `CameraBackend::open_camera` with
+    /// the camera picked in setup.
+    pub fn with_reopen(
+        mut self,
+        reopen: impl FnMut() -> Result<C, String> + Send + 'static,
+    ) -> Self {
+        self.reopen = Some(Reopener(Arc::new(Mutex::new(reopen))));
+        self
     }
 
     pub fn options(&self) -> CameraFeedOptions {
-        self.options
+        *lock(&self.options)
     }
 
     pub fn update_options(&mut self, options: CameraFeedOptions) {
-        self.options = options;
+        *lock(&self.options) = options;
+    }
+
+    /// Set how many frames per second the capture loop aims for. Changing it
+    /// restarts the loop.
+    pub fn set_target_fps(&mut self, target_fps: f32) {
+        self.target_fps = target_fps.max(1.0);
+    }
+
+    /// Whether the last video capture succeeded. While this is `false` the
+    /// feed keeps retrying in the background and shows the last good frame.
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures == 0
+    }
+
+    /// Whether the camera was still there the last time a capture failed.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Get the camera ready for `capture_still`, e.g. by focusing.
+    pub fn prepare_still(
+        &self,
+    ) -> impl std::future::Future<Output = Result<(), String>> + Send + 'static {
+        let prepared = self.with_camera(|camera| camera.prepare_still_capture());
+        async move { prepared.await?.map_err(|err| format!("{:?}", err)) }
+    }
+
+    /// Has the capture loop run `f` on the camera on a blocking thread
+    /// between video frames, e.g. to change its settings while the feed
+    /// keeps running. Fails if the loop stops first or `f` panics.
+    pub fn with_camera<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut C) -> T + Send + 'static,
+    ) -> impl std::future::Future<Output = Result<T, String>> + Send + 'static {
+        let (reply, result) = oneshot::channel();
+        let sent = self
+            .request_sender
+            .send(Request::Run(Box::new(move |camera| {
+                let _ = reply.send(f(camera));
+            })));
+        async move {
+            sent.map_err(|_| "the camera feed has stopped".to_string())?;
+            result
+                .await
+                .map_err(|_| "the camera feed stopped before getting to it".to_string())
+        }
+    }
+
+    /// Drops the feed and waits until the camera has been closed, so the
+    /// device can be opened again. The capture loop only lets go of it once
+    /// its subscription is gone, so this gives up after `CLOSE_TIMEOUT`.
+    pub fn closed(self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let camera = Arc::downgrade(&self.camera);
+        drop(self);
+        async move {
+            let deadline = Instant::now() + CLOSE_TIMEOUT;
+            while camera.strong_count() > 0 {
+                if Instant::now() >= deadline {
+                    log::warn!("Camera is still in use after {:?}", CLOSE_TIMEOUT);
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
     }
 
     /// Take an image outside of the normal video capture cycle
-    pub async fn capture_still(
-        &mut self,
+    ///
+    /// The capture loop takes it right after the video frame it's working
+    /// on, and the postprocessing runs on a blocking thread, so the returned
+    /// future can be handed to `Task::perform` without stalling the UI. Fails
+    /// if the camera takes longer than `timeout`.
+    pub fn capture_still(
+        &self,
         postprocessing_options: CameraFeedOptions,
-    ) -> Result<RgbaImage, C::Error> {
-        let cloned_camera = self.camera.clone();
-        let frame = tokio::task::spawn_blocking(move || {
-            cloned_camera
-                .lock()
-                .expect("failed to lock camera mutex")
-                .capture_still_frame()
-                .map(|x| image_postprocessing(x, postprocessing_options))
-        })
-        .await
-        .expect("capture_still task terminated unexpectedly")?;
-        Ok(image_postprocessing(frame, postprocessing_options))
+        timeout: Duration,
+    ) -> impl std::future::Future<Output = Result<RgbaImage, String>> + Send + 'static {
+        let (reply, result) = oneshot::channel();
+        let sent = self.request_sender.send(Request::Still { timeout, reply });
+        async move {
+            sent.map_err(|_| "the camera feed has stopped".to_string())?;
+            let frame = result
+                .await
+                .map_err(|_| "the camera feed stopped before taking the photo".to_string())??;
+            tokio::task::spawn_blocking(move || image_postprocessing(frame, postprocessing_options))
+                .await
+                .map_err(|err| err.to_string())
+        }
     }
 
-    /// Take an image outside of the normal video capture cycle
-    pub fn capture_still_sync(
-        &mut self,
+    /// Takes `count` photos `interval` apart, like `capture_still` but without
+    /// waiting on the UI between them. Fails if the whole burst takes longer
+    /// than `timeout`.
+    pub fn capture_burst(
+        &self,
+        count: usize,
+        interval: Duration,
         postprocessing_options: CameraFeedOptions,
-    ) -> Result<RgbaImage, C::Error> {
-        let frame = self
-            .camera
-            .lock()
-            .expect("failed to lock camera mutex")
-            .capture_still_frame()
-            .map(|x| image_postprocessing(x, postprocessing_options))?;
-        Ok(image_postprocessing(frame, postprocessing_options))
+        timeout: Duration,
+    ) -> impl std::future::Future<Output = Result<Vec<RgbaImage>, String>> + Send + 'static {
+        let (reply, result) = oneshot::channel();
+        let sent = self.request_sender.send(Request::Burst {
+            count,
+            interval,
+            timeout,
+            reply,
+        });
+        async move {
+            sent.map_err(|_| "the camera feed has stopped".to_string())?;
+            let frames = result
+                .await
+                .map_err(|_| "the camera feed stopped before taking the photos".to_string())??;
+            tokio::task::spawn_blocking(move || {
+                frames
+                    .into_iter()
+                    .map(|frame| image_postprocessing(frame, postprocessing_options))
+                    .collect()
+            })
+            .await
+            .map_err(|err| err.to_string())
+        }
     }
 
-    pub fn update(&mut self, message: CameraMessage) -> Task<CameraMessage> {
+    /// Video frames from the camera, captured at up to the target FPS. Frames
+    /// the UI hasn't caught up with are dropped rather than queued.
+    pub fn subscription(&self) -> Subscription<CameraMessage> {
+        Subscription::run_with_id(
+            (self.id, self.target_fps.to_bits()),
+            capture_loop(
+                self.camera.clone(),
+                self.requests.clone(),
+                self.options.clone(),
+                self.last_histogram.clone(),
+                self.target_fps,
+                self.reopen.clone().map(|reopen| reopen.0),
+            ),
+        )
+    }
+
+    /// Runs the capture loop without a subscription, dropping the frames, so
+    /// tests can take photos with the feed.
+    #[cfg(test)]
+    pub fn spawn_capture_loop(&self) -> tokio::task::JoinHandle<()> {
+        use iced::futures::StreamExt;
+        tokio::spawn(
+            capture_loop(
+                self.camera.clone(),
+                self.requests.clone(),
+                self.options.clone(),
+                self.last_histogram.clone(),
+                self.target_fps,
+                self.reopen.clone().map(|reopen| reopen.0),
+            )
+            .for_each(|_| async {}),
+        )
+    }
+
+    pub fn update(&mut self, message: CameraMessage) {
         match message {
-            CameraMessage::CaptureFrame => {
-                let cloned_camera = self.camera.clone();
-                let options = self.options;
-                Task::perform(
-                    async move {
-                        tokio::task::spawn_blocking(move || {
-                            let frame = match cloned_camera
-                                .lock()
-                                .expect("failed to lock camera mutex")
-                                .capture_video_frame()
-                            {
-                                Ok(frame) => frame,
-                                Err(_) => {
-                                    return Handle::from_rgba(0, 0, vec![]);
-                                }
-                            };
-
-                            let frame = image_postprocessing(frame, options);
-
-                            // output a handle
-                            Handle::from_rgba(frame.width(), frame.height(), frame.into_raw())
-                        })
-                        .await
-                        .unwrap()
-                    },
-                    CameraMessage::NewFrame,
-                )
-            }
             CameraMessage::NewFrame(data) => {
-                *self.current_frame.lock().expect("failed to lock frame") = Some(data);
-                Task::perform(async {}, |_| CameraMessage::CaptureFrame)
+                self.consecutive_failures = 0;
+                self.connected = true;
+                *lock(&self.last_good_frame) = Some(data);
+            }
+            CameraMessage::CaptureFailed => {
+                self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+            }
+            CameraMessage::Disconnected => {
+                self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+                self.connected = false;
             }
         }
     }
 
-    /// Get the image handle of the current frame.
+    /// Get the image handle of the last frame that was captured successfully,
+    /// or an empty one before the first frame has arrived.
     pub fn handle(&self) -> Handle {
-        self.current_frame
-            .lock()
-            .expect("failed to lock frame")
-            .clone()
-            .unwrap_or_else(|| Handle::from_rgba(0, 0, vec![]))
+        lock(&self.last_good_frame)
+            .as_ref()
+            .map_or_else(|| EMPTY_FRAME.clone(), |frame| frame.handle.clone())
+    }
+
+    /// The size of the last frame that was captured successfully, or `None`
+    /// before a frame with any pixels in it has arrived.
+    fn frame_size(&self) -> Option<iced::Size<u32>> {
+        lock(&self.last_good_frame)
+            .as_ref()
+            .map(|frame| frame.size)
+            .filter(|size| size.width > 0 && size.height > 0)
+    }
+
+    /// The luminance histogram of a recent frame, before any postprocessing,
+    /// or `None` before one has been measured.
+    pub fn last_histogram(&self) -> Option<Histogram> {
+        lock(&self.last_histogram).clone()
     }
 
     /// Wrap the output of `frame_image` in an `Image` widget.
     pub fn view(&self) -> iced::widget::image::Image<Handle> {
         iced::widget::Image::new(self.handle())
     }
+
+    /// The whole frame, fit inside the available space, with everything a
+    /// crop to `aspect_ratio` would cut off shaded. Frames should be sent
+    /// uncropped for the guide to show anything.
+    pub fn view_with_crop_guide<'a, Message: 'a>(
+        &self,
+        aspect_ratio: f32,
+    ) -> iced::Element<'a, Message> {
+        let frame = self
+            .frame_size()
+            .map(|size| iced::Size::new(size.width as f32, size.height as f32));
+        let image = iced::widget::Image::new(self.handle())
+            .content_fit(iced::ContentFit::Contain)
+            .width(iced::Length::Fill)
+            .height(iced::Length::Fill);
+        match frame {
+            Some(frame) => iced::widget::stack([
+                image.into(),
+                iced::widget::canvas(crop_guide::CropGuide {
+                    frame,
+                    aspect_ratio,
+                })
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .into(),
+            ])
+            .into(),
+            None => image.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FrameKind {
+    Video,
+    Still,
+}
+
+/// Work for the capture loop to do on the camera between video frames.
+enum Request<C: crate::backend::cameras::CameraBackendCamera> {
+    Still {
+        timeout: Duration,
+        reply: oneshot::Sender<Result<RgbaImage, String>>,
+    },
+    /// Several stills in a row, see `CameraBackendCamera::capture_still_burst`.
+    Burst {
+        count: usize,
+        interval: Duration,
+        timeout: Duration,
+        reply: oneshot::Sender<Result<Vec<RgbaImage>, String>>,
+    },
+    /// Runs on a blocking thread and sends its own reply.
+    Run(Box<dyn FnOnce(&mut C) + Send>),
+}
+
+/// The capture loop's hold on the camera. The loop keeps it locked for as
+/// long as it runs and is the only thing that touches it, so video frames,
+/// stills and settings changes can't deadlock each other. Blocking work takes
+/// the guard along to a blocking thread and hands it back afterwards.
+struct CameraOwner<C> {
+    shared: SharedCamera<C>,
+    /// `None` while a blocking call that panicked has dropped it.
+    guard: Option<tokio::sync::OwnedMutexGuard<C>>,
+}
+
+impl<C: crate::backend::cameras::CameraBackendCamera + 'static> CameraOwner<C> {
+    async fn new(shared: SharedCamera<C>) -> Self {
+        let guard = shared.clone().lock_owned().await;
+        Self {
+            shared,
+            guard: Some(guard),
+        }
+    }
+
+    async fn get(&mut self) -> &mut C {
+        if self.guard.is_none() {
+            self.guard = Some(self.shared.clone().lock_owned().await);
+        }
+        self.guard.as_mut().expect("camera was just locked")
+    }
+
+    /// Runs `f` on a blocking thread. Fails with a `JoinError` if it panicked.
+    async fn blocking<T: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut C) -> T + Send + 'static,
+    ) -> Result<T, tokio::task::JoinError> {
+        self.get().await;
+        let mut guard = self.guard.take().expect("camera was just locked");
+        let (guard, result) = tokio::task::spawn_blocking(move || {
+            let result = f(&mut guard);
+            (guard, result)
+        })
+        .await?;
+        self.guard = Some(guard);
+        Ok(result)
+    }
+
+    /// Captures a frame, awaiting the camera directly if it's natively async
+    /// and otherwise on a blocking thread.
+    async fn capture(
+        &mut self,
+        kind: FrameKind,
+    ) -> Result<Result<RgbaImage, C::Error>, tokio::task::JoinError> {
+        if C::NATIVE_ASYNC {
+            let camera = self.get().await;
+            Ok(match kind {
+                FrameKind::Video => camera.capture_video_frame_async().await,
+                FrameKind::Still => camera.capture_still_frame_async().await,
+            })
+        } else {
+            self.blocking(move |camera| match kind {
+                FrameKind::Video => camera.capture_video_frame(),
+                FrameKind::Still => camera.capture_still_frame(),
+            })
+            .await
+        }
+    }
+
+    /// Captures a burst of stills, pacing natively async cameras here since
+    /// they can't block.
+    async fn capture_burst(
+        &mut self,
+        count: usize,
+        interval: Duration,
+    ) -> Result<Result<Vec<RgbaImage>, C::Error>, tokio::task::JoinError> {
+        if !C::NATIVE_ASYNC {
+            return self
+                .blocking(move |camera| camera.capture_still_burst(count, interval))
+                .await;
+        }
+        let mut photos = Vec::with_capacity(count);
+        for taken in 1..=count {
+            let started = Instant::now();
+            match self.get().await.capture_still_frame_async().await {
+                Ok(photo) => photos.push(photo),
+                Err(err) => return Ok(Err(err)),
+            }
+            if taken < count {
+                tokio::time::sleep(interval.saturating_sub(started.elapsed())).await;
+            }
+        }
+        Ok(Ok(photos))
+    }
+
+    /// Captures a video frame, leaving it encoded if `encoded` is set and the
+    /// camera can.
+    async fn capture_video(
+        &mut self,
+        encoded: bool,
+    ) -> Result<Result<VideoFrame, C::Error>, tokio::task::JoinError> {
+        if encoded && !C::NATIVE_ASYNC {
+            self.blocking(|camera| camera.capture_video_frame_encoded())
+                .await
+        } else {
+            Ok(self.capture(FrameKind::Video).await?.map(VideoFrame::Rgba))
+        }
+    }
+
+    async fn serve(&mut self, request: Request<C>) {
+        match request {
+            Request::Still { timeout, reply } => {
+                // Giving up drops the capture. Natively async cameras are
+                // cancelled, but a blocking capture keeps the camera until it
+                // returns, so the next request waits for it.
+                let result =
+                    match tokio::time::timeout(timeout, self.capture(FrameKind::Still)).await {
+                        Ok(Ok(Ok(frame))) => Ok(frame),
+                        Ok(Ok(Err(err))) => Err(format!("{:?}", err)),
+                        Ok(Err(err)) => Err(err.to_string()),
+                        Err(_) => Err(format!("no photo within {:?}", timeout)),
+                    };
+                // nobody is waiting if the still was cancelled
+                let _ = reply.send(result);
+            }
+            Request::Burst {
+                count,
+                interval,
+                timeout,
+                reply,
+            } => {
+                let result = match tokio::time::timeout(
+                    timeout,
+                    self.capture_burst(count, interval),
+                )
+                .await
+                {
+                    Ok(Ok(Ok(frames))) => Ok(frames),
+                    Ok(Ok(Err(err))) => Err(format!("{:?}", err)),
+                    Ok(Err(err)) => Err(err.to_string()),
+                    Err(_) => Err(format!("no burst within {:?}", timeout)),
+                };
+                let _ = reply.send(result);
+            }
+            Request::Run(f) => {
+                // a panic drops the reply, which the requester sees as an error
+                let _ = self.blocking(f).await;
+            }
+        }
+    }
+}
+
+fn capture_loop<C: crate::backend::cameras::CameraBackendCamera + 'static>(
+    camera: SharedCamera<C>,
+    requests: Requests<C>,
+    options: Arc<Mutex<CameraFeedOptions>>,
+    histogram: Arc<Mutex<Option<Histogram>>>,
+    target_fps: f32,
+    reopen: Option<Reopen<C>>,
+) -> impl iced::futures::Stream<Item = CameraMessage> {
+    iced::stream::channel(1, move |mut sender| async move {
+        // a loop from before a restart may still hold these, so wait for it
+        let mut requests = requests.lock_owned().await;
+        let mut camera = CameraOwner::new(camera).await;
+
+        let mut interval = tokio::time::interval(Duration::from_secs_f32(1.0 / target_fps));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        let mut consecutive_failures = 0u32;
+        let mut stats = FrameStats::new();
+        let mut paused_since: Option<Instant> = None;
+        // frames captured since the feed was started or last resumed
+        let mut live_frames = 0u32;
+        let mut frames_until_histogram = 0u32;
+
+        loop {
+            let paused = paused_since.is_some() && lock(&options).paused;
+            tokio::select! {
+                // requests first, so a still never waits for more than the
+                // video frame that's already being captured
+                biased;
+                request = requests.recv() => match request {
+                    Some(request) => {
+                        camera.serve(request).await;
+                        continue;
+                    }
+                    // every copy of the feed is gone
+                    None => return,
+                },
+                _ = interval.tick(), if !paused => {}
+                _ = tokio::time::sleep(PAUSED_POLL_INTERVAL), if paused => {
+                    if sender.is_closed() {
+                        return;
+                    }
+                    continue;
+                }
+            }
+
+            let options = *lock(&options);
+            // only still set here if the feed was just resumed
+            if let Some(since) = paused_since.take() {
+                log::debug!("Camera feed resumed after {:?}", since.elapsed());
+                // start the timing log over so the pause doesn't skew it
+                stats = FrameStats::new();
+                interval.reset();
+            }
+            let started = Instant::now();
+            let result = match camera.capture_video(!options.needs_pixels()).await {
+                // nothing to do to it, so skip decoding it twice. The
+                // histogram isn't measured for these.
+                Ok(Ok(frame @ VideoFrame::Jpeg(_))) => {
+                    video_frame_handle(frame, options).map_err(|err| (err.to_string(), true))
+                }
+                Ok(Ok(VideoFrame::Rgba(frame))) => {
+                    let histogram = (frames_until_histogram == 0).then(|| histogram.clone());
+                    frames_until_histogram = frames_until_histogram
+                        .checked_sub(1)
+                        .unwrap_or(HISTOGRAM_INTERVAL - 1);
+                    tokio::task::spawn_blocking(move || {
+                        if let Some(histogram) = histogram {
+                            *lock(&histogram) =
+                                Some(Histogram::from_frame(&frame, HISTOGRAM_DECIMATION));
+                        }
+                        video_frame_handle(VideoFrame::Rgba(frame), options)
+                    })
+                    .await
+                    .map_err(|err| (err.to_string(), true))
+                    .and_then(|handle| handle.map_err(|err| (err.to_string(), true)))
+                }
+                Ok(Err(err)) => {
+                    let connected = camera
+                        .blocking(|camera| camera.is_connected())
+                        .await
+                        .unwrap_or(true);
+                    Err((format!("{:?}", err), connected))
+                }
+                Err(err) => Err((err.to_string(), true)),
+            };
+
+            match result {
+                Ok(handle) => {
+                    if consecutive_failures > 0 {
+                        log::info!(
+                            "Camera recovered after {} failed captures",
+                            consecutive_failures
+                        );
+                        consecutive_failures = 0;
+                    }
+                    stats.record_capture(started.elapsed());
+                    live_frames = live_frames.saturating_add(1);
+                    if options.paused {
+                        // keep this frame, which has the paused options applied
+                        log::debug!("Camera feed paused after {} frames", live_frames);
+                        paused_since = Some(Instant::now());
+                        live_frames = 0;
+                    }
+                    if let Err(err) = sender.try_send(CameraMessage::NewFrame(handle)) {
+                        if err.is_disconnected() {
+                            // the subscription was dropped
+                            return;
+                        }
+                        stats.record_drop();
+                    }
+                }
+                Err((err, connected)) => {
+                    consecutive_failures = consecutive_failures.saturating_add(1);
+                    let delay = INITIAL_RETRY_DELAY
+                        .saturating_mul(1 << (consecutive_failures - 1).min(16))
+                        .min(MAX_RETRY_DELAY);
+                    log::warn!(
+                        "Failed to capture a video frame ({} in a row), retrying in {:?}: {}",
+                        consecutive_failures,
+                        delay,
+                        err
+                    );
+                    let message = if connected {
+                        CameraMessage::CaptureFailed
+                    } else {
+                        CameraMessage::Disconnected
+                    };
+                    if sender.send(message).await.is_err() {
+                        return;
+                    }
+                    tokio::time::sleep(delay).await;
+                    if let Some(reopen) = reopen.as_ref().filter(|_| !connected) {
+                        let reopen = reopen.clone();
+                        match tokio::task::spawn_blocking(move || (*lock(&reopen))())
+                            .await
+                            .unwrap_or_else(|err| Err(err.to_string()))
+                        {
+                            Ok(reopened) => {
+                                log::info!("Camera reconnected");
+                                *camera.get().await = reopened;
+                            }
+                            Err(err) => log::debug!("Camera is still missing: {}", err),
+                        }
+                    }
+                    interval.reset();
+                }
+            }
+
+            stats.log_if_due();
+        }
+    })
+}
+
+/// Running totals for the capture loop's periodic timing log.
+struct FrameStats {
+    since: Instant,
+    captured: u32,
+    dropped: u32,
+    capture_time: Duration,
+}
+
+impl FrameStats {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            captured: 0,
+            dropped: 0,
+            capture_time: Duration::ZERO,
+        }
+    }
+
+    fn record_capture(&mut self, elapsed: Duration) {
+        self.captured += 1;
+        self.capture_time += elapsed;
+    }
+
+    fn record_drop(&mut self) {
+        self.dropped += 1;
+    }
+
+    fn log_if_due(&mut self) {
+        let elapsed = self.since.elapsed();
+        if elapsed < TIMING_LOG_INTERVAL {
+            return;
+        }
+        if self.captured > 0 {
+            log::debug!(
+                "Camera feed: {:.1} fps, {:.1} ms per frame, {} dropped",
+                self.captured as f32 / elapsed.as_secs_f32(),
+                self.capture_time.as_secs_f32() * 1000.0 / self.captured as f32,
+                self.dropped
+            );
+        }
+        *self = Self::new();
+    }
+}
+
+/// Locks `mutex`, ignoring poisoning: a capture that panicked halfway through
+/// shouldn't take the whole feed down with it.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Turns a video frame into a handle to show, with `options` applied. A JPEG
+/// is handed to iced as it is if the options don't need its pixels, and
+/// decoded when it's drawn. Only its header is read here, for its size.
+pub fn video_frame_handle(
+    frame: VideoFrame,
+    options: CameraFeedOptions,
+) -> Result<FrameHandle, image::ImageError> {
+    match frame {
+        VideoFrame::Jpeg(data) if !options.needs_pixels() => {
+            use image::ImageDecoder;
+            let (width, height) =
+                image::codecs::jpeg::JpegDecoder::new(std::io::Cursor::new(&data))?.dimensions();
+            Ok(FrameHandle {
+                handle: Handle::from_bytes(data),
+                size: iced::Size::new(width, height),
+            })
+        }
+        frame => {
+            let frame = image_postprocessing(frame.into_rgba()?, options);
+            let size = iced::Size::new(frame.width(), frame.height());
+            Ok(FrameHandle {
+                handle: Frame::from(frame).to_handle(),
+                size,
+            })
+        }
+    }
 }
 
 fn image_postprocessing(
     frame: image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
     options: CameraFeedOptions,
 ) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    if frame.width() == 0 || frame.height() == 0 {
+        log::warn!(
+            "Camera returned an empty {}x{} frame, leaving it as is",
+            frame.width(),
+            frame.height()
+        );
+        return frame;
+    }
+
+    // rotate the frame so the crop below works with the upright dimensions
+    let frame = match options.rotation {
+        Rotation::None => frame,
+        Rotation::Rotate90 => image::imageops::rotate90(&frame),
+        Rotation::Rotate180 => image::imageops::rotate180(&frame),
+        Rotation::Rotate270 => image::imageops::rotate270(&frame),
+    };
+
+    // crop in on the zoomed region
+    let frame = zoom(frame, options.zoom, options.center);
+
     // crop the frame to meet the aspect ratio
-    let mut frame = if let Some(aspect_ratio) = options.aspect_ratio {
-        let frame_aspect_ratio = frame.width() as f32 / frame.height() as f32;
-        let new_width;
-        let new_height;
-        let left_offset;
-        let top_offset;
-        if aspect_ratio < frame_aspect_ratio {
-            // trim off left and right
-            new_height = frame.height();
-            new_width = (frame.height() as f32 * aspect_ratio) as u32;
-            left_offset = (frame.width() - new_width) / 2;
-            top_offset = 0;
-        } else if aspect_ratio > frame_aspect_ratio {
-            // trim off top and bottom
-            new_width = frame.width();
-            new_height = (frame.width() as f32 / aspect_ratio) as u32;
-            top_offset = (frame.height() - new_height) / 2;
-            left_offset = 0;
-        } else {
-            // perfect aspect ratio!
-            new_width = frame.width();
-            new_height = frame.height();
-            top_offset = 0;
-            left_offset = 0;
-        }
-        image::imageops::crop_imm(&frame, left_offset, top_offset, new_width, new_height).to_image()
-    // this might be pricy...
-    } else {
-        frame
+    let mut frame = match options.aspect_ratio {
+        Some(aspect_ratio) => crop_to_aspect_ratio(frame, aspect_ratio),
+        None => frame,
     };
 
+    // apply the color filter
+    apply_filter_in_place(&mut frame, options.filter);
+
     // mirror the frame
     if options.mirror {
         image::imageops::flip_horizontal_in_place(&mut frame);
@@ -189,20 +940,331 @@ fn image_postprocessing(
     border_radius::round(&mut frame, &options.radius);
 
     // apply blur
+    if options.blur_sigma > 0.0 {
+        frame = blur::gaussian(&frame, options.blur_sigma);
+    }
     if options.blur > 0.0 {
         frame = image::imageops::thumbnail(
             &frame,
-            (frame.width() as f32 / options.blur) as u32,
-            (frame.height() as f32 / options.blur) as u32,
+            ((frame.width() as f32 / options.blur) as u32).max(1),
+            ((frame.height() as f32 / options.blur) as u32).max(1),
         )
         // We could do:
         // frame = image::imageops::blur(&frame, options.blur);
         // but the performance hit is too high for this kind of application
     }
-    image::imageops::resize(
+
+    // shrink the frame until its longest side fits
+    let longest = frame.width().max(frame.height());
+    match options.max_dimension {
+        Some(max_dimension) if longest > max_dimension => {
+            let scale = max_dimension.max(1) as f64 / longest as f64;
+            image::imageops::resize(
+                &frame,
+                ((frame.width() as f64 * scale).round() as u32).max(1),
+                ((frame.height() as f64 * scale).round() as u32).max(1),
+                image::imageops::FilterType::Triangle,
+            )
+        }
+        _ => frame,
+    }
+}
+
+/// Crops `frame` to `1 / factor` of its size around `center`, keeping the
+/// crop inside the frame and at least 1px in each dimension. Factors below 1.0
+/// are treated as 1.0.
+fn zoom(frame: RgbaImage, factor: f32, center: Option<(f32, f32)>) -> RgbaImage {
+    if factor.is_nan() || factor <= 1.0 {
+        return frame;
+    }
+    let width = ((frame.width() as f32 / factor) as u32).clamp(1, frame.width());
+    let height = ((frame.height() as f32 / factor) as u32).clamp(1, frame.height());
+    let (center_x, center_y) = center.unwrap_or((0.5, 0.5));
+    // offsets are kept even so chroma-subsampled sources don't shift color
+    let left = ((frame.width() as f32 * center_x) as i64 - width as i64 / 2)
+        .clamp(0, (frame.width() - width) as i64) as u32
+        & !1;
+    let top = ((frame.height() as f32 * center_y) as i64 - height as i64 / 2)
+        .clamp(0, (frame.height() - height) as i64) as u32
+        & !1;
+    image::imageops::crop_imm(&frame, left, top, width, height).to_image()
+}
+
+/// Trims the sides or the top and bottom off `frame`, keeping it centered, so
+/// its width divided by its height is `aspect_ratio`. The crop is rounded to
+/// the nearest pixel and never larger than the frame, so frames that are a
+/// pixel off or smaller than expected are still cropped evenly.
+fn crop_to_aspect_ratio(frame: RgbaImage, aspect_ratio: f32) -> RgbaImage {
+    let (width, height) = frame.dimensions();
+    if width == 0 || height == 0 || !aspect_ratio.is_finite() || aspect_ratio <= 0.0 {
+        return frame;
+    }
+    let aspect_ratio = aspect_ratio as f64;
+    let wanted_width = ((height as f64 * aspect_ratio).round() as u32).clamp(1, width);
+    let (new_width, new_height) = if wanted_width < width {
+        // trim off left and right
+        (wanted_width, height)
+    } else {
+        // trim off top and bottom
+        let wanted_height = ((width as f64 / aspect_ratio).round() as u32).clamp(1, height);
+        (width, wanted_height)
+    };
+    if (new_width, new_height) == (width, height) {
+        // perfect aspect ratio!
+        return frame;
+    }
+    image::imageops::crop_imm(
         &frame,
-        ((frame.width() as f64) / 1.4) as u32,
-        ((frame.height() as f64) / 1.4) as u32,
-        image::imageops::FilterType::Triangle,
+        (width - new_width) / 2,
+        (height - new_height) / 2,
+        new_width,
+        new_height,
     )
+    .to_image()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{frame, ScriptedCamera, FRAME_HEIGHT, FRAME_WIDTH};
+
+    /// A frame whose red channel is the x coordinate, to see where a crop
+    /// was taken from.
+    fn ruler(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, _| image::Rgba([x as u8, 0, 0, 255]))
+    }
+
+    /// A frame whose red and green channels are the x and y coordinates.
+    fn grid(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([x as u8, y as u8, 0, 255])
+        })
+    }
+
+    /// Where a crop of `grid` was taken from.
+    fn origin(crop: &RgbaImage) -> (u8, u8) {
+        let pixel = crop.get_pixel(0, 0);
+        (pixel[0], pixel[1])
+    }
+
+    fn cropped(width: u32, height: u32, aspect_ratio: f32) -> (u32, u32) {
+        crop_to_aspect_ratio(frame(width, height), aspect_ratio).dimensions()
+    }
+
+    #[test]
+    fn crop_to_aspect_ratio_trims_the_longer_side_evenly() {
+        assert_eq!(cropped(1920, 1080, 1.0), (1080, 1080));
+        assert_eq!(cropped(1080, 1920, 1.0), (1080, 1080));
+        assert_eq!(cropped(1920, 1080, 1.5), (1620, 1080));
+        let centered = crop_to_aspect_ratio(ruler(10, 4), 1.0);
+        assert_eq!(centered.dimensions(), (4, 4));
+        assert_eq!(centered.get_pixel(0, 0)[0], 3);
+    }
+
+    #[test]
+    fn crop_to_aspect_ratio_rounds_frames_a_pixel_off() {
+        assert_eq!(cropped(1500, 1000, 1.5), (1500, 1000));
+        assert_eq!(cropped(1501, 1000, 1.5), (1500, 1000));
+        assert_eq!(cropped(1500, 1001, 1.5), (1500, 1000));
+        // 1499x999.33 rounds down, 1498.5x999 rounds up
+        assert_eq!(cropped(1499, 1000, 1.5), (1499, 999));
+        assert_eq!(cropped(1500, 999, 1.5), (1499, 999));
+    }
+
+    #[test]
+    fn crop_to_aspect_ratio_handles_frames_smaller_than_expected() {
+        assert_eq!(cropped(640, 100, 1.5), (150, 100));
+        assert_eq!(cropped(100, 640, 1.5), (100, 67));
+        assert_eq!(cropped(1, 1000, 1.5), (1, 1));
+        assert_eq!(cropped(1000, 1, 1.5), (2, 1));
+    }
+
+    #[test]
+    fn crop_to_aspect_ratio_returns_empty_frames_as_they_are() {
+        assert_eq!(cropped(0, 0, 1.5), (0, 0));
+        assert_eq!(cropped(0, 1080, 1.5), (0, 1080));
+        assert_eq!(cropped(1920, 0, 1.5), (1920, 0));
+    }
+
+    #[test]
+    fn zoom_shrinks_by_the_factor() {
+        for (factor, expected) in [
+            (1.5, (1280, 720)),
+            (2.0, (960, 540)),
+            (3.0, (640, 360)),
+            (4.0, (480, 270)),
+        ] {
+            let zoomed = zoom(frame(1920, 1080), factor, None);
+            assert_eq!(zoomed.dimensions(), expected, "{}x", factor);
+        }
+        assert_eq!(zoom(frame(1920, 1080), 10_000.0, None).dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn zoom_keeps_offsets_even() {
+        // centered, 25,15 would be the exact top left
+        let zoomed = zoom(grid(100, 60), 2.0, None);
+        assert_eq!(zoomed.dimensions(), (50, 30));
+        assert_eq!(origin(&zoomed), (24, 14));
+
+        // 30-16,42-10 is already even
+        let zoomed = zoom(grid(100, 60), 3.0, Some((0.3, 0.7)));
+        assert_eq!(zoomed.dimensions(), (33, 20));
+        assert_eq!(origin(&zoomed), (14, 32));
+    }
+
+    #[test]
+    fn zoom_stays_inside_the_frame() {
+        let zoomed = zoom(grid(100, 60), 3.0, Some((0.0, 0.0)));
+        assert_eq!(origin(&zoomed), (0, 0));
+        // the furthest it can go is 67,40, rounded down to even
+        let zoomed = zoom(grid(100, 60), 3.0, Some((1.0, 1.0)));
+        assert_eq!(zoomed.dimensions(), (33, 20));
+        assert_eq!(origin(&zoomed), (66, 40));
+    }
+
+    #[test]
+    fn zoom_of_1_or_less_leaves_the_frame_alone() {
+        for factor in [1.0, 0.5, 0.0, -2.0, f32::NAN] {
+            assert_eq!(zoom(grid(100, 60), factor, Some((0.2, 0.2))), grid(100, 60));
+        }
+    }
+
+    fn shrunk(width: u32, height: u32, max_dimension: Option<u32>) -> (u32, u32) {
+        let options = CameraFeedOptions {
+            max_dimension,
+            ..Default::default()
+        };
+        image_postprocessing(frame(width, height), options).dimensions()
+    }
+
+    #[test]
+    fn max_dimension_fits_the_longest_side() {
+        assert_eq!(shrunk(1920, 1080, Some(640)), (640, 360));
+        assert_eq!(shrunk(1080, 1920, Some(640)), (360, 640));
+        assert_eq!(shrunk(1000, 1000, Some(320)), (320, 320));
+        // 213.33 rounds down
+        assert_eq!(shrunk(1920, 1280, Some(320)), (320, 213));
+    }
+
+    #[test]
+    fn max_dimension_leaves_smaller_frames_alone() {
+        assert_eq!(shrunk(320, 240, Some(640)), (320, 240));
+        assert_eq!(shrunk(640, 480, Some(640)), (640, 480));
+        assert_eq!(shrunk(1920, 1080, None), (1920, 1080));
+    }
+
+    #[test]
+    fn max_dimension_keeps_thin_frames_a_pixel_wide() {
+        assert_eq!(shrunk(4000, 2, Some(100)), (100, 1));
+        assert_eq!(shrunk(1000, 1000, Some(0)), (1, 1));
+    }
+
+    #[test]
+    fn shrinking_alone_leaves_encoded_frames_for_iced() {
+        let shrunk = CameraFeedOptions {
+            max_dimension: Some(320),
+            ..Default::default()
+        };
+        assert!(!shrunk.needs_pixels());
+        assert!(CameraFeedOptions {
+            mirror: true,
+            ..shrunk
+        }
+        .needs_pixels());
+    }
+
+    /// Runs `feed`'s capture loop until it delivers a frame, and returns what
+    /// it sent, after handing each message to the feed.
+    async fn until_new_frame(feed: &mut CameraFeed<ScriptedCamera>) -> Vec<&'static str> {
+        use iced::futures::StreamExt;
+        let stream = capture_loop(
+            feed.camera.clone(),
+            feed.requests.clone(),
+            feed.options.clone(),
+            feed.last_histogram.clone(),
+            DEFAULT_TARGET_FPS,
+            feed.reopen.clone().map(|reopen| reopen.0),
+        );
+        let mut stream = std::pin::pin!(stream);
+        let mut sent = Vec::new();
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(10), stream.next())
+                .await
+                .expect("timed out waiting for a frame")
+                .expect("the capture loop stopped");
+            sent.push(match message {
+                CameraMessage::NewFrame(_) => "frame",
+                CameraMessage::CaptureFailed => "failed",
+                CameraMessage::Disconnected => "disconnected",
+            });
+            let done = matches!(message, CameraMessage::NewFrame(_));
+            feed.update(message);
+            if done {
+                return sent;
+            }
+            // what the UI would see in between
+            assert!(feed.consecutive_failures > 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn crop_guide_knows_the_size_of_frames_passed_through() {
+        let camera = ScriptedCamera::new();
+        camera.send_jpeg();
+        let mut feed = CameraFeed::new(camera, CameraFeedOptions::default());
+        until_new_frame(&mut feed).await;
+        assert!(matches!(feed.handle(), Handle::Bytes(..)));
+        assert_eq!(
+            feed.frame_size(),
+            Some(iced::Size::new(FRAME_WIDTH, FRAME_HEIGHT))
+        );
+    }
+
+    #[tokio::test]
+    async fn capture_loop_recovers_after_failed_captures() {
+        let camera = ScriptedCamera::new();
+        camera.fail_video(3, "timed out");
+        let mut feed = CameraFeed::new(camera.clone(), CameraFeedOptions::default());
+
+        let started = Instant::now();
+        let sent = until_new_frame(&mut feed).await;
+        assert_eq!(sent, ["failed", "failed", "failed", "frame"]);
+        assert_eq!(camera.video_captures(), 4);
+        // backed off 100, 200 and then 400 ms
+        assert!(started.elapsed() >= Duration::from_millis(700));
+        assert_eq!(feed.consecutive_failures, 0);
+        assert!(feed.connected);
+    }
+
+    #[tokio::test]
+    async fn capture_loop_reopens_a_disconnected_camera() {
+        let unplugged = ScriptedCamera::new();
+        unplugged.fail_video(10, "no such device");
+        unplugged.set_connected(false);
+        let replacement = ScriptedCamera::new();
+        let reopens = Arc::new(Mutex::new(0));
+        let mut feed = CameraFeed::new(unplugged.clone(), CameraFeedOptions::default())
+            .with_reopen({
+                let replacement = replacement.clone();
+                let reopens = reopens.clone();
+                move || {
+                    let mut reopens = lock(&reopens);
+                    *reopens += 1;
+                    // still unplugged the first time it's tried
+                    if *reopens == 1 {
+                        Err("no such device".to_string())
+                    } else {
+                        Ok(replacement.clone())
+                    }
+                }
+            });
+
+        let sent = until_new_frame(&mut feed).await;
+        assert_eq!(sent, ["disconnected", "disconnected", "frame"]);
+        assert_eq!(*lock(&reopens), 2);
+        assert_eq!(unplugged.video_captures(), 2);
+        assert_eq!(replacement.video_captures(), 1);
+        assert!(feed.connected);
+    }
 }
@@ -0,0 +1,261 @@
+use std::cell::RefCell;
+
+use image::RgbaImage;
+
+/// The frame is shrunk by this much before blurring, which makes the box
+/// passes 16 times cheaper and loses nothing a strong blur wouldn't.
+const DOWNSCALE: u32 = 4;
+/// Three box blurs in a row are close enough to a gaussian.
+const PASSES: usize = 3;
+
+thread_local! {
+    /// Scratch buffer for the box passes. Frames are blurred on the blocking
+    /// pool, whose threads are reused, so this saves allocating a buffer for
+    /// every frame.
+    static SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Approximates a gaussian blur with a standard deviation of `sigma` pixels of
+/// the original frame. The result is `DOWNSCALE` times smaller than `frame`
+/// and is scaled back up when it's drawn, which keeps it smooth.
+pub fn gaussian(frame: &RgbaImage, sigma: f32) -> RgbaImage {
+    let mut small = shrink(frame);
+    let width = small.width() as usize;
+    SCRATCH.with_borrow_mut(|scratch| {
+        scratch.resize(small.as_raw().len(), 0);
+        for radius in box_radii(sigma / DOWNSCALE as f32) {
+            // rows into the scratch buffer, then columns back into the frame
+            blur_rows(&small, scratch, width, radius);
+            blur_columns(scratch, &mut small, width, radius);
+        }
+    });
+    small
+}
+
+/// Shrinks `frame` by `DOWNSCALE` by averaging blocks of pixels. Much quicker
+/// than `imageops::thumbnail`, which handles any ratio. Pixels past the last
+/// whole block are left out.
+fn shrink(frame: &RgbaImage) -> RgbaImage {
+    let width = (frame.width() / DOWNSCALE).max(1) as usize;
+    let height = (frame.height() / DOWNSCALE).max(1) as usize;
+    let block_width = frame.width() as usize / width;
+    let block_height = frame.height() as usize / height;
+    let block_pixels = (block_width * block_height) as u32;
+    let stride = frame.width() as usize * 4;
+    // a block is at most 7 pixels high, so its columns fit in a u16
+    let mut columns = vec![0u16; stride];
+    let mut small = Vec::with_capacity(width * height * 4);
+    for rows in frame
+        .as_raw()
+        .chunks_exact(stride * block_height)
+        .take(height)
+    {
+        // whole rows at a time, which the compiler can vectorize
+        columns.fill(0);
+        for row in rows.chunks_exact(stride) {
+            for (column, value) in columns.iter_mut().zip(row) {
+                *column += *value as u16;
+            }
+        }
+        for block in columns.chunks_exact(block_width * 4).take(width) {
+            let mut sum = [0u32; 4];
+            for pixel in block.chunks_exact(4) {
+                for (total, value) in sum.iter_mut().zip(pixel) {
+                    *total += *value as u32;
+                }
+            }
+            small.extend(sum.map(|total| ((total + block_pixels / 2) / block_pixels) as u8));
+        }
+    }
+    RgbaImage::from_raw(width as u32, height as u32, small)
+        .expect("shrunk frame is the size it was made for")
+}
+
+/// Radii of `PASSES` box blurs that add up to a gaussian with the given
+/// `sigma`, from "Fast Almost-Gaussian Filtering" by Peter Kovesi.
+fn box_radii(sigma: f32) -> [usize; PASSES] {
+    let n = PASSES as f32;
+    let ideal_width = (12.0 * sigma * sigma / n + 1.0).sqrt();
+    let mut lower = ideal_width.floor() as i32;
+    if lower % 2 == 0 {
+        lower -= 1;
+    }
+    let lower = lower.max(1);
+    let upper = lower + 2;
+    let l = lower as f32;
+    let lower_count = ((12.0 * sigma * sigma - n * l * l - 4.0 * n * l - 3.0 * n)
+        / (-4.0 * l - 4.0))
+        .round()
+        .max(0.0) as usize;
+    std::array::from_fn(|i| {
+        let width = if i < lower_count { lower } else { upper };
+        (width as usize - 1) / 2
+    })
+}
+
+/// Divides the sum of a `radius` box by its size, rounding to the nearest.
+/// Dividing is slow enough to matter in the box passes, so it's multiplied by
+/// the reciprocal as a 32 bit fraction instead. Rounding the reciprocal up
+/// keeps that exact for sums this small.
+fn averager(radius: usize) -> impl Fn(u32) -> u8 {
+    let window = (2 * radius + 1) as u32;
+    let reciprocal = (1u64 << 32).div_ceil(window as u64);
+    move |total| (((total + window / 2) as u64 * reciprocal) >> 32) as u8
+}
+
+/// One box blur pass along each row of an image `width` pixels wide. Keeps a
+/// running sum, so each pixel costs the same whatever the radius. Edge pixels
+/// are repeated past the ends of a row.
+fn blur_rows(src: &[u8], dst: &mut [u8], width: usize, radius: usize) {
+    let average = averager(radius);
+    for (row, out) in src
+        .chunks_exact(width * 4)
+        .zip(dst.chunks_exact_mut(width * 4))
+    {
+        let pixel = |i: usize| &row[i.min(width - 1) * 4..][..4];
+        let mut sum = [0u32; 4];
+        // the window around the first pixel, which hangs off the start
+        for i in 0..=radius {
+            let weight = if i == 0 { radius as u32 + 1 } else { 1 };
+            for (total, value) in sum.iter_mut().zip(pixel(i)) {
+                *total += weight * *value as u32;
+            }
+        }
+        for (i, out) in out.chunks_exact_mut(4).enumerate() {
+            for (value, total) in out.iter_mut().zip(sum) {
+                *value = average(total);
+            }
+            let (leaving, entering) = (pixel(i.saturating_sub(radius)), pixel(i + radius + 1));
+            for ((total, entering), leaving) in sum.iter_mut().zip(entering).zip(leaving) {
+                *total = *total + *entering as u32 - *leaving as u32;
+            }
+        }
+    }
+}
+
+/// One box blur pass down each column of an image `width` pixels wide, like
+/// `blur_rows`. Every column moves down a row at once, so it reads whole rows
+/// instead of jumping between them.
+fn blur_columns(src: &[u8], dst: &mut [u8], width: usize, radius: usize) {
+    let average = averager(radius);
+    let stride = width * 4;
+    let height = src.len() / stride;
+    let row = |y: usize| &src[y.min(height - 1) * stride..][..stride];
+    // the window around the first row, which hangs off the top
+    let mut sums: Vec<u32> = row(0)
+        .iter()
+        .map(|value| (radius as u32 + 1) * *value as u32)
+        .collect();
+    for y in 1..=radius {
+        for (total, value) in sums.iter_mut().zip(row(y)) {
+            *total += *value as u32;
+        }
+    }
+    for (y, out) in dst.chunks_exact_mut(stride).enumerate() {
+        for (value, total) in out.iter_mut().zip(&sums) {
+            *value = average(*total);
+        }
+        let (leaving, entering) = (row(y.saturating_sub(radius)), row(y + radius + 1));
+        for ((total, entering), leaving) in sums.iter_mut().zip(entering).zip(leaving) {
+            *total = *total + *entering as u32 - *leaving as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// A frame with something different in every channel of every pixel.
+    fn noise(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let n = (x * 7919 + y * 104_729) % 251;
+            Rgba([
+                n as u8,
+                (n * 3 % 256) as u8,
+                (255 - n) as u8,
+                (x * 20 % 256) as u8,
+            ])
+        })
+    }
+
+    /// The box blur of `frame` worked out the slow way, one window at a time.
+    fn naive_box_blur(frame: &RgbaImage, radius: i64) -> RgbaImage {
+        let clamped = |value: i64, len: u32| value.clamp(0, len as i64 - 1) as u32;
+        let along_rows = RgbaImage::from_fn(frame.width(), frame.height(), |x, y| {
+            let window = (-radius..=radius)
+                .map(|d| frame.get_pixel(clamped(x as i64 + d, frame.width()), y));
+            average(window, radius)
+        });
+        RgbaImage::from_fn(frame.width(), frame.height(), |x, y| {
+            let window = (-radius..=radius)
+                .map(|d| along_rows.get_pixel(x, clamped(y as i64 + d, frame.height())));
+            average(window, radius)
+        })
+    }
+
+    fn average<'a>(pixels: impl Iterator<Item = &'a Rgba<u8>>, radius: i64) -> Rgba<u8> {
+        let window = 2 * radius as u32 + 1;
+        let mut sum = [0u32; 4];
+        for pixel in pixels {
+            for (total, value) in sum.iter_mut().zip(pixel.0) {
+                *total += value as u32;
+            }
+        }
+        Rgba(sum.map(|total| ((total + window / 2) / window) as u8))
+    }
+
+    #[test]
+    fn box_passes_match_a_naive_box_blur() {
+        let frame = noise(23, 17);
+        for radius in 0..12 {
+            let mut blurred = frame.clone();
+            let mut scratch = vec![0; frame.as_raw().len()];
+            blur_rows(&frame, &mut scratch, 23, radius);
+            blur_columns(&scratch, &mut blurred, 23, radius);
+            assert_eq!(
+                blurred,
+                naive_box_blur(&frame, radius as i64),
+                "radius {}",
+                radius
+            );
+        }
+    }
+
+    #[test]
+    fn shrink_averages_blocks() {
+        // blocks of 5 by 4, and the last row is left over
+        let frame = RgbaImage::from_fn(10, 9, |x, y| {
+            if x < 5 {
+                Rgba([0, 0, 0, 255])
+            } else if y < 4 {
+                Rgba([200, 100, (y % 2 * 100) as u8, 255])
+            } else if y < 8 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([255, 0, 0, 255])
+            }
+        });
+        let small = shrink(&frame);
+        assert_eq!(small.dimensions(), (2, 2));
+        assert_eq!(small.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(small.get_pixel(1, 0), &Rgba([200, 100, 50, 255]));
+        assert_eq!(small.get_pixel(1, 1), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn shrink_keeps_tiny_frames_a_pixel_wide() {
+        let small = shrink(&noise(3, 9));
+        assert_eq!(small.dimensions(), (1, 2));
+    }
+
+    #[test]
+    fn flat_frame_stays_flat() {
+        let gray = Rgba([90, 120, 150, 255]);
+        let blurred = gaussian(&RgbaImage::from_pixel(64, 48, gray), 24.0);
+        assert_eq!(blurred.dimensions(), (16, 12));
+        assert!(blurred.pixels().all(|pixel| *pixel == gray));
+    }
+}
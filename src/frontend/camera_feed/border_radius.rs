@@ -1,7 +1,12 @@
 use iced::border::Radius;
 use image::{ImageBuffer, Rgba};
 
-/// See: https://users.rust-lang.org/t/how-to-trim-image-to-circle-image-without-jaggy/70374/2
+/// Samples per axis for pixels the arc passes through. 16x16 samples give 256
+/// levels of coverage, which is all a u8 alpha can show.
+const SUPERSAMPLING: u32 = 16;
+
+/// Makes the corners of `img` transparent outside of `radius`, fading the
+/// pixels on the arc by how much of them lies inside it.
 pub(super) fn round(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, radius: &Radius) {
     let (width, height) = img.dimensions();
     debug_assert!(radius.top_left + radius.top_right <= width as f32);
@@ -10,112 +15,158 @@ pub(super) fn round(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, radius: &Radius) {
     debug_assert!(radius.top_right + radius.bottom_right <= height as f32);
 
     // top left
-    border_radius(img, radius.top_left as u32, |x, y| (x - 1, y - 1));
+    border_radius(img, radius.top_left, |x, y| (x, y));
     // top right
-    border_radius(img, radius.top_right as u32, |x, y| (width - x, y - 1));
+    border_radius(img, radius.top_right, |x, y| (width - 1 - x, y));
     // bottom right
-    border_radius(img, radius.bottom_right as u32, |x, y| {
-        (width - x, height - y)
+    border_radius(img, radius.bottom_right, |x, y| {
+        (width - 1 - x, height - 1 - y)
     });
     // bottom left
-    border_radius(img, radius.bottom_left as u32, |x, y| (x - 1, height - y));
+    border_radius(img, radius.bottom_left, |x, y| (x, height - 1 - y));
 }
 
+/// Rounds one corner. `coordinates` maps a pixel counted from that corner of
+/// the image to the pixel in the image. Only the `r` by `r` square in the
+/// corner is touched.
 fn border_radius(
     img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
-    r: u32,
+    r: f32,
     coordinates: impl Fn(u32, u32) -> (u32, u32),
 ) {
-    if r == 0 {
+    if r.is_nan() || r <= 0.0 {
         return;
     }
-    let r0 = r;
-
-    // 16x antialiasing: 16x16 grid creates 256 possible shades, great for u8!
-    let r = 16 * r;
-
-    let mut x = 0;
-    let mut y = r - 1;
-    let mut p: i32 = 2 - r as i32;
-
-    // ...
-
-    let mut alpha: u16 = 0;
-    let mut skip_draw = true;
-
-    let draw = |img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, alpha, x, y| {
-        debug_assert!((1..=256).contains(&alpha));
-        let pixel_alpha = &mut img[coordinates(r0 - x, r0 - y)].0[3];
-        *pixel_alpha = ((alpha * *pixel_alpha as u16 + 128) / 256) as u8;
-    };
-
-    'l: loop {
-        // (comments for bottom_right case:)
-        // remove contents below current position
-        {
-            let i = x / 16;
-            for j in y / 16 + 1..r0 {
-                img[coordinates(r0 - i, r0 - j)].0[3] = 0;
+    let size = (r.ceil() as u32).min(img.width()).min(img.height());
+    for y in 0..size {
+        for x in 0..size {
+            let coverage = coverage(x, y, r);
+            if coverage < 1.0 {
+                let pixel_alpha = &mut img[coordinates(x, y)].0[3];
+                *pixel_alpha = (*pixel_alpha as f32 * coverage).round() as u8;
             }
         }
-        // remove contents right of current position mirrored
-        {
-            let j = x / 16;
-            for i in y / 16 + 1..r0 {
-                img[coordinates(r0 - i, r0 - j)].0[3] = 0;
+    }
+}
+
+/// How much of the pixel at `x`, `y` lies inside the circle of radius `r`
+/// centered at `r`, `r`, from 0.0 to 1.0.
+fn coverage(x: u32, y: u32, r: f32) -> f32 {
+    let (left, top) = (x as f32, y as f32);
+    let (right, bottom) = (left + 1.0, top + 1.0);
+    let nearest = (r - right)
+        .max(left - r)
+        .max(0.0)
+        .hypot((r - bottom).max(top - r).max(0.0));
+    if nearest >= r {
+        return 0.0;
+    }
+    let farthest = (r - left)
+        .abs()
+        .max((right - r).abs())
+        .hypot((r - top).abs().max((bottom - r).abs()));
+    if farthest <= r {
+        return 1.0;
+    }
+
+    // the arc passes through this pixel, so sample it
+    let step = 1.0 / SUPERSAMPLING as f32;
+    let mut inside = 0;
+    for j in 0..SUPERSAMPLING {
+        let dy = top + (j as f32 + 0.5) * step - r;
+        for i in 0..SUPERSAMPLING {
+            let dx = left + (i as f32 + 0.5) * step - r;
+            if dx * dx + dy * dy <= r * r {
+                inside += 1;
             }
         }
+    }
+    inside as f32 / (SUPERSAMPLING * SUPERSAMPLING) as f32
+}
 
-        // draw when moving to next pixel in x-direction
-        if !skip_draw {
-            draw(img, alpha, x / 16 - 1, y / 16);
-            draw(img, alpha, y / 16, x / 16 - 1);
-            alpha = 0;
-        }
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
 
-        for _ in 0..16 {
-            skip_draw = false;
+    use super::*;
 
-            if x >= y {
-                break 'l;
-            }
+    const SIZE: u32 = 80;
 
-            alpha += y as u16 % 16 + 1;
-            if p < 0 {
-                x += 1;
-                p += (2 * x + 2) as i32;
-            } else {
-                // draw when moving to next pixel in y-direction
-                if y % 16 == 0 {
-                    draw(img, alpha, x / 16, y / 16);
-                    draw(img, alpha, y / 16, x / 16);
-                    skip_draw = true;
-                    alpha = (x + 1) as u16 % 16 * 16;
-                }
+    fn opaque() -> RgbaImage {
+        RgbaImage::from_pixel(SIZE, SIZE, Rgba([255, 255, 255, 255]))
+    }
 
-                x += 1;
-                p -= (2 * (y - x) + 2) as i32;
-                y -= 1;
-            }
-        }
+    /// How much of the pixel at `x`, `y` is inside the circle, sampled much
+    /// more finely than `coverage` does.
+    fn exact_coverage(x: u32, y: u32, r: f32) -> f32 {
+        const SAMPLES: u32 = 256;
+        let r = r as f64;
+        let inside = (0..SAMPLES * SAMPLES)
+            .filter(|i| {
+                let dx = x as f64 + (i % SAMPLES) as f64 / SAMPLES as f64 - r;
+                let dy = y as f64 + (i / SAMPLES) as f64 / SAMPLES as f64 - r;
+                dx * dx + dy * dy <= r * r
+            })
+            .count();
+        inside as f32 / (SAMPLES * SAMPLES) as f32
     }
 
-    // one corner pixel left
-    if x / 16 == y / 16 {
-        // column under current position possibly not yet accounted
-        if x == y {
-            alpha += y as u16 % 16 + 1;
+    #[test]
+    fn corner_alpha_matches_the_coverage() {
+        for r in [1.0, 4.0, 10.5, 32.0] {
+            let mut img = opaque();
+            round(&mut img, &Radius::from(r));
+            let corner = r.ceil() as u32;
+            let mut transparent = 0.0;
+            for y in 0..corner {
+                for x in 0..corner {
+                    let alpha = img.get_pixel(x, y)[3] as f32 / 255.0;
+                    let expected = exact_coverage(x, y, r);
+                    // a 16x16 grid can be off by a row of samples
+                    assert!(
+                        (alpha - expected).abs() <= 1.0 / 16.0,
+                        "pixel {},{} with radius {} has alpha {}, expected {}",
+                        x,
+                        y,
+                        r,
+                        alpha,
+                        expected
+                    );
+                    transparent += 1.0 - alpha;
+                }
+            }
+            // what's cut off is the square minus a quarter of the circle
+            let cut_off = r * r * (1.0 - std::f32::consts::FRAC_PI_4);
+            assert!(
+                (transparent - cut_off).abs() <= cut_off * 0.02 + 0.05,
+                "radius {} cut off {}, expected {}",
+                r,
+                transparent,
+                cut_off
+            );
+            // the other corners are the same, mirrored
+            for y in 0..corner {
+                for x in 0..corner {
+                    let alpha = img.get_pixel(x, y)[3];
+                    assert_eq!(img.get_pixel(SIZE - 1 - x, y)[3], alpha);
+                    assert_eq!(img.get_pixel(x, SIZE - 1 - y)[3], alpha);
+                    assert_eq!(img.get_pixel(SIZE - 1 - x, SIZE - 1 - y)[3], alpha);
+                }
+            }
+            // and nothing past them is touched
+            assert_eq!(img.get_pixel(corner, corner)[3], 255);
+            assert_eq!(img.get_pixel(SIZE / 2, 0)[3], 255);
+            assert_eq!(img.get_pixel(0, SIZE / 2)[3], 255);
         }
-        let s = y as u16 % 16 + 1;
-        let alpha = 2 * alpha - s * s;
-        draw(img, alpha, x / 16, y / 16);
     }
 
-    // remove remaining square of content in the corner
-    let range = y / 16 + 1..r0;
-    for i in range.clone() {
-        for j in range.clone() {
-            img[coordinates(r0 - i, r0 - j)].0[3] = 0;
-        }
+    #[test]
+    fn zero_radius_leaves_the_image_as_it_was() {
+        let original = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            Rgba([x as u8, y as u8, (x * y) as u8, (x + y) as u8])
+        });
+        let mut img = original.clone();
+        round(&mut img, &Radius::from(0.0));
+        assert_eq!(img.as_raw(), original.as_raw());
     }
 }
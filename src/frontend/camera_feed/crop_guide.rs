@@ -0,0 +1,70 @@
+use iced::widget::canvas;
+use iced::{mouse, Color, ContentFit, Point, Rectangle, Renderer, Size, Theme};
+
+/// Darkens whatever falls outside the crop.
+const MASK_COLOR: Color = Color::from_rgba(0.0, 0.0, 0.0, 0.6);
+const OUTLINE_COLOR: Color = Color::WHITE;
+const OUTLINE_WIDTH: f32 = 2.0;
+
+/// Draws over a frame shown with `ContentFit::Contain`, shading the parts of
+/// it a crop to `aspect_ratio` will cut off.
+pub(super) struct CropGuide {
+    /// Size of the frame being shown, in its own pixels.
+    pub frame: Size,
+    pub aspect_ratio: f32,
+}
+
+impl<Message> canvas::Program<Message> for CropGuide {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+
+        // where the image widget letterboxes the frame
+        let size = ContentFit::Contain.fit(self.frame, bounds.size());
+        let image = Rectangle::new(
+            Point::new(
+                (bounds.width - size.width) / 2.0,
+                (bounds.height - size.height) / 2.0,
+            ),
+            size,
+        );
+
+        // the same centered crop as `crop_to_aspect_ratio`
+        let crop_size = if image.width / image.height > self.aspect_ratio {
+            Size::new(image.height * self.aspect_ratio, image.height)
+        } else {
+            Size::new(image.width, image.width / self.aspect_ratio)
+        };
+        let crop = Rectangle::new(
+            Point::new(
+                image.x + (image.width - crop_size.width) / 2.0,
+                image.y + (image.height - crop_size.height) / 2.0,
+            ),
+            crop_size,
+        );
+
+        // the crop is centered, so the bands on either side are the same
+        let side = Size::new(crop.x - image.x, image.height);
+        let band = Size::new(image.width, crop.y - image.y);
+        frame.fill_rectangle(image.position(), side, MASK_COLOR);
+        frame.fill_rectangle(Point::new(crop.x + crop.width, image.y), side, MASK_COLOR);
+        frame.fill_rectangle(image.position(), band, MASK_COLOR);
+        frame.fill_rectangle(Point::new(image.x, crop.y + crop.height), band, MASK_COLOR);
+        frame.stroke(
+            &canvas::Path::rectangle(crop.position(), crop.size()),
+            canvas::Stroke::default()
+                .with_color(OUTLINE_COLOR)
+                .with_width(OUTLINE_WIDTH),
+        );
+
+        vec![frame.into_geometry()]
+    }
+}
@@ -0,0 +1,133 @@
+use image::RgbaImage;
+
+/// Luminance at or above which a pixel counts as blown out.
+const BRIGHT_CLIP: u8 = 255;
+/// Luminance below which a pixel counts as crushed to black.
+const DARK_CLIP: u8 = 5;
+
+/// How many pixels of a frame have each luminance, from a sample of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    bins: [u32; 256],
+    total: u32,
+}
+
+/// Which way a frame is badly exposed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clipping {
+    TooBright,
+    TooDark,
+}
+
+impl Histogram {
+    /// Counts every `step`th pixel of every `step`th row, which is plenty
+    /// for spotting clipping and much cheaper than the whole frame.
+    pub(super) fn from_frame(frame: &RgbaImage, step: u32) -> Self {
+        let step = step.max(1) as usize;
+        let mut bins = [0u32; 256];
+        let mut total = 0;
+        for row in frame.rows().step_by(step) {
+            for pixel in row.step_by(step) {
+                let [r, g, b, _] = pixel.0.map(u32::from);
+                // same weights as the grayscale filter, in integers
+                bins[((299 * r + 587 * g + 114 * b) / 1000) as usize] += 1;
+                total += 1;
+            }
+        }
+        Self { bins, total }
+    }
+
+    /// Fraction of the sampled pixels with a luminance in `levels`.
+    pub fn fraction(&self, levels: impl std::ops::RangeBounds<u8>) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let count: u32 = (0..=u8::MAX)
+            .filter(|level| levels.contains(level))
+            .map(|level| self.bins[level as usize])
+            .sum();
+        count as f32 / self.total as f32
+    }
+
+    /// Whether more than `max_fraction` of the pixels are blown out or
+    /// crushed to black. Blown out wins if both are.
+    pub fn clipping(&self, max_fraction: f32) -> Option<Clipping> {
+        if self.fraction(BRIGHT_CLIP..) > max_fraction {
+            Some(Clipping::TooBright)
+        } else if self.fraction(..DARK_CLIP) > max_fraction {
+            Some(Clipping::TooDark)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(64, 48, image::Rgba([value, value, value, 255]))
+    }
+
+    #[test]
+    fn white_frame_is_too_bright() {
+        let histogram = Histogram::from_frame(&plain(255), 8);
+        assert_eq!(histogram.fraction(BRIGHT_CLIP..), 1.0);
+        assert_eq!(histogram.clipping(0.05), Some(Clipping::TooBright));
+    }
+
+    #[test]
+    fn black_frame_is_too_dark() {
+        let histogram = Histogram::from_frame(&plain(0), 8);
+        assert_eq!(histogram.fraction(..DARK_CLIP), 1.0);
+        assert_eq!(histogram.clipping(0.05), Some(Clipping::TooDark));
+    }
+
+    #[test]
+    fn gray_frame_is_fine() {
+        let histogram = Histogram::from_frame(&plain(128), 8);
+        assert_eq!(histogram.fraction(128..=128), 1.0);
+        assert_eq!(histogram.fraction(BRIGHT_CLIP..), 0.0);
+        assert_eq!(histogram.fraction(..DARK_CLIP), 0.0);
+        assert_eq!(histogram.clipping(0.05), None);
+    }
+
+    #[test]
+    fn blown_out_wins_over_crushed() {
+        let frame = RgbaImage::from_fn(64, 48, |x, _| {
+            let value = if x < 32 { 255 } else { 0 };
+            image::Rgba([value, value, value, 255])
+        });
+        let histogram = Histogram::from_frame(&frame, 1);
+        assert_eq!(histogram.fraction(BRIGHT_CLIP..), 0.5);
+        assert_eq!(histogram.fraction(..DARK_CLIP), 0.5);
+        assert_eq!(histogram.clipping(0.25), Some(Clipping::TooBright));
+        assert_eq!(histogram.clipping(0.5), None);
+    }
+
+    #[test]
+    fn only_sampled_pixels_are_counted() {
+        // white exactly where a step of 3 samples, black everywhere else
+        let frame = RgbaImage::from_fn(10, 10, |x, y| {
+            let value = if x % 3 == 0 && y % 3 == 0 { 255 } else { 0 };
+            image::Rgba([value, value, value, 255])
+        });
+        let sampled = Histogram::from_frame(&frame, 3);
+        assert_eq!(sampled.total, 16);
+        assert_eq!(sampled.fraction(BRIGHT_CLIP..), 1.0);
+
+        let every = Histogram::from_frame(&frame, 1);
+        assert_eq!(every.total, 100);
+        assert_eq!(every.fraction(BRIGHT_CLIP..), 0.16);
+        // a step of 0 means every pixel too
+        assert_eq!(Histogram::from_frame(&frame, 0), every);
+    }
+
+    #[test]
+    fn empty_frame_has_no_clipping() {
+        let histogram = Histogram::from_frame(&RgbaImage::new(0, 0), 8);
+        assert_eq!(histogram.fraction(..), 0.0);
+        assert_eq!(histogram.clipping(0.0), None);
+    }
+}
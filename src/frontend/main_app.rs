@@ -1,7 +1,13 @@
-use std::time::Duration;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
 
 use anim::Animation;
 use iced::{
+    border::Radius,
     widget::{
         column, container, horizontal_space, image::Handle, progress_bar, row, text,
         vertical_space, Space,
@@ -10,10 +16,24 @@ use iced::{
 };
 use image::RgbaImage;
 
-use crate::{backend::render_take::render_take, AppPage, KeyMessage, PhotoBoothMessage};
+use crate::{
+    backend::{
+        audio::{Sound, SoundPlayer},
+        cameras::{CameraBackendCamera, CameraStatus, Frame},
+        filters::{apply_filter, apply_filter_in_place, ColorFilter},
+        printing::{configured_printer, print_strip},
+        queue::{self, QueuedSession},
+        render_take::{
+            encode_print_png, render_gif, render_take, render_thumbnail, Caption, RenderAssets,
+            StripTemplate,
+        },
+        servers::{EmailDelivery, Progress},
+    },
+    AppPage, KeyMessage, PhotoBoothMessage,
+};
 
 use super::{
-    camera_feed::{CameraFeed, CameraFeedOptions},
+    camera_feed::{CameraFeed, CameraFeedOptions, Clipping, Rotation},
     loading_spinners,
     title_overlay::{supporting_text, title_overlay, title_text},
 };
@@ -22,24 +42,175 @@ mod animations;
 mod status_overlay;
 
 const PHOTO_ASPECT_RATIO: f32 = 3.0 / 2.0;
-const PHOTO_COUNT: usize = 4;
+/// The secondary camera only needs to show who's waiting, so it runs slowly.
+const SECONDARY_FEED_FPS: f32 = 10.0;
+const SECONDARY_FEED_WIDTH: f32 = 320.0;
+/// Longest side of the sharp live feed. Plenty for a screen, and much less to
+/// process and upload than a full size frame.
+const PREVIEW_MAX_DIMENSION: u32 = 1280;
+/// Longest side of the blurred background, which loses nothing by being tiny.
+const BACKGROUND_MAX_DIMENSION: u32 = 320;
+/// How long each photo shows in the animated GIF.
+const GIF_FRAME_DELAY: Duration = Duration::from_millis(600);
+/// How often the camera's battery and storage are checked.
+const CAMERA_STATUS_INTERVAL: Duration = Duration::from_secs(60);
+/// Battery percentage below which staff are warned to swap it.
+const LOW_BATTERY_PERCENT: u8 = 20;
+/// How long the "let's try that photo again" message shows before the
+/// countdown starts over.
+const RETRY_MESSAGE_DURATION: Duration = Duration::from_secs(3);
+/// Photos in a session that may fail (after their retry) before the session
+/// is given up on.
+const MAX_FAILED_PHOTOS: u32 = 3;
 
 const QR_CODE_QUIET_ZONE: usize = 2;
-const QR_CODE_VERSION: iced::widget::qr_code::Version = iced::widget::qr_code::Version::Normal(5);
-const QR_CODE_SIDE_LENGTH: usize = QR_CODE_QUIET_ZONE * 2 + (5 * 4 + 17);
+/// Smallest version a QR code is made with, so short links don't shrink it
+/// and the placeholder shown while uploading is the same size.
+const QR_CODE_MIN_VERSION: u8 = 5;
+const QR_CODE_MAX_VERSION: u8 = 40;
+const QR_CODE_SIDE_LENGTH: usize = qr_code_side_length(QR_CODE_MIN_VERSION);
+/// Time between the photos of a rapid burst.
+const RAPID_INTERVAL: Duration = Duration::from_secs(1);
+/// Width of the strips the guests pick a template from.
+const TEMPLATE_THUMBNAIL_WIDTH: u32 = 200;
+/// Width the photos are shrunk to when they're shown with a filter on them,
+/// so each change shows straight away.
+const FILTER_PREVIEW_WIDTH: u32 = 480;
+
+/// How the photos of a strip are paced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum CaptureMode {
+    /// A countdown before every photo.
+    #[default]
+    Standard,
+    /// One countdown, then the rest of the photos a second apart.
+    Rapid,
+}
+
+impl CaptureMode {
+    pub const ALL: [CaptureMode; 2] = [Self::Standard, Self::Rapid];
+}
+
+impl std::fmt::Display for CaptureMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Standard => write!(f, "Countdown before each photo"),
+            Self::Rapid => write!(f, "Rapid (one countdown)"),
+        }
+    }
+}
+
+/// Options chosen on the setup page that affect how photos are taken.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CaptureSettings {
+    pub color_filter: ColorFilter,
+    pub rotation: Rotation,
+    pub zoom: f32,
+    pub center: Option<(f32, f32)>,
+    /// Frames the camera discards after opening its still stream.
+    pub warmup_frames: u32,
+    /// Whether `exposure` and `white_balance` are applied instead of letting
+    /// the camera adjust between shots.
+    pub lock_exposure: bool,
+    /// Exposure compensation in stops.
+    pub exposure: f32,
+    /// White balance in kelvin.
+    pub white_balance: u32,
+    /// Seconds to wait for a still before trying again.
+    pub still_timeout: f32,
+    /// Fraction of pixels that may be blown out or black before the guests
+    /// are told the picture looks too bright or too dark.
+    pub clipping_warning: f32,
+    /// Whether the live feed is flipped like a mirror, which is what people
+    /// expect when they see themselves.
+    pub mirror_preview: bool,
+    /// Whether the photos themselves are flipped. Turn this off to have text
+    /// in the background read the right way on the strip.
+    pub mirror_photos: bool,
+    /// Seconds without a key press before a session waiting on the guests is
+    /// abandoned and the start screen shown again.
+    pub idle_timeout: f32,
+    /// Whether the focus is held from the start of a session until the booth
+    /// is back on the start screen, so webcams don't hunt during countdowns.
+    pub lock_focus: bool,
+    pub capture_mode: CaptureMode,
+    /// Number the countdown before each photo starts from.
+    pub countdown_from: u32,
+    /// Whether the animations are sped up, for running through a session
+    /// quickly while setting up.
+    pub fast_animations: bool,
+    /// Whether the countdown beeps and photos make a shutter sound. Needs the
+    /// `audio` feature.
+    pub sound_effects: bool,
+    /// Whether sessions go straight to the spool, to be uploaded and emailed
+    /// once the booth is back online, instead of being uploaded right away.
+    pub offline_mode: bool,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            color_filter: ColorFilter::None,
+            rotation: Rotation::None,
+            zoom: 1.0,
+            center: None,
+            warmup_frames: 5,
+            lock_exposure: false,
+            exposure: 0.0,
+            white_balance: 5500,
+            still_timeout: 8.0,
+            clipping_warning: 0.05,
+            mirror_preview: true,
+            mirror_photos: true,
+            idle_timeout: 60.0,
+            lock_focus: false,
+            capture_mode: CaptureMode::Standard,
+            countdown_from: 3,
+            fast_animations: false,
+            sound_effects: true,
+            offline_mode: false,
+        }
+    }
+}
+
+impl CaptureSettings {
+    fn animation_speed(&self) -> animations::Speed {
+        animations::Speed::new(self.fast_animations)
+    }
+}
 
 enum CapturePhotosState {
     Countdown {
         current: usize,
         countdown_timeline: anim::Timeline<animations::countdown_circle::AnimationState>,
+        /// Whether the camera has been told to get ready for the still.
+        prepared: bool,
     },
     Capture {
         capture_timeline: anim::Timeline<animations::capture_flash::AnimationState>,
+        /// Whether the still has already been requested a second time.
+        retried: bool,
+        /// Held here until the flash is over.
+        photo: Option<Frame>,
     },
     Preview {
         preview_timeline: anim::Timeline<animations::capture_preview::AnimationState>,
         captured_handle: Handle,
     },
+    /// The still failed twice, so the countdown for the same photo starts
+    /// over at `until`.
+    Failed { until: Instant },
+}
+
+impl CapturePhotosState {
+    fn countdown(from: u32, speed: animations::Speed) -> Self {
+        Self::Countdown {
+            current: from.max(1) as usize,
+            countdown_timeline: animations::countdown_circle::animation(speed).begin_animation(),
+            prepared: false,
+        }
+    }
 }
 
 enum MainAppState {
@@ -53,29 +224,105 @@ enum MainAppState {
     CapturePhotos {
         current: usize,
         state: CapturePhotosState,
+        /// Photos that have failed so far this session.
+        failures: u32,
+        /// Whether only `current` is being taken again, after which the
+        /// guests go back to reviewing the photos.
+        retake: bool,
     },
+    /// All the photos are taken and the guests can pick one to retake.
+    ReviewPhotos {
+        /// The photo to retake, or the photo count for carrying on.
+        selected: usize,
+    },
+    /// The guests try the filters on their photos and pick one for the
+    /// strip.
+    ChooseFilter {
+        /// Index into `ColorFilter::ALL`.
+        selected: usize,
+        /// The photos shrunk, which the filters are tried on.
+        small_photos: Vec<RgbaImage>,
+    },
+    /// The guests pick which template their strip is made with. Skipped if
+    /// there's only one.
+    ChooseTemplate {
+        selected: usize,
+        /// The photos in each template, as they finish rendering.
+        thumbnails: Vec<Option<Handle>>,
+    },
+    /// Waiting for the strip to be put together on a blocking thread.
+    Rendering,
     RenderedPreview {
         progress_timeline: anim::Timeline<f32>,
         template_preview_timeline: anim::Timeline<animations::upsell_templates::AnimationState>,
     },
-    EmailEntry,
+    /// Sending the strip to the printer before the email screen.
+    Printing,
+    EmailEntry {
+        /// Addresses the photos couldn't be sent to last time, so the guests
+        /// can enter them again.
+        undelivered: Vec<String>,
+        /// Index into the added emails of the one highlighted for removal.
+        selected: Option<usize>,
+        /// The strip didn't print, so the guests are told to expect only the
+        /// email.
+        print_failed: bool,
+    },
     Emailing {
         progress_timeline: anim::Timeline<f32>,
     },
+    /// Every email went out, so the guests get a moment of thanks before the
+    /// booth starts over.
+    EmailSent {
+        timeline: anim::Timeline<animations::email_sent::AnimationState>,
+    },
+}
+
+/// A finished strip and what's made along with it.
+#[derive(Debug, Clone)]
+pub struct RenderedTake {
+    strip: RgbaImage,
+    /// The strip at print size as a 300 DPI PNG, unless it failed to encode.
+    print: Option<Vec<u8>>,
+    /// The photos as a GIF, unless it failed to encode.
+    gif: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Clone)]
 pub enum MainAppMessage<S: crate::backend::servers::ServerBackend + 'static> {
     Camera(super::camera_feed::CameraMessage),
+    SecondaryCamera(super::camera_feed::CameraMessage),
     Tick,
     KeyReleased(KeyMessage),
+    PrepareCapture,
+    CapturePrepared(Result<(), String>),
     CaptureStill,
-    Uploaded(Result<S::UploadHandle, String>),
-    Emailed(Result<bool, String>),
+    StillCaptured(Result<RgbaImage, String>),
+    /// The rest of the photos of a rapid session, taken in one go.
+    BurstCaptured(Result<Vec<RgbaImage>, String>),
+    /// The photos in the template at the index, for the guests to choose from.
+    TemplateThumbnail(usize, Result<RgbaImage, String>),
+    StripRendered(Result<RenderedTake, String>),
+    /// How far along the upload or email of the session with the number is,
+    /// from 0.0 to 1.0.
+    UploadProgress(u64, f32),
+    /// Results for the session with the number, which the guests may have
+    /// left since. See `session_generation`.
+    Uploaded(u64, Result<S::UploadHandle, String>),
+    /// The session was written to the spool instead of being uploaded.
+    Queued(u64, Result<PathBuf, String>),
+    Emailed(Result<Vec<EmailDelivery>, String>),
+    Printed(Result<(), String>),
+    CheckCameraStatus,
+    CameraStatus(Result<Option<CameraStatus>, String>),
     OtherKeyPress,
 
     EmailInput(String),
     EmailSubmit,
+    /// Delete or Backspace, which the email input swallows while focused.
+    EmailRemove {
+        backspace: bool,
+    },
 }
 
 pub struct MainApp<
@@ -83,15 +330,65 @@ pub struct MainApp<
     S: crate::backend::servers::ServerBackend + 'static,
 > {
     feed: CameraFeed<C::Camera>,
+    /// Optional staff-facing camera shown picture-in-picture between takes.
+    secondary_feed: Option<CameraFeed<C::Camera>>,
+    settings: CaptureSettings,
+    /// Fills in `{event}` on the strip. Blank if setup didn't name the event.
+    event_name: String,
+    /// Templates the guests can choose from, all with room for the same
+    /// number of photos. The one picked in setup comes first.
+    templates: Vec<StripTemplate>,
+    /// Index of the template the current strip is made with.
+    template: usize,
+    /// Filter the current strip is made with. The photos uploaded with it
+    /// are left as they were taken.
+    photo_filter: ColorFilter,
+    /// Photos taken each session.
+    photo_count: usize,
+    /// Each of `templates` decoded, as it's first needed. The first starts in
+    /// the background when the booth does. Shared by every strip after that.
+    render_assets: Vec<Arc<OnceLock<Result<RenderAssets, String>>>>,
+    /// Last status the camera reported, if it reports one at all.
+    camera_status: Option<CameraStatus>,
+    sounds: SoundPlayer,
     state: MainAppState,
-    captured_photos: Vec<RgbaImage>,
+    captured_photos: Vec<Frame>,
+    /// Photos from a rapid burst waiting for their turn to be shown.
+    burst: VecDeque<Frame>,
     previews: Vec<iced::widget::image::Handle>,
-    strip: Option<RgbaImage>,
+    strip: Option<Frame>,
+    /// `strip` as a print-ready PNG.
+    print_png: Option<Vec<u8>>,
     strip_handle: Option<Handle>,
     logo_handle: Handle,
     emails: Vec<String>,
+    /// Whether the address being typed was rejected as malformed.
+    email_invalid: bool,
+    /// Last key press, or when the booth last started waiting on the guests.
+    last_activity: Instant,
     upload_handle: Option<S::UploadHandle>,
+    /// Sessions being uploaded by their number, kept to be queued if the
+    /// upload fails. Can outlive the session if the guests leave first.
+    unsent: HashMap<u64, QueuedSession>,
+    /// Bumped whenever a session is rendered or abandoned, so an upload that
+    /// finishes after the guests left doesn't show up for the next ones.
+    session_generation: u64,
+    /// The current session's directory in the spool, if it was queued.
+    /// Emails entered for it are added there.
+    queued: Option<PathBuf>,
+    /// Where sessions that couldn't be uploaded are queued, if there's
+    /// anywhere to queue them.
+    spool: Option<PathBuf>,
+    /// Whether the camera was last told to lock its focus.
+    focus_locked: bool,
+    /// Printer strips can be sent to, if one is configured.
+    printer: Option<String>,
+    /// Reported progress of the current upload or email, if the server
+    /// backend reports any.
+    upload_progress: Option<f32>,
     qr_code_data: Option<iced::widget::qr_code::Data>,
+    /// Cells across `qr_code_data`, quiet zone included.
+    qr_code_side_length: usize,
     pub new_page: Option<Box<(AppPage<C, S>, Task<PhotoBoothMessage<C, S>>)>>,
 }
 
@@ -100,23 +397,84 @@ impl<
         S: crate::backend::servers::ServerBackend + 'static,
     > MainApp<C, S>
 {
-    pub fn new(feed: CameraFeed<C::Camera>) -> (Self, Task<MainAppMessage<S>>) {
+    pub fn new(
+        feed: CameraFeed<C::Camera>,
+        secondary_feed: Option<CameraFeed<C::Camera>>,
+        settings: CaptureSettings,
+        event_name: String,
+        templates: Vec<StripTemplate>,
+    ) -> (Self, Task<MainAppMessage<S>>) {
+        let secondary_feed = secondary_feed.map(|mut feed| {
+            feed.update_options(CameraFeedOptions {
+                radius: Radius::from(8.0),
+                blur: 4.0,
+                mirror: true,
+                ..Default::default()
+            });
+            feed.set_target_fps(SECONDARY_FEED_FPS);
+            feed
+        });
+        let photo_count = templates[0].layout.photo_count();
+        let render_assets: Vec<_> = templates
+            .iter()
+            .map(|_| Arc::new(OnceLock::new()))
+            .collect();
+        // decoded now so the first strip doesn't wait for it
+        let preload = {
+            let render_assets = render_assets[0].clone();
+            let template = templates[0].clone();
+            Task::future(tokio::task::spawn_blocking(move || {
+                if let Err(err) = load_render_assets(&render_assets, &template) {
+                    // rendering reports it again, where the guests see it
+                    log::error!("Failed to load the strip template: {}", err);
+                }
+            }))
+            .discard()
+        };
         (
             Self {
                 feed,
+                secondary_feed,
+                settings,
+                event_name,
                 state: MainAppState::PaymentRequired { error: None },
                 new_page: None,
-                captured_photos: Vec::with_capacity(PHOTO_COUNT),
-                previews: Vec::with_capacity(PHOTO_COUNT),
+                captured_photos: Vec::with_capacity(photo_count),
+                burst: VecDeque::new(),
+                previews: Vec::with_capacity(photo_count),
+                templates,
+                template: 0,
+                photo_filter: ColorFilter::None,
+                photo_count,
+                render_assets,
+                camera_status: None,
+                sounds: if settings.sound_effects {
+                    SoundPlayer::new()
+                } else {
+                    SoundPlayer::silent()
+                },
                 logo_handle: Handle::from_bytes(include_bytes!("../../assets/banner.png").to_vec()),
                 strip: None,
+                print_png: None,
                 strip_handle: None,
                 qr_code_data: None,
+                qr_code_side_length: QR_CODE_SIDE_LENGTH,
 
                 emails: Vec::new(),
+                email_invalid: false,
+                last_activity: Instant::now(),
                 upload_handle: None,
+                unsent: HashMap::new(),
+                session_generation: 0,
+                queued: None,
+                spool: queue::spool_dir()
+                    .inspect_err(|err| log::warn!("Sessions can't be queued: {}", err))
+                    .ok(),
+                focus_locked: false,
+                printer: configured_printer(),
+                upload_progress: None,
             },
-            Task::none(),
+            Task::batch([Task::done(MainAppMessage::CheckCameraStatus), preload]),
         )
     }
 
@@ -124,6 +482,15 @@ impl<
         &mut self,
         message: MainAppMessage<S>,
         server_backend: S,
+    ) -> Task<MainAppMessage<S>> {
+        let task = self.update_state(message, server_backend);
+        Task::batch([task, self.sync_focus_lock()])
+    }
+
+    fn update_state(
+        &mut self,
+        message: MainAppMessage<S>,
+        server_backend: S,
     ) -> Task<MainAppMessage<S>> {
         self.feed.update_options(
             if matches!(
@@ -134,96 +501,309 @@ impl<
             ) {
                 CameraFeedOptions {
                     blur: 1.0,
-                    aspect_ratio: Some(PHOTO_ASPECT_RATIO),
-                    mirror: true,
+                    // before the countdown the whole frame is shown with a
+                    // guide over what the crop will cut off
+                    aspect_ratio: matches!(self.state, MainAppState::CapturePhotos { .. })
+                        .then_some(PHOTO_ASPECT_RATIO),
+                    max_dimension: Some(PREVIEW_MAX_DIMENSION),
+                    mirror: self.settings.mirror_preview,
+                    filter: self.settings.color_filter,
+                    rotation: self.settings.rotation,
+                    zoom: self.settings.zoom,
+                    center: self.settings.center,
                     ..Default::default()
                 }
             } else {
                 CameraFeedOptions {
-                    blur: 20.0, // 1/20th the resolution
+                    blur_sigma: 24.0,
+                    max_dimension: Some(BACKGROUND_MAX_DIMENSION),
                     aspect_ratio: None,
-                    mirror: true,
+                    mirror: self.settings.mirror_preview,
+                    filter: self.settings.color_filter,
+                    rotation: self.settings.rotation,
+                    zoom: self.settings.zoom,
+                    center: self.settings.center,
+                    // these are covered by an overlay, so a frozen frame looks
+                    // the same and leaves the CPU for rendering and uploading
+                    paused: matches!(
+                        self.state,
+                        MainAppState::Rendering
+                            | MainAppState::RenderedPreview { .. }
+                            | MainAppState::Printing
+                            | MainAppState::EmailEntry { .. }
+                            | MainAppState::Emailing { .. }
+                    ),
                     ..Default::default()
                 }
             },
         );
 
+        if matches!(
+            message,
+            MainAppMessage::KeyReleased(_)
+                | MainAppMessage::OtherKeyPress
+                | MainAppMessage::EmailInput(_)
+                | MainAppMessage::EmailSubmit
+                | MainAppMessage::EmailRemove { .. }
+        ) {
+            self.last_activity = Instant::now();
+        }
+
         match message {
-            MainAppMessage::Camera(msg) => self.feed.update(msg).map(MainAppMessage::Camera),
+            MainAppMessage::Camera(msg) => {
+                self.feed.update(msg);
+                Task::none()
+            }
+            MainAppMessage::SecondaryCamera(msg) => {
+                if let Some(feed) = &mut self.secondary_feed {
+                    feed.update(msg);
+                }
+                Task::none()
+            }
+            MainAppMessage::PrepareCapture => {
+                Task::perform(self.feed.prepare_still(), MainAppMessage::CapturePrepared)
+            }
+            MainAppMessage::CapturePrepared(result) => {
+                if let Err(err) = result {
+                    // the shot can still be taken, it just might be out of focus
+                    log::warn!("Failed to prepare the camera for a still: {}", err);
+                }
+                Task::none()
+            }
             MainAppMessage::CaptureStill => {
                 log::debug!("Capturing still image...");
-                let image = self
-                    .feed
-                    .capture_still_sync(CameraFeedOptions {
-                        aspect_ratio: Some(PHOTO_ASPECT_RATIO),
-                        mirror: true,
-                        ..Default::default()
-                    })
-                    .expect("failed to capture image");
-                log::debug!("Image captured successfully.");
-                self.captured_photos.push(image);
-                match &mut self.state {
-                    MainAppState::CapturePhotos { state, .. } => {
-                        *state = CapturePhotosState::Capture {
-                            capture_timeline: animations::capture_flash::animation()
-                                .begin_animation(),
+                Task::perform(
+                    self.feed.capture_still(
+                        self.still_options(),
+                        Duration::from_secs_f32(self.settings.still_timeout.max(1.0)),
+                    ),
+                    MainAppMessage::StillCaptured,
+                )
+            }
+            MainAppMessage::BurstCaptured(result) => {
+                let MainAppState::CapturePhotos {
+                    state: CapturePhotosState::Capture { photo, .. },
+                    ..
+                } = &mut self.state
+                else {
+                    log::warn!("Discarding a burst that arrived after its capture");
+                    return Task::none();
+                };
+                match result {
+                    Ok(photos) => {
+                        log::debug!("Burst of {} photos captured.", photos.len());
+                        let mut photos = photos.into_iter().map(Frame::from);
+                        if photo.is_none() {
+                            *photo = photos.next();
                         }
+                        self.burst.extend(photos);
+                        Task::none()
+                    }
+                    // carries on a photo at a time from here
+                    Err(err) => {
+                        log::warn!(
+                            "Error capturing burst, taking the photo on its own: {}",
+                            err
+                        );
+                        Task::done(MainAppMessage::StillCaptured(Err(err)))
+                    }
+                }
+            }
+            MainAppMessage::TemplateThumbnail(index, result) => {
+                let MainAppState::ChooseTemplate { thumbnails, .. } = &mut self.state else {
+                    return Task::none();
+                };
+                match result {
+                    Ok(thumbnail) => thumbnails[index] = Some(Frame::from(thumbnail).to_handle()),
+                    // it can still be chosen, it just won't have a preview
+                    Err(err) => log::warn!(
+                        "Error rendering a thumbnail of {}: {}",
+                        self.templates[index].name(),
+                        err
+                    ),
+                }
+                Task::none()
+            }
+            MainAppMessage::StillCaptured(result) => {
+                let MainAppState::CapturePhotos {
+                    current,
+                    state: CapturePhotosState::Capture { retried, photo, .. },
+                    failures,
+                    retake,
+                } = &mut self.state
+                else {
+                    log::warn!("Discarding a still that arrived after its capture");
+                    return Task::none();
+                };
+                match result {
+                    // only one photo per index, whatever order things happen in
+                    Ok(image) if photo.is_none() => {
+                        log::debug!("Image captured successfully.");
+                        // the preview starts on the next tick once the flash is over
+                        *photo = Some(Frame::from(image));
+                        Task::none()
+                    }
+                    Ok(_) => {
+                        log::warn!("Discarding an extra still for photo {}", *current + 1);
+                        Task::none()
+                    }
+                    Err(err) if !*retried => {
+                        log::warn!("Error capturing photo, trying once more: {}", err);
+                        *retried = true;
+                        Task::done(MainAppMessage::CaptureStill)
+                    }
+                    Err(err) if *failures + 1 < MAX_FAILED_PHOTOS => {
+                        log::error!("Error capturing photo, starting it over: {}", err);
+                        *failures += 1;
+                        self.state = MainAppState::CapturePhotos {
+                            current: *current,
+                            state: CapturePhotosState::Failed {
+                                until: Instant::now() + RETRY_MESSAGE_DURATION,
+                            },
+                            failures: *failures,
+                            retake: *retake,
+                        };
+                        Task::none()
+                    }
+                    Err(err) => {
+                        log::error!("Error capturing photo, giving up: {}", err);
+                        self.reset_session();
+                        self.state = MainAppState::PaymentRequired {
+                            error: Some(
+                                "The photo could not be taken. Please try again.".to_string(),
+                            ),
+                        };
+                        Task::none()
                     }
-                    _ => (),
                 }
+            }
+            MainAppMessage::Tick if self.is_idle() => {
+                log::info!("Nobody has pressed anything in a while, starting over");
+                self.reset_session();
+                self.state = MainAppState::PaymentRequired { error: None };
                 Task::none()
             }
             MainAppMessage::Tick => match &mut self.state {
                 MainAppState::CapturePhotosPrepare { ready_timeline } => {
                     if ready_timeline.update().is_completed() {
+                        self.burst.clear();
                         self.state = MainAppState::CapturePhotos {
                             current: 0,
-                            state: CapturePhotosState::Countdown {
-                                current: 3,
-                                countdown_timeline: animations::countdown_circle::animation()
-                                    .begin_animation(),
-                            },
+                            state: CapturePhotosState::countdown(
+                                self.settings.countdown_from,
+                                self.settings.animation_speed(),
+                            ),
+                            failures: 0,
+                            retake: false,
                         }
                     };
                     Task::none()
                 }
-                MainAppState::CapturePhotos { state, current } => match state {
+                MainAppState::CapturePhotos {
+                    state,
+                    current,
+                    retake,
+                    ..
+                } => match state {
                     CapturePhotosState::Countdown {
-                        current,
+                        current: seconds,
                         countdown_timeline,
+                        prepared,
                     } => {
+                        if *seconds == 1 && !*prepared {
+                            // let the camera focus during the last second
+                            *prepared = true;
+                            return Task::done(MainAppMessage::PrepareCapture);
+                        }
                         if countdown_timeline.update().is_completed() {
-                            *current -= 1;
-                            if *current == 0 {
+                            *seconds -= 1;
+                            if *seconds == 0 {
+                                self.sounds.play(Sound::Shutter);
+                                // flash as the photo is requested, not when it
+                                // arrives, so it lines up with the countdown
                                 *state = CapturePhotosState::Capture {
-                                    capture_timeline: animations::capture_flash::animation()
-                                        .to_timeline(),
+                                    capture_timeline: animations::capture_flash::animation(
+                                        self.settings.animation_speed(),
+                                    )
+                                    .begin_animation(),
+                                    retried: false,
+                                    photo: None,
                                 };
+                                let remaining = self.photo_count - *current;
+                                if self.settings.capture_mode == CaptureMode::Rapid
+                                    && !*retake
+                                    && remaining > 1
+                                {
+                                    return Task::perform(
+                                        self.feed.capture_burst(
+                                            remaining,
+                                            RAPID_INTERVAL,
+                                            self.still_options(),
+                                            Duration::from_secs_f32(
+                                                self.settings.still_timeout.max(1.0),
+                                            ) * remaining as u32
+                                                + RAPID_INTERVAL * (remaining as u32 - 1),
+                                        ),
+                                        MainAppMessage::BurstCaptured,
+                                    );
+                                }
                                 return Task::done(MainAppMessage::CaptureStill);
                             } else {
-                                *countdown_timeline =
-                                    animations::countdown_circle::animation().begin_animation();
+                                self.sounds.play(Sound::CountdownBeep);
+                                *countdown_timeline = animations::countdown_circle::animation(
+                                    self.settings.animation_speed(),
+                                )
+                                .begin_animation();
                             }
                         };
                         Task::none()
                     }
-                    CapturePhotosState::Capture { capture_timeline } => {
+                    CapturePhotosState::Capture {
+                        capture_timeline,
+                        photo,
+                        ..
+                    } => {
+                        // wait for the photo if it's taking longer than the flash
                         if capture_timeline.update().is_completed() {
-                            let last_photo = self
-                                .captured_photos
-                                .last()
-                                .expect("capture didn't complete")
-                                .clone();
-                            *state = CapturePhotosState::Preview {
-                                preview_timeline: animations::capture_preview::animation()
-                                    .begin_animation(),
-                                captured_handle: Handle::from_rgba(
-                                    last_photo.width(),
-                                    last_photo.height(),
-                                    last_photo.into_raw(),
-                                ),
+                            if let Some(photo) = photo.take() {
+                                let captured_handle = filtered_handle(
+                                    &photo,
+                                    self.settings.color_filter,
+                                    PREVIEW_MAX_DIMENSION,
+                                );
+                                // a retake replaces the photo it's for
+                                match self.captured_photos.get_mut(*current) {
+                                    Some(old) => *old = photo,
+                                    None => self.captured_photos.push(photo),
+                                }
+                                let preview_timeline =
+                                    if self.settings.capture_mode == CaptureMode::Rapid && !*retake
+                                    {
+                                        animations::capture_preview::rapid_animation(
+                                            self.settings.animation_speed(),
+                                        )
+                                        .begin_animation()
+                                    } else {
+                                        animations::capture_preview::animation(
+                                            self.settings.animation_speed(),
+                                        )
+                                        .begin_animation()
+                                    };
+                                *state = CapturePhotosState::Preview {
+                                    preview_timeline,
+                                    captured_handle,
+                                }
                             }
-                        };
+                        }
+                        Task::none()
+                    }
+                    CapturePhotosState::Failed { until } => {
+                        if Instant::now() >= *until {
+                            *state = CapturePhotosState::countdown(
+                                self.settings.countdown_from,
+                                self.settings.animation_speed(),
+                            );
+                        }
                         Task::none()
                     }
                     CapturePhotosState::Preview {
@@ -231,50 +811,27 @@ impl<
                     } => {
                         if preview_timeline.update().is_completed() {
                             *current += 1;
-                            if *current < PHOTO_COUNT {
-                                *state = CapturePhotosState::Countdown {
-                                    current: 3,
-                                    countdown_timeline: animations::countdown_circle::animation()
-                                        .begin_animation(),
+                            if let Some(photo) = self.burst.pop_front().filter(|_| !*retake) {
+                                // already taken, so only the flash is shown
+                                self.sounds.play(Sound::Shutter);
+                                *state = CapturePhotosState::Capture {
+                                    capture_timeline: animations::capture_flash::animation(
+                                        self.settings.animation_speed(),
+                                    )
+                                    .begin_animation(),
+                                    retried: true,
+                                    photo: Some(photo),
                                 };
-                                Task::none()
+                            } else if *current < self.photo_count && !*retake {
+                                *state = CapturePhotosState::countdown(
+                                    self.settings.countdown_from,
+                                    self.settings.animation_speed(),
+                                );
                             } else {
-                                let old = self.captured_photos.drain(..).collect::<Vec<_>>();
-                                self.previews.clear();
-                                for photo in &old {
-                                    self.previews.push(iced::widget::image::Handle::from_rgba(
-                                        photo.width(),
-                                        photo.height(),
-                                        photo.as_raw().clone(),
-                                    ));
-                                }
-                                self.strip = Some(render_take(old.clone()));
-                                self.strip_handle = Some(Handle::from_rgba(
-                                    self.strip.as_ref().unwrap().width(),
-                                    self.strip.as_ref().unwrap().height(),
-                                    self.strip.as_ref().unwrap().as_raw().clone(),
-                                ));
-                                self.upload_handle = None;
-                                self.qr_code_data = None;
-                                self.state = MainAppState::RenderedPreview {
-                                    progress_timeline: anim::Options::new(0.0, 1.0)
-                                        .duration(Duration::from_millis(
-                                            animations::upsell_templates::ANIMATION_LENGTH,
-                                        ))
-                                        .easing(anim::easing::linear())
-                                        .begin_animation(),
-                                    template_preview_timeline:
-                                        animations::upsell_templates::animation().begin_animation(),
-                                };
-                                let future = server_backend
-                                    .upload_photo(self.strip.as_ref().unwrap().clone(), old);
-                                Task::perform(future, |result| {
-                                    MainAppMessage::Uploaded(result.map_err(|x| x.to_string()))
-                                })
+                                self.review_photos();
                             }
-                        } else {
-                            Task::none()
                         }
+                        Task::none()
                     }
                 },
                 MainAppState::RenderedPreview {
@@ -285,44 +842,186 @@ impl<
                     if progress_timeline.update().is_completed()
                         && template_preview_timeline.update().is_completed()
                     {
-                        self.state = MainAppState::EmailEntry;
-                        self.emails = vec!["".to_string(); 1];
-                        iced::widget::text_input::focus("email_input")
+                        self.enter_emails(false)
                     } else {
                         Task::none()
                     }
                 }
+                MainAppState::EmailSent { timeline } => {
+                    if timeline.update().is_completed() {
+                        self.state = MainAppState::PaymentRequired { error: None };
+                    }
+                    Task::none()
+                }
                 _ => Task::none(),
             },
-            MainAppMessage::Uploaded(result) => {
+            MainAppMessage::StripRendered(result) => {
+                let photos = self
+                    .captured_photos
+                    .drain(..)
+                    .map(|photo| photo.to_image())
+                    .collect();
+                let RenderedTake { strip, print, gif } = match result {
+                    Ok(rendered) => rendered,
+                    Err(err) => {
+                        log::error!("Error rendering strip: {}", err);
+                        self.reset_session();
+                        self.state = MainAppState::PaymentRequired {
+                            error: Some(
+                                "Your photos could not be put together. Please try again."
+                                    .to_string(),
+                            ),
+                        };
+                        return Task::none();
+                    }
+                };
+                let strip = Frame::from(strip);
+                self.strip_handle = Some(strip.to_handle());
+                self.upload_handle = None;
+                self.qr_code_data = None;
+                self.state = MainAppState::RenderedPreview {
+                    progress_timeline: anim::Options::new(0.0, 1.0)
+                        .duration(
+                            self.settings
+                                .animation_speed()
+                                .length(animations::upsell_templates::ANIMATION_LENGTH),
+                        )
+                        .easing(anim::easing::linear())
+                        .begin_animation(),
+                    template_preview_timeline: animations::upsell_templates::animation(
+                        self.settings.animation_speed(),
+                    )
+                    .begin_animation(),
+                };
+                self.close_queued();
+                self.session_generation += 1;
+                let generation = self.session_generation;
+                let session = QueuedSession {
+                    strip: strip.to_image(),
+                    print: print.clone(),
+                    photos,
+                    gif,
+                    emails: Vec::new(),
+                };
+                self.strip = Some(strip);
+                self.print_png = print;
+                if self.settings.offline_mode {
+                    return self.enqueue(generation, session);
+                }
+                let (progress, progress_updates) = Progress::channel();
+                self.upload_progress = None;
+                let future = server_backend.upload_photo(
+                    session.strip.clone(),
+                    session.print.clone(),
+                    session.photos.clone(),
+                    session.gif.clone(),
+                    progress,
+                );
+                self.unsent.insert(generation, session);
+                Task::batch([
+                    Task::perform(future, move |result| {
+                        MainAppMessage::Uploaded(generation, result.map_err(|x| x.to_string()))
+                    }),
+                    Task::run(progress_updates, move |progress| {
+                        MainAppMessage::UploadProgress(generation, progress)
+                    }),
+                ])
+            }
+            MainAppMessage::UploadProgress(generation, progress) => {
+                if generation == self.session_generation {
+                    self.upload_progress = Some(progress);
+                }
+                Task::none()
+            }
+            MainAppMessage::Uploaded(generation, result) => {
                 log::debug!("Upload result received: {:?}", result);
+                let unsent = self.unsent.remove(&generation);
+                if generation != self.session_generation {
+                    // the guests have left, but their photos are still kept
+                    if let (Err(err), Some(session)) = (result, unsent) {
+                        log::error!("Error uploading an earlier session's photos: {}", err);
+                        return self.enqueue(generation, session);
+                    }
+                    return Task::none();
+                }
                 match result {
                     Ok(res) => {
                         self.upload_handle = Some(res);
-                        self.qr_code_data = Some(
-                            iced::widget::qr_code::Data::with_version(
-                                server_backend
-                                    .get_link(self.upload_handle.as_ref().unwrap().clone()),
-                                QR_CODE_VERSION,
-                                iced::widget::qr_code::ErrorCorrection::Medium,
-                            )
-                            .expect("could not create qr code"),
-                        );
+                        let link =
+                            server_backend.get_link(self.upload_handle.as_ref().unwrap().clone());
+                        if !link.starts_with("http://") && !link.starts_with("https://") {
+                            log::debug!("Not showing a QR code for {}", link);
+                            return Task::none();
+                        }
+                        match link_qr_code(&link) {
+                            Some((data, side_length)) => {
+                                self.qr_code_data = Some(data);
+                                self.qr_code_side_length = side_length;
+                            }
+                            // the photos can still be emailed
+                            None => log::error!("{} is too long for a QR code", link),
+                        }
                         Task::none()
                     }
                     Err(err) => {
+                        log::error!("Error uploading photos: {}", err);
+                        if let Some(session) = unsent {
+                            log::info!("Queuing the photos to upload later");
+                            return self.enqueue(generation, session);
+                        }
+                        self.reset_session();
                         self.state = MainAppState::PaymentRequired {
                             error: Some(
                                 "The photos could not be uploaded. Please try again.".to_string(),
                             ),
                         };
-                        log::error!("Error uploading photos: {}", err);
                         Task::none()
                     }
                 }
             }
+            MainAppMessage::Queued(generation, result) if generation != self.session_generation => {
+                match result {
+                    // nobody is left to add emails to it
+                    Ok(dir) => {
+                        if let Err(err) = queue::close(&dir) {
+                            log::error!(
+                                "Failed to close queued session {}: {}",
+                                dir.display(),
+                                err
+                            );
+                        }
+                    }
+                    Err(err) => log::error!("Error queuing an earlier session's photos: {}", err),
+                }
+                Task::none()
+            }
+            MainAppMessage::Queued(_, result) => match result {
+                Ok(dir) => {
+                    self.queued = Some(dir);
+                    // the guests left before it was written
+                    if !matches!(
+                        self.state,
+                        MainAppState::RenderedPreview { .. } | MainAppState::EmailEntry { .. }
+                    ) {
+                        self.close_queued();
+                    }
+                    Task::none()
+                }
+                Err(err) => {
+                    log::error!("Error queuing photos: {}", err);
+                    self.reset_session();
+                    self.state = MainAppState::PaymentRequired {
+                        error: Some(
+                            "The photos could not be uploaded. Please try again.".to_string(),
+                        ),
+                    };
+                    Task::none()
+                }
+            },
             MainAppMessage::KeyReleased(key) => {
                 log::debug!("Key released: {:?}", key);
+                // checked up front, since the match borrows the state
+                let can_print = self.can_print();
                 match &mut self.state {
                     MainAppState::PaymentRequired { .. } => match key {
                         KeyMessage::Up => Task::none(),
@@ -335,10 +1034,110 @@ impl<
                     },
                     MainAppState::Preview => {
                         self.state = MainAppState::CapturePhotosPrepare {
-                            ready_timeline: animations::ready::animation().begin_animation(),
+                            ready_timeline: animations::ready::animation(
+                                self.settings.animation_speed(),
+                            )
+                            .begin_animation(),
                         };
                         Task::none()
                     }
+                    MainAppState::ReviewPhotos { selected } => {
+                        // the photos, then carrying on
+                        let choices = self.photo_count + 1;
+                        match key {
+                            KeyMessage::Up => {
+                                *selected = (*selected + choices - 1) % choices;
+                                Task::none()
+                            }
+                            KeyMessage::Down => {
+                                *selected = (*selected + 1) % choices;
+                                Task::none()
+                            }
+                            KeyMessage::Space if *selected + 1 == choices => self.choose_filter(),
+                            KeyMessage::Space => {
+                                self.state = MainAppState::CapturePhotos {
+                                    current: *selected,
+                                    state: CapturePhotosState::countdown(
+                                        self.settings.countdown_from,
+                                        self.settings.animation_speed(),
+                                    ),
+                                    failures: 0,
+                                    retake: true,
+                                };
+                                Task::none()
+                            }
+                            KeyMessage::Escape => {
+                                self.captured_photos.clear();
+                                self.state = MainAppState::CapturePhotos {
+                                    current: 0,
+                                    state: CapturePhotosState::countdown(
+                                        self.settings.countdown_from,
+                                        self.settings.animation_speed(),
+                                    ),
+                                    failures: 0,
+                                    retake: false,
+                                };
+                                Task::none()
+                            }
+                        }
+                    }
+                    MainAppState::ChooseFilter {
+                        selected,
+                        small_photos,
+                    } => {
+                        let choices = ColorFilter::ALL.len();
+                        match key {
+                            KeyMessage::Up | KeyMessage::Down => {
+                                *selected = if matches!(key, KeyMessage::Up) {
+                                    (*selected + choices - 1) % choices
+                                } else {
+                                    (*selected + 1) % choices
+                                };
+                                let filter = ColorFilter::ALL[*selected];
+                                self.previews = small_photos
+                                    .iter()
+                                    .map(|photo| {
+                                        Frame::from(apply_filter(photo, filter)).to_handle()
+                                    })
+                                    .collect();
+                                Task::none()
+                            }
+                            KeyMessage::Space => {
+                                self.photo_filter = ColorFilter::ALL[*selected];
+                                self.choose_template()
+                            }
+                            KeyMessage::Escape => {
+                                self.review_photos();
+                                Task::none()
+                            }
+                        }
+                    }
+                    MainAppState::ChooseTemplate { selected, .. } => {
+                        let choices = self.templates.len();
+                        match key {
+                            KeyMessage::Up => {
+                                *selected = (*selected + choices - 1) % choices;
+                                Task::none()
+                            }
+                            KeyMessage::Down => {
+                                *selected = (*selected + 1) % choices;
+                                Task::none()
+                            }
+                            KeyMessage::Space => {
+                                self.template = *selected;
+                                self.render_strip()
+                            }
+                            KeyMessage::Escape => {
+                                self.review_photos();
+                                Task::none()
+                            }
+                        }
+                    }
+                    MainAppState::RenderedPreview { .. }
+                        if matches!(key, KeyMessage::Up) && can_print =>
+                    {
+                        self.start_printing()
+                    }
                     MainAppState::RenderedPreview {
                         progress_timeline, ..
                     } => {
@@ -350,12 +1149,54 @@ impl<
                             .begin_animation();
                         Task::none()
                     }
-                    MainAppState::EmailEntry => iced::widget::text_input::focus("email_input"),
+                    MainAppState::EmailEntry { selected, .. } => {
+                        // the input is at the top, so moving past either end
+                        // of the added emails goes back to it
+                        let added = self.emails.len().saturating_sub(1);
+                        match key {
+                            KeyMessage::Down if added > 0 => {
+                                *selected = match *selected {
+                                    None => Some(1),
+                                    Some(index) if index < added => Some(index + 1),
+                                    Some(_) => None,
+                                };
+                            }
+                            KeyMessage::Up if added > 0 => {
+                                *selected = match *selected {
+                                    None => Some(added),
+                                    Some(1) => None,
+                                    Some(index) => Some(index - 1),
+                                };
+                            }
+                            _ => {}
+                        }
+                        iced::widget::text_input::focus("email_input")
+                    }
                     _ => Task::none(),
                 }
             }
+            MainAppMessage::CheckCameraStatus => Task::perform(
+                self.feed
+                    .with_camera(|camera| camera.status().map_err(|err| format!("{:?}", err))),
+                |result| MainAppMessage::CameraStatus(result.and_then(|status| status)),
+            ),
+            MainAppMessage::CameraStatus(result) => {
+                match result {
+                    Ok(status) => {
+                        log::debug!("Camera status: {:?}", status);
+                        self.camera_status = status;
+                    }
+                    // a camera that can't report its status can still take photos
+                    Err(err) => log::warn!("Failed to read the camera status: {}", err),
+                }
+                Task::none()
+            }
             MainAppMessage::OtherKeyPress => iced::widget::text_input::focus("email_input"),
             MainAppMessage::EmailInput(email) => {
+                self.email_invalid = false;
+                if let MainAppState::EmailEntry { selected, .. } = &mut self.state {
+                    *selected = None;
+                }
                 if self.emails.is_empty() {
                     self.emails.push(email);
                 } else {
@@ -365,22 +1206,64 @@ impl<
             }
             MainAppMessage::EmailSubmit => {
                 log::debug!("Email submit triggered. Current emails: {:?}", self.emails);
-                if self.upload_handle.is_none() {
+                // a second Enter can arrive before the emails are on their way
+                if !matches!(self.state, MainAppState::EmailEntry { .. }) {
+                    return Task::none();
+                }
+                if self.upload_handle.is_none() && self.queued.is_none() {
                     log::warn!("Didn't finish uploading.");
                     return Task::none();
                 }
-                if self.emails[0].len() > 0 {
+                let email = self.emails[0].trim().to_string();
+                if !email.is_empty() {
+                    if !is_valid_email(&email) {
+                        self.email_invalid = true;
+                        return iced::widget::text_input::focus("email_input");
+                    }
+                    self.emails[0] = email;
                     self.emails.splice(0..0, ["".to_string()]);
+                    if let MainAppState::EmailEntry { selected, .. } = &mut self.state {
+                        *selected = None;
+                    }
                     Task::none()
                 } else {
                     self.emails.splice(0..1, []);
                     if self.emails.is_empty() {
+                        self.reset_session();
                         self.state = MainAppState::PaymentRequired { error: None };
                         Task::none()
+                    } else if let Some(dir) = self.queued.clone() {
+                        // emailed from the spool once the booth is back online
+                        let added = queue::add_emails(&dir, &self.emails);
+                        self.reset_session();
+                        if let Err(err) = added {
+                            log::error!("Error queuing emails: {}", err);
+                            self.state = MainAppState::PaymentRequired {
+                                error: Some(
+                                    "The photos could not be emailed. Please try again."
+                                        .to_string(),
+                                ),
+                            };
+                            return Task::none();
+                        }
+                        self.state = MainAppState::EmailSent {
+                            timeline: animations::email_sent::animation(
+                                self.settings.animation_speed(),
+                            )
+                            .begin_animation(),
+                        };
+                        Task::none()
                     } else {
-                        if let Some(upload_handle) = self.upload_handle.take() {
-                            let future =
-                                server_backend.send_email(upload_handle, self.emails.clone());
+                        // kept until every address got the photos, in case
+                        // some have to be entered again
+                        if let Some(upload_handle) = self.upload_handle.clone() {
+                            let (progress, progress_updates) = Progress::channel();
+                            self.upload_progress = None;
+                            let future = server_backend.send_email(
+                                upload_handle,
+                                self.emails.clone(),
+                                progress,
+                            );
                             self.state = MainAppState::Emailing {
                                 progress_timeline: anim::Options::new(0.0, 1.0)
                                     .duration(Duration::from_millis(15000))
@@ -391,14 +1274,19 @@ impl<
                                     .begin_animation(),
                             };
                             self.emails.clear();
-                            self.strip_handle = None;
-                            self.strip = None;
                             log::trace!("Sending email with photos...");
-                            Task::perform(future, |result| {
-                                MainAppMessage::Emailed(result.map_err(|x| x.to_string()))
-                            })
+                            let generation = self.session_generation;
+                            Task::batch([
+                                Task::perform(future, |result| {
+                                    MainAppMessage::Emailed(result.map_err(|x| x.to_string()))
+                                }),
+                                Task::run(progress_updates, move |progress| {
+                                    MainAppMessage::UploadProgress(generation, progress)
+                                }),
+                            ])
                         } else {
                             log::error!("No upload handle available for emailing.");
+                            self.reset_session();
                             self.state = MainAppState::PaymentRequired {
                                 error: Some(
                                     "The photos could not be emailed. Please try again."
@@ -413,31 +1301,38 @@ impl<
             MainAppMessage::Emailed(result) => {
                 log::debug!("Email result received: {:?}", result);
                 match self.state {
-                    MainAppState::Emailing {
-                        ref mut progress_timeline,
-                    } => match result {
-                        Ok(all_success) => {
-                            if all_success {
-                                *progress_timeline =
-                                    anim::Options::new(progress_timeline.value(), 1.0)
-                                        .duration(Duration::from_millis(1000))
-                                        .easing(
-                                            anim::easing::cubic_ease()
-                                                .mode(anim::easing::EasingMode::InOut),
-                                        )
-                                        .begin_animation();
-                                self.state = MainAppState::PaymentRequired { error: None };
+                    MainAppState::Emailing { .. } => match result {
+                        Ok(deliveries) => {
+                            let undelivered: Vec<String> = deliveries
+                                .into_iter()
+                                .filter(|delivery| !delivery.delivered)
+                                .map(|delivery| delivery.address)
+                                .collect();
+                            if undelivered.is_empty() {
+                                self.reset_session();
+                                self.state = MainAppState::EmailSent {
+                                    timeline: animations::email_sent::animation(
+                                        self.settings.animation_speed(),
+                                    )
+                                    .begin_animation(),
+                                };
+                                Task::none()
                             } else {
-                                self.state = MainAppState::PaymentRequired {
-                                    error: Some(
-                                        "Some email addresses provided could not be reached. Please contact photobooth@caj.ac.jp for assistance."
-                                            .to_string(),
-                                    ),
+                                // only the addresses that failed are sent again
+                                log::warn!("Couldn't email photos to {:?}", undelivered);
+                                self.emails = vec!["".to_string(); 1];
+                                self.email_invalid = false;
+                                self.last_activity = Instant::now();
+                                self.state = MainAppState::EmailEntry {
+                                    undelivered,
+                                    selected: None,
+                                    print_failed: false,
                                 };
+                                iced::widget::text_input::focus("email_input")
                             }
-                            Task::none()
                         }
                         Err(err) => {
+                            self.reset_session();
                             self.state = MainAppState::PaymentRequired {
                                 error: Some(
                                     "The photos could not be emailed. Please try again."
@@ -451,28 +1346,432 @@ impl<
                     _ => Task::none(),
                 }
             }
+            MainAppMessage::EmailRemove { backspace } => {
+                let MainAppState::EmailEntry { selected, .. } = &mut self.state else {
+                    return Task::none();
+                };
+                let Some(index) = *selected else {
+                    return Task::none();
+                };
+                // backspace is still editing the input until it's empty
+                if backspace && !self.emails[0].is_empty() {
+                    return Task::none();
+                }
+                if index < self.emails.len() {
+                    let removed = self.emails.remove(index);
+                    log::debug!("Removed email {}", removed);
+                }
+                let added = self.emails.len().saturating_sub(1);
+                *selected = (added > 0).then(|| index.min(added));
+                iced::widget::text_input::focus("email_input")
+            }
+            MainAppMessage::Printed(result) => {
+                log::debug!("Print result received: {:?}", result);
+                if !matches!(self.state, MainAppState::Printing) {
+                    return Task::none();
+                }
+                // the photos are uploaded either way, so the guests still get
+                // their email
+                if let Err(err) = &result {
+                    log::error!("Error printing photos: {}", err);
+                }
+                self.enter_emails(result.is_err())
+            }
         }
     }
 
-    pub fn view<'a>(&'a self, _server_backend: &'a S) -> Element<'a, MainAppMessage<S>> {
-        iced::widget::stack([
-            self.feed
-                .view()
-                .content_fit(
-                    if matches!(
-                        self.state,
-                        MainAppState::CapturePhotosPrepare { .. }
-                            | MainAppState::CapturePhotos { .. }
-                            | MainAppState::Preview
-                    ) {
-                        ContentFit::Contain
-                    } else {
-                        ContentFit::Cover
-                    },
+    pub fn subscription(&self) -> iced::Subscription<MainAppMessage<S>> {
+        iced::Subscription::batch([
+            self.feed.subscription().map(MainAppMessage::Camera),
+            match &self.secondary_feed {
+                Some(feed) => feed.subscription().map(MainAppMessage::SecondaryCamera),
+                None => iced::Subscription::none(),
+            },
+            iced::time::every(CAMERA_STATUS_INTERVAL).map(|_| MainAppMessage::CheckCameraStatus),
+            if matches!(self.state, MainAppState::EmailEntry { .. }) {
+                // the app-wide key subscription only sees keys no widget
+                // used, and the focused input uses these
+                iced::event::listen_with(|event, _status, _window| match event {
+                    iced::Event::Keyboard(iced::keyboard::Event::KeyPressed {
+                        key: iced::keyboard::Key::Named(iced::keyboard::key::Named::Delete),
+                        ..
+                    }) => Some(MainAppMessage::EmailRemove { backspace: false }),
+                    iced::Event::Keyboard(iced::keyboard::Event::KeyPressed {
+                        key: iced::keyboard::Key::Named(iced::keyboard::key::Named::Backspace),
+                        ..
+                    }) => Some(MainAppMessage::EmailRemove { backspace: true }),
+                    _ => None,
+                })
+            } else {
+                iced::Subscription::none()
+            },
+        ])
+    }
+
+    /// How stills are processed: cropped to the strip's shape at full size,
+    /// since the strip does its own resizing. The color filter is left off so
+    /// the photos are uploaded as they were taken, and goes on the strip.
+    fn still_options(&self) -> CameraFeedOptions {
+        CameraFeedOptions {
+            aspect_ratio: Some(PHOTO_ASPECT_RATIO),
+            mirror: self.settings.mirror_photos,
+            filter: ColorFilter::None,
+            rotation: self.settings.rotation,
+            zoom: self.settings.zoom,
+            center: self.settings.center,
+            max_dimension: None,
+            ..Default::default()
+        }
+    }
+
+    /// Locks the focus once the guests start a session and hands it back to
+    /// autofocus when the booth is back on the start screen. Done after each
+    /// update rather than at every place the state changes.
+    fn sync_focus_lock(&mut self) -> Task<MainAppMessage<S>> {
+        let lock = self.settings.lock_focus
+            && !matches!(
+                self.state,
+                MainAppState::PaymentRequired { .. } | MainAppState::Preview
+            );
+        if lock == self.focus_locked {
+            return Task::none();
+        }
+        self.focus_locked = lock;
+        Task::future(self.feed.with_camera(move |camera| {
+            // not worth bothering the guests over, the photos still work
+            if let Err(err) = camera.set_focus_locked(lock) {
+                log::warn!("Failed to set the focus lock: {:?}", err);
+            }
+        }))
+        .discard()
+    }
+
+    /// Shows every photo so the guests can pick one to take again.
+    fn review_photos(&mut self) {
+        self.previews = self
+            .captured_photos
+            .iter()
+            .map(|photo| filtered_handle(photo, self.settings.color_filter, FILTER_PREVIEW_WIDTH))
+            .collect();
+        self.last_activity = Instant::now();
+        self.state = MainAppState::ReviewPhotos {
+            selected: self.photo_count,
+        };
+    }
+
+    /// Lets the guests try the filters on shrunk copies of their photos,
+    /// starting from the one the live feed showed them.
+    fn choose_filter(&mut self) -> Task<MainAppMessage<S>> {
+        self.last_activity = Instant::now();
+        let small_photos: Vec<_> = self
+            .captured_photos
+            .iter()
+            .map(|photo| shrink(&photo.to_image(), FILTER_PREVIEW_WIDTH))
+            .collect();
+        let selected = ColorFilter::ALL
+            .iter()
+            .position(|filter| *filter == self.settings.color_filter)
+            .unwrap_or(0);
+        self.previews = small_photos
+            .iter()
+            .map(|photo| Frame::from(apply_filter(photo, ColorFilter::ALL[selected])).to_handle())
+            .collect();
+        self.state = MainAppState::ChooseFilter {
+            selected,
+            small_photos,
+        };
+        Task::none()
+    }
+
+    /// Lets the guests pick a template if there's more than one, rendering
+    /// the photos into each on blocking threads to show them.
+    fn choose_template(&mut self) -> Task<MainAppMessage<S>> {
+        if self.templates.len() == 1 {
+            return self.render_strip();
+        }
+        self.last_activity = Instant::now();
+        self.state = MainAppState::ChooseTemplate {
+            selected: 0,
+            thumbnails: vec![None; self.templates.len()],
+        };
+        // shrunk once here instead of for every template
+        let photos: Arc<Vec<RgbaImage>> = Arc::new(
+            self.captured_photos
+                .iter()
+                .map(|photo| {
+                    let mut photo = shrink(&photo.to_image(), TEMPLATE_THUMBNAIL_WIDTH);
+                    apply_filter_in_place(&mut photo, self.photo_filter);
+                    photo
+                })
+                .collect(),
+        );
+        Task::batch(
+            self.templates
+                .iter()
+                .zip(&self.render_assets)
+                .enumerate()
+                .map(|(index, (template, render_assets))| {
+                    let photos = photos.clone();
+                    let template = template.clone();
+                    let render_assets = render_assets.clone();
+                    Task::perform(
+                        tokio::task::spawn_blocking(move || {
+                            // decoding now also saves the wait once it's chosen
+                            let assets = load_render_assets(&render_assets, &template)?;
+                            render_thumbnail(&photos, assets, TEMPLATE_THUMBNAIL_WIDTH)
+                                .map_err(|err| err.to_string())
+                        }),
+                        move |result| {
+                            MainAppMessage::TemplateThumbnail(
+                                index,
+                                result.unwrap_or_else(|err| Err(err.to_string())),
+                            )
+                        },
+                    )
+                }),
+        )
+    }
+
+    /// Puts the strip and the GIF together on a blocking thread.
+    fn render_strip(&mut self) -> Task<MainAppMessage<S>> {
+        let photo_filter = self.photo_filter;
+        let photos: Vec<_> = self.captured_photos.iter().map(Frame::to_image).collect();
+        let caption = Caption {
+            event: self.event_name.clone(),
+            date: chrono::Local::now().format("%B %-d, %Y").to_string(),
+        };
+        let template = self.templates[self.template].clone();
+        let render_assets = self.render_assets[self.template].clone();
+        self.state = MainAppState::Rendering;
+        // resizing the photos takes long enough to stall the UI
+        Task::perform(
+            tokio::task::spawn_blocking(move || {
+                let started = Instant::now();
+                // only the strip and gif get the filter, the photos are
+                // uploaded as they were taken
+                let mut photos = photos;
+                for photo in &mut photos {
+                    apply_filter_in_place(photo, photo_filter);
+                }
+                // waits for the preload if it's still going
+                let assets = load_render_assets(&render_assets, &template)?;
+                // the strip is enough without the gif
+                let gif = render_gif(&photos, GIF_FRAME_DELAY)
+                    .inspect_err(|err| log::warn!("Error rendering gif: {}", err))
+                    .ok();
+                let rendered = render_take(photos, assets, Some(&caption))
+                    .map(|rendered| RenderedTake {
+                        // printing is extra, so the strip goes on without it
+                        print: encode_print_png(&rendered.print)
+                            .inspect_err(|err| log::warn!("Error encoding print: {}", err))
+                            .ok(),
+                        strip: rendered.screen,
+                        gif,
+                    })
+                    .map_err(|err| err.to_string());
+                log::info!("Rendered the strip and gif in {:?}", started.elapsed());
+                rendered
+            }),
+            |result| {
+                MainAppMessage::StripRendered(result.unwrap_or_else(|err| Err(err.to_string())))
+            },
+        )
+    }
+
+    /// The current strip as a 2x6" PNG at 300 DPI, ready to print without
+    /// resampling.
+    pub fn print_strip(&self) -> Option<&[u8]> {
+        self.print_png.as_deref()
+    }
+
+    fn can_print(&self) -> bool {
+        self.printer.is_some() && self.print_strip().is_some()
+    }
+
+    /// Sends the print version of the strip to the printer on a blocking
+    /// thread.
+    fn start_printing(&mut self) -> Task<MainAppMessage<S>> {
+        let (Some(printer), Some(print)) = (self.printer.clone(), self.print_strip()) else {
+            return Task::none();
+        };
+        let print = print.to_vec();
+        self.state = MainAppState::Printing;
+        Task::perform(
+            tokio::task::spawn_blocking(move || {
+                print_strip(&print, &printer).map_err(|err| err.to_string())
+            }),
+            |result| MainAppMessage::Printed(result.unwrap_or_else(|err| Err(err.to_string()))),
+        )
+    }
+
+    /// Moves on to the email screen with one empty address to type into.
+    fn enter_emails(&mut self, print_failed: bool) -> Task<MainAppMessage<S>> {
+        self.state = MainAppState::EmailEntry {
+            undelivered: Vec::new(),
+            selected: None,
+            print_failed,
+        };
+        self.emails = vec!["".to_string(); 1];
+        self.email_invalid = false;
+        self.last_activity = Instant::now();
+        iced::widget::text_input::focus("email_input")
+    }
+
+    /// Whether the guests have left the booth waiting on them for longer than
+    /// the idle timeout. Screens that move on by themselves, and the email
+    /// screen while the photos are still uploading, never count as idle.
+    fn is_idle(&self) -> bool {
+        let waiting_on_guests = match self.state {
+            MainAppState::Preview
+            | MainAppState::ReviewPhotos { .. }
+            | MainAppState::ChooseFilter { .. }
+            | MainAppState::ChooseTemplate { .. } => true,
+            MainAppState::EmailEntry { .. } => {
+                self.upload_handle.is_some() || self.queued.is_some()
+            }
+            _ => false,
+        };
+        waiting_on_guests
+            && self.last_activity.elapsed().as_secs_f32() >= self.settings.idle_timeout
+    }
+
+    /// Writes `session` to the spool in the background, to be uploaded once
+    /// the booth is back online.
+    fn enqueue(&mut self, generation: u64, session: QueuedSession) -> Task<MainAppMessage<S>> {
+        let spool = self.spool.clone();
+        Task::perform(
+            tokio::task::spawn_blocking(move || {
+                spool
+                    .ok_or(queue::QueueError::NoSpoolDir)
+                    .and_then(|spool| queue::enqueue_in(&spool, &session))
+                    .map_err(|err| err.to_string())
+            }),
+            move |result| {
+                MainAppMessage::Queued(
+                    generation,
+                    result.unwrap_or_else(|err| Err(err.to_string())),
                 )
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .into(),
+            },
+        )
+    }
+
+    /// Forgets the current session, once the guests are done with it or have
+    /// walked away. Its upload carries on, and is still queued if it fails,
+    /// but nothing it reports shows up for the next guests.
+    fn reset_session(&mut self) {
+        self.session_generation += 1;
+        self.close_queued();
+        self.captured_photos.clear();
+        self.burst.clear();
+        self.emails.clear();
+        self.email_invalid = false;
+        self.upload_handle = None;
+        self.upload_progress = None;
+        self.qr_code_data = None;
+        self.strip_handle = None;
+        self.strip = None;
+        self.print_png = None;
+    }
+
+    /// Lets the queued session be uploaded, now that no more emails will be
+    /// added to it.
+    fn close_queued(&mut self) {
+        if let Some(dir) = self.queued.take() {
+            if let Err(err) = queue::close(&dir) {
+                log::error!("Failed to close queued session {}: {}", dir.display(), err);
+            }
+        }
+    }
+
+    /// The chip shown while photos are uploading, with a bar if the backend
+    /// reports how far along it is. Nothing once they're queued instead.
+    fn upload_status(&self) -> Element<'_, MainAppMessage<S>> {
+        if self.queued.is_some() {
+            return "".into();
+        }
+        status_overlay::status_overlay(
+            row([
+                loading_spinners::Circular::new()
+                    .size(30.0)
+                    .bar_height(3.0)
+                    .easing(&loading_spinners::easing::STANDARD_DECELERATE)
+                    .into(),
+                text("Uploading photos in the background...").into(),
+            ])
+            .push_maybe(
+                self.upload_progress
+                    .map(|progress| progress_bar(0.0..=1.0, progress).width(120).height(4.0)),
+            )
+            .align_y(Alignment::Center)
+            .spacing(8),
+        )
+        .into()
+    }
+
+    /// Whether the picture is badly exposed, while guests are getting ready
+    /// and can still move or ask staff to fix the lighting.
+    fn clipping(&self) -> Option<Clipping> {
+        if !matches!(
+            self.state,
+            MainAppState::Preview | MainAppState::CapturePhotosPrepare { .. }
+        ) {
+            return None;
+        }
+        self.feed
+            .last_histogram()?
+            .clipping(self.settings.clipping_warning)
+    }
+
+    /// The camera's battery percentage, if it's low enough to warn about.
+    fn low_battery_percent(&self) -> Option<u8> {
+        self.camera_status?
+            .battery_percent
+            .filter(|percent| *percent < LOW_BATTERY_PERCENT)
+    }
+
+    /// The secondary camera in the bottom right corner, while nobody is
+    /// posing for the main one.
+    fn secondary_feed_view(&self) -> Option<Element<'_, MainAppMessage<S>>> {
+        let feed = self.secondary_feed.as_ref()?;
+        if !matches!(
+            self.state,
+            MainAppState::PaymentRequired { .. } | MainAppState::Preview
+        ) {
+            return None;
+        }
+        Some(
+            container(
+                feed.view()
+                    .width(SECONDARY_FEED_WIDTH)
+                    .content_fit(ContentFit::Contain),
+            )
+            .padding(16)
+            .align_right(Length::Fill)
+            .align_bottom(Length::Fill)
+            .into(),
+        )
+    }
+
+    pub fn view<'a>(&'a self, _server_backend: &'a S) -> Element<'a, MainAppMessage<S>> {
+        iced::widget::stack([
+            match self.state {
+                MainAppState::Preview | MainAppState::CapturePhotosPrepare { .. } => {
+                    self.feed.view_with_crop_guide(PHOTO_ASPECT_RATIO)
+                }
+                _ => self
+                    .feed
+                    .view()
+                    .content_fit(
+                        if matches!(self.state, MainAppState::CapturePhotos { .. }) {
+                            ContentFit::Contain
+                        } else {
+                            ContentFit::Cover
+                        },
+                    )
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .into(),
+            },
             match &self.state {
                 MainAppState::PaymentRequired { error } => title_overlay(
                     container(
@@ -547,17 +1846,26 @@ impl<
                 MainAppState::CapturePhotosPrepare { ready_timeline } => {
                     animations::ready::view(ready_timeline.value()).into()
                 }
-                MainAppState::CapturePhotos { current, state } => iced::widget::stack([
-                    status_overlay::status_overlay(text(format!("photo {} of {PHOTO_COUNT}", current + 1)).size(24)).into(),
+                MainAppState::CapturePhotos { current, state, .. } => iced::widget::stack([
+                    status_overlay::status_overlay(text(format!("photo {} of {}", current + 1, self.photo_count)).size(24)).into(),
                     match state {
                         CapturePhotosState::Countdown {
                             current,
                             countdown_timeline,
+                            ..
                         } => animations::countdown_circle::view(*current, countdown_timeline.value())
                             .into(),
-                        CapturePhotosState::Capture { capture_timeline } => {
+                        CapturePhotosState::Capture { capture_timeline, .. } => {
                             animations::capture_flash::view(capture_timeline.value()).into()
                         }
+                        CapturePhotosState::Failed { .. } => title_overlay(
+                            column([
+                                title_text("Something went wrong, let's try that photo again").into(),
+                                supporting_text("Get ready, the countdown will start over.").into(),
+                                vertical_space().height(12.0).into(),
+                            ]),
+                            true,
+                        ),
                         CapturePhotosState::Preview {
                             preview_timeline,
                             captured_handle,
@@ -567,6 +1875,118 @@ impl<
                         }
                     }
                 ]).into(),
+                MainAppState::ReviewPhotos { selected } => {
+                    let selected = *selected;
+                    let outline = move |choice: usize| {
+                        move |theme: &iced::Theme| {
+                            if choice == selected {
+                                container::Style {
+                                    border: iced::Border::default()
+                                        .rounded(8.0)
+                                        .color(theme.extended_palette().primary.strong.color)
+                                        .width(4.0),
+                                    ..Default::default()
+                                }
+                            } else {
+                                container::Style::default()
+                            }
+                        }
+                    };
+                    title_overlay(
+                        column([
+                            title_text("How do your photos look?").into(),
+                            supporting_text("Pick one to retake with [UP] and [DOWN] and press [SPACE], or press [ESC] to retake them all.").into(),
+                            vertical_space().height(12.0).into(),
+                            row(self.previews.iter().enumerate().map(|(i, preview)| {
+                                container(
+                                    iced::widget::image(preview.clone())
+                                        .width(240)
+                                        .content_fit(ContentFit::Contain),
+                                )
+                                .padding(6)
+                                .style(outline(i))
+                                .into()
+                            }))
+                            .spacing(12)
+                            .into(),
+                            vertical_space().height(12.0).into(),
+                            container(text("Looks good, keep going").size(24))
+                                .padding(10)
+                                .style(outline(self.previews.len()))
+                                .into(),
+                        ])
+                        .align_x(Alignment::Center),
+                        false,
+                    )
+                }
+                MainAppState::ChooseFilter { selected, .. } => title_overlay(
+                    column([
+                        title_text("Add a filter?").into(),
+                        supporting_text("Try them with [UP] and [DOWN] and press [SPACE] to use one, or press [ESC] to go back to your photos.").into(),
+                        vertical_space().height(12.0).into(),
+                        row(self.previews.iter().map(|preview| {
+                            iced::widget::image(preview.clone())
+                                .width(240)
+                                .content_fit(ContentFit::Contain)
+                                .into()
+                        }))
+                        .spacing(12)
+                        .into(),
+                        vertical_space().height(12.0).into(),
+                        text(ColorFilter::ALL[*selected].to_string()).size(24).into(),
+                    ])
+                    .align_x(Alignment::Center),
+                    false,
+                ),
+                MainAppState::ChooseTemplate {
+                    selected,
+                    thumbnails,
+                } => title_overlay(
+                    column([
+                        title_text("Pick a frame").into(),
+                        supporting_text("Choose with [UP] and [DOWN] and press [SPACE], or press [ESC] to go back to your photos.").into(),
+                        vertical_space().height(12.0).into(),
+                        row(self.templates.iter().zip(thumbnails).enumerate().map(|(i, (template, thumbnail))| {
+                            column([
+                                container(match thumbnail {
+                                    Some(thumbnail) => Element::from(
+                                        iced::widget::image(thumbnail.clone())
+                                            .width(TEMPLATE_THUMBNAIL_WIDTH as f32)
+                                            .content_fit(ContentFit::Contain),
+                                    ),
+                                    None => loading_spinners::Circular::new()
+                                        .size(40.0)
+                                        .bar_height(4.0)
+                                        .easing(&loading_spinners::easing::STANDARD_DECELERATE)
+                                        .into(),
+                                })
+                                .padding(6)
+                                .style(move |theme: &iced::Theme| {
+                                    if i == *selected {
+                                        container::Style {
+                                            border: iced::Border::default()
+                                                .rounded(8.0)
+                                                .color(theme.extended_palette().primary.strong.color)
+                                                .width(4.0),
+                                            ..Default::default()
+                                        }
+                                    } else {
+                                        container::Style::default()
+                                    }
+                                })
+                                .into(),
+                                text(template.name()).into(),
+                            ])
+                            .align_x(Alignment::Center)
+                            .spacing(6)
+                            .into()
+                        }))
+                        .spacing(12)
+                        .into(),
+                    ])
+                    .align_x(Alignment::Center),
+                    false,
+                ),
                 MainAppState::RenderedPreview {
                     progress_timeline,
                     template_preview_timeline,
@@ -579,7 +1999,12 @@ impl<
                             )
                             .into(),
                             title_text("Your photos are ready!").into(),
-                            supporting_text("On the next screen, enter your emails.").into(),
+                            supporting_text(if self.can_print() {
+                                "Press [UP] to print a copy, or enter your emails on the next screen."
+                            } else {
+                                "On the next screen, enter your emails."
+                            })
+                            .into(),
                             vertical_space().height(12.0).into(),
                             progress_bar(0.0..=1.0, progress_timeline.value())
                                 .height(4.0)
@@ -588,21 +2013,46 @@ impl<
                         false,
                     )
                     .into(),
-                    status_overlay::status_overlay(row([
-                        loading_spinners::Circular::new()
-                            .size(30.0)
-                            .bar_height(3.0)
-                            .easing(&loading_spinners::easing::STANDARD_DECELERATE)
-                            .into(),
-                        text("Uploading photos in the background...").into()
-                    ]).spacing(8)).into()
+                    self.upload_status()
                 ]).into(),
-                MainAppState::EmailEntry => iced::widget::stack([
+                MainAppState::EmailEntry {
+                    undelivered,
+                    selected,
+                    print_failed,
+                } => iced::widget::stack([
                     title_overlay(
                         row([
                             column([
                                 title_text("Enter your email addresses").into(),
-                                supporting_text("Start typing to add an email.").into(),
+                                if undelivered.is_empty() && !print_failed {
+                                    supporting_text("Start typing to add an email.").into()
+                                } else {
+                                    container(
+                                        iced::widget::text(if undelivered.is_empty() {
+                                            "Your strip couldn't be printed, but you can still get it by email. Start typing to add an email.".to_string()
+                                        } else {
+                                            format!(
+                                                "Your photos couldn't be sent to {}. Check the spelling and enter it again, or press [Enter] to finish.",
+                                                undelivered.join(", ")
+                                            )
+                                        })
+                                        .size(16),
+                                    )
+                                    .style(|theme: &iced::Theme| container::Style {
+                                        border: iced::Border::default().rounded(4.0).color(
+                                            theme.extended_palette().danger.strong.color,
+                                        ).width(1.0),
+                                        background: Some(
+                                            theme.extended_palette().danger.weak.color.into(),
+                                        ),
+                                        text_color: Some(
+                                            theme.extended_palette().danger.weak.text,
+                                        ),
+                                        ..Default::default()
+                                    })
+                                    .padding(8)
+                                    .into()
+                                },
                                 vertical_space().height(12.0).into(),
                                 container(
                                     column([
@@ -625,7 +2075,7 @@ impl<
                                             })
                                             .size(24))
                                             .on_press_maybe(
-                                                if self.upload_handle.is_none() && self.emails[0].len() == 0 {
+                                                if self.upload_handle.is_none() && self.queued.is_none() && self.emails[0].len() == 0 {
                                                     None
                                                 } else {
                                                     Some(MainAppMessage::EmailSubmit)
@@ -635,18 +2085,37 @@ impl<
                                             .into(),
                                         ])
                                         .into(),
+                                        if self.email_invalid {
+                                            text("That doesn't look like an email address. Please check it and try again.")
+                                                .style(iced::widget::text::danger)
+                                                .into()
+                                        } else {
+                                            Space::new(0, 0).into()
+                                        },
                                         vertical_space().height(12.0).into(),
                                         container(
-                                            if self.emails.len() <= 1 {
+                                            // uploaded, but to somewhere a phone can't open
+                                            if self.emails.len() <= 1 && self.upload_handle.is_some() && self.qr_code_data.is_none() {
+                                                Element::from(Space::new(0, 0))
+                                            } else if self.emails.len() <= 1 {
                                                 Element::from(column([
-                                                    text("You can also scan the QR code to download your photos!").into(),
+                                                    text(if self.queued.is_some() {
+                                                        "We're offline right now, but you can still enter your emails."
+                                                    } else {
+                                                        "You can also scan the QR code to download your photos!"
+                                                    }).into(),
                                                     Element::from(if let Some(ref qr_code_data) = self.qr_code_data {
                                                         container(
                                                             iced::widget::qr_code(qr_code_data).cell_size(8).style(|_|iced::widget::qr_code::Style {
                                                                 background: Color::WHITE,
                                                                 cell: Color::BLACK
                                                             })
-                                                        ).center((QR_CODE_SIDE_LENGTH * 8) as u16).padding(8)
+                                                        ).center((self.qr_code_side_length * 8) as u16).padding(8)
+                                                    } else if self.queued.is_some() {
+                                                        container(
+                                                            text("Your photos will be emailed once we're back online.")
+                                                                .color(Color::BLACK)
+                                                        ).style(|_| container::background(Color::WHITE)).padding(8).center((QR_CODE_SIDE_LENGTH * 8) as u16)
                                                     } else {
                                                         container(
                                                             column([
@@ -666,24 +2135,40 @@ impl<
                                                 column(
                                                     self.emails
                                                         .iter()
+                                                        .enumerate()
                                                         .skip(1)
-                                                        .map(|email| {
+                                                        .map(|(index, email)| {
+                                                            let is_selected = *selected == Some(index);
                                                             iced::widget::container(
                                                                 iced::widget::text(email.as_str())
                                                                     .size(24)
                                                             ).width(Length::Fill)
                                                                 .padding(10)
-                                                                .style(|theme: &iced::Theme| container::Style {
-                                                                    background: Some(
-                                                                        theme.extended_palette().background.strong.color.into(),
-                                                                    ),
-                                                                    text_color: Some(
-                                                                        theme.extended_palette().background.strong.text,
-                                                                    ),
-                                                                    ..Default::default()
+                                                                .style(move |theme: &iced::Theme| {
+                                                                    let pair = if is_selected {
+                                                                        theme.extended_palette().primary.strong
+                                                                    } else {
+                                                                        theme.extended_palette().background.strong
+                                                                    };
+                                                                    container::Style {
+                                                                        background: Some(pair.color.into()),
+                                                                        text_color: Some(pair.text),
+                                                                        ..Default::default()
+                                                                    }
                                                                 }).into()
                                                         }),
-                                                ).push(vertical_space()).spacing(8).into()
+                                                )
+                                                .push(vertical_space())
+                                                .push(
+                                                    text(if selected.is_some() {
+                                                        "Press [Delete] to remove the highlighted email."
+                                                    } else {
+                                                        "Press [DOWN] to pick an email to remove."
+                                                    })
+                                                    .size(16),
+                                                )
+                                                .spacing(8)
+                                                .into()
                                             },
                                         )
                                         .padding(12)
@@ -730,18 +2215,39 @@ impl<
                         false,
                     ).into(),
                     if self.upload_handle.is_none() {
-                        status_overlay::status_overlay(row([
-                            loading_spinners::Circular::new()
-                                .size(30.0)
-                                .bar_height(3.0)
-                                .easing(&loading_spinners::easing::STANDARD_DECELERATE)
-                                .into(),
-                            text("Uploading photos in the background...").into()
-                        ]).spacing(8)).into()
+                        self.upload_status()
                     } else {
                         "".into()
                     }
                 ]).into(),
+                MainAppState::Rendering => title_overlay(
+                    column([
+                        title_text("Putting your photos together...").into(),
+                        supporting_text("This will only take a moment.").into(),
+                        vertical_space().height(12.0).into(),
+                    ]),
+                    false,
+                )
+                .into(),
+                MainAppState::Printing => title_overlay(
+                    column([
+                        container(
+                            loading_spinners::Circular::new()
+                                .size(40.0)
+                                .bar_height(4.0)
+                                .easing(&loading_spinners::easing::STANDARD_DECELERATE),
+                        )
+                        .center(Length::Fill)
+                        .into(),
+                        title_text("Sending your photos to the printer...").into(),
+                        supporting_text("Next, you can enter your emails.").into(),
+                    ]),
+                    false,
+                )
+                .into(),
+                MainAppState::EmailSent { timeline } => {
+                    title_overlay(animations::email_sent::view(timeline.value()), false)
+                }
                 MainAppState::Emailing { progress_timeline } => title_overlay(
                     iced::widget::column([
                         container(
@@ -755,15 +2261,724 @@ impl<
                         title_text("We're emailing your photos now.").into(),
                         supporting_text("Check your inbox to download your pictures.").into(),
                         vertical_space().height(12.0).into(),
-                        progress_bar(0.0..=1.0, progress_timeline.value())
-                            .height(8.0)
-                            .into(),
+                        // estimated from how long emails usually take unless
+                        // the backend reports how far along it is
+                        progress_bar(
+                            0.0..=1.0,
+                            self.upload_progress
+                                .unwrap_or_else(|| progress_timeline.value()),
+                        )
+                        .height(8.0)
+                        .into(),
                     ]),
                     false,
                 )
                 .into(),
             },
         ])
+        .push_maybe(self.secondary_feed_view())
+        .push_maybe((!self.feed.is_healthy()).then(|| {
+            status_overlay::status_overlay(
+                text(if self.feed.is_connected() {
+                    "Camera not responding, retrying..."
+                } else {
+                    "Camera disconnected — please notify staff"
+                })
+                .size(24),
+            )
+            .align_y(Alignment::Start)
+        }))
+        .push_maybe(self.clipping().map(|clipping| {
+            status_overlay::status_overlay(
+                text(match clipping {
+                    Clipping::TooBright => "The picture looks too bright",
+                    Clipping::TooDark => "The picture looks too dark",
+                })
+                .size(24),
+            )
+        }))
+        .push_maybe(self.low_battery_percent().map(|percent| {
+            status_overlay::status_overlay(
+                text(format!(
+                    "Camera battery at {}% — please notify staff",
+                    percent
+                ))
+                .size(24),
+            )
+            .align_x(Alignment::End)
+            .align_y(Alignment::Start)
+        }))
         .into()
     }
 }
+
+/// A handle to show `photo` with `filter` on it, shrunk to `width` if there
+/// is a filter to apply so it doesn't hold up the UI.
+fn filtered_handle(photo: &Frame, filter: ColorFilter, width: u32) -> Handle {
+    if filter == ColorFilter::None {
+        return photo.to_handle();
+    }
+    let mut photo = shrink(&photo.to_image(), width);
+    apply_filter_in_place(&mut photo, filter);
+    Frame::from(photo).to_handle()
+}
+
+/// `image` scaled down to `width`, keeping its shape. Quick but rough, which
+/// is fine for previews.
+fn shrink(image: &RgbaImage, width: u32) -> RgbaImage {
+    let width = width.min(image.width()).max(1);
+    let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+    image::imageops::thumbnail(image, width, height)
+}
+
+/// Decodes `template` into `cell` unless that's been done already, blocking
+/// while another thread is at it. A failure is kept too, since decoding the
+/// same file again won't fix it.
+fn load_render_assets<'a>(
+    cell: &'a OnceLock<Result<RenderAssets, String>>,
+    template: &StripTemplate,
+) -> Result<&'a RenderAssets, String> {
+    cell.get_or_init(|| RenderAssets::load(template).map_err(|err| err.to_string()))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// Catches addresses that can't work, like "foo@" or "foo@bar". Whether the
+/// address actually exists is left to the server.
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !email.chars().any(char::is_whitespace)
+        && domain.split('.').count() >= 2
+        && domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+/// Cells across a QR code of `version`, quiet zone included.
+const fn qr_code_side_length(version: u8) -> usize {
+    QR_CODE_QUIET_ZONE * 2 + version as usize * 4 + 17
+}
+
+/// The smallest QR code from `QR_CODE_MIN_VERSION` up that fits `link`, and
+/// how many cells across it is.
+fn link_qr_code(link: &str) -> Option<(iced::widget::qr_code::Data, usize)> {
+    (QR_CODE_MIN_VERSION..=QR_CODE_MAX_VERSION).find_map(|version| {
+        let data = iced::widget::qr_code::Data::with_version(
+            link,
+            iced::widget::qr_code::Version::Normal(version),
+            iced::widget::qr_code::ErrorCorrection::Medium,
+        )
+        .ok()?;
+        Some((data, qr_code_side_length(version)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{
+        self, ScriptedCamera, ScriptedCameraBackend, ScriptedServerBackend, ServerCall,
+    };
+
+    /// Longest a test waits for the booth to get anywhere.
+    const TIMEOUT: Duration = Duration::from_secs(20);
+
+    type TestApp = MainApp<ScriptedCameraBackend, ScriptedServerBackend>;
+
+    /// The app with a scripted camera and server, driven the way the runtime
+    /// would: every message its tasks produce is fed back to it.
+    struct Booth {
+        app: TestApp,
+        camera: ScriptedCamera,
+        server: ScriptedServerBackend,
+        /// Where the app queues sessions, instead of the real spool.
+        spool: tempfile::TempDir,
+    }
+
+    impl Booth {
+        /// A sped up, silent booth on the start screen.
+        async fn new() -> Self {
+            Self::with_settings(CaptureSettings {
+                fast_animations: true,
+                sound_effects: false,
+                ..Default::default()
+            })
+            .await
+        }
+
+        async fn with_settings(settings: CaptureSettings) -> Self {
+            let camera = ScriptedCamera::new();
+            let feed = CameraFeed::new(camera.clone(), CameraFeedOptions::default());
+            feed.spawn_capture_loop();
+            let (app, task) = TestApp::new(
+                feed,
+                None,
+                settings,
+                String::new(),
+                vec![StripTemplate::bundled()],
+            );
+            let spool = tempfile::tempdir().unwrap();
+            let mut booth = Self {
+                app,
+                camera,
+                server: ScriptedServerBackend::default(),
+                spool,
+            };
+            booth.app.spool = Some(booth.spool.path().to_path_buf());
+            booth.run(task).await;
+            booth
+        }
+
+        /// Runs `task` and feeds its messages to the app, and so on until
+        /// nothing is left to run.
+        async fn run(&mut self, task: Task<MainAppMessage<ScriptedServerBackend>>) {
+            let mut messages = VecDeque::from(testing::run(task).await);
+            while let Some(message) = messages.pop_front() {
+                let task = self.app.update(message, self.server.clone());
+                messages.extend(testing::run(task).await);
+            }
+        }
+
+        async fn send(&mut self, message: MainAppMessage<ScriptedServerBackend>) {
+            let task = self.app.update(message, self.server.clone());
+            self.run(task).await;
+        }
+
+        async fn press(&mut self, key: KeyMessage) {
+            self.send(MainAppMessage::KeyReleased(key)).await;
+        }
+
+        /// Ticks until `done` is true of the state, failing the test if that
+        /// takes longer than `TIMEOUT`.
+        async fn tick_until(&mut self, what: &str, done: impl Fn(&MainAppState) -> bool) {
+            let deadline = Instant::now() + TIMEOUT;
+            while !done(&self.app.state) {
+                assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+                self.send(MainAppMessage::Tick).await;
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+
+        /// Starts a session from the start screen, which goes on to the
+        /// countdown by itself.
+        async fn start(&mut self) {
+            self.press(KeyMessage::Space).await;
+            assert!(matches!(self.app.state, MainAppState::Preview));
+            self.press(KeyMessage::Space).await;
+        }
+
+        /// Starts a session and takes every photo.
+        async fn take_photos(&mut self) {
+            self.start().await;
+            self.tick_until("the photos to be reviewed", |state| {
+                matches!(state, MainAppState::ReviewPhotos { .. })
+            })
+            .await;
+        }
+
+        /// Takes every photo and carries on to the strip, which is uploaded.
+        async fn render(&mut self) {
+            self.take_photos().await;
+            // carrying on is the last choice, and starts out selected
+            self.press(KeyMessage::Space).await;
+            assert!(matches!(self.app.state, MainAppState::ChooseFilter { .. }));
+            // with only one template this renders straight away
+            self.press(KeyMessage::Space).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn session_is_captured_rendered_uploaded_and_emailed() {
+        let mut booth = Booth::new().await;
+        let photo_count = booth.app.photo_count;
+
+        booth.take_photos().await;
+        assert_eq!(booth.camera.still_captures(), photo_count);
+        assert_eq!(booth.app.captured_photos.len(), photo_count);
+        assert!(booth.server.calls().is_empty());
+
+        booth.press(KeyMessage::Space).await;
+        booth.press(KeyMessage::Space).await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::RenderedPreview { .. }
+        ));
+        assert_eq!(
+            booth.server.calls(),
+            [ServerCall::Upload {
+                photos: photo_count,
+                print: true,
+                gif: true,
+            }]
+        );
+        assert_eq!(booth.app.upload_handle, Some(1));
+        assert_eq!(booth.app.upload_progress, Some(1.0));
+        assert!(booth.app.qr_code_data.is_some());
+        assert!(booth.app.unsent.is_empty());
+
+        booth
+            .tick_until("the email screen", |state| {
+                matches!(state, MainAppState::EmailEntry { .. })
+            })
+            .await;
+        booth
+            .send(MainAppMessage::EmailInput("guest@example.com".to_string()))
+            .await;
+        booth.send(MainAppMessage::EmailSubmit).await;
+        // submitting an empty address sends the ones entered
+        booth.send(MainAppMessage::EmailSubmit).await;
+        assert!(matches!(booth.app.state, MainAppState::EmailSent { .. }));
+        assert_eq!(
+            booth.server.calls()[1..],
+            [ServerCall::Email {
+                handle: 1,
+                emails: vec!["guest@example.com".to_string()],
+            }]
+        );
+        assert!(booth.app.upload_handle.is_none());
+        assert!(booth.app.strip.is_none());
+
+        booth
+            .tick_until("the start screen", |state| {
+                matches!(state, MainAppState::PaymentRequired { error: None })
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn failed_email_starts_over_with_an_error() {
+        let mut booth = Booth::new().await;
+        booth.server.fail_emails(1, "smtp is down");
+        booth.render().await;
+        booth
+            .tick_until("the email screen", |state| {
+                matches!(state, MainAppState::EmailEntry { .. })
+            })
+            .await;
+        booth
+            .send(MainAppMessage::EmailInput("guest@example.com".to_string()))
+            .await;
+        booth.send(MainAppMessage::EmailSubmit).await;
+        booth.send(MainAppMessage::EmailSubmit).await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::PaymentRequired { error: Some(_) }
+        ));
+        assert_eq!(booth.server.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn submitting_again_while_emailing_is_ignored() {
+        let mut booth = Booth::new().await;
+        booth.render().await;
+        booth
+            .tick_until("the email screen", |state| {
+                matches!(state, MainAppState::EmailEntry { .. })
+            })
+            .await;
+        booth
+            .send(MainAppMessage::EmailInput("guest@example.com".to_string()))
+            .await;
+        booth.send(MainAppMessage::EmailSubmit).await;
+        // the third lands before the email from the second has gone out
+        let sending = booth
+            .app
+            .update(MainAppMessage::EmailSubmit, booth.server.clone());
+        assert!(matches!(booth.app.state, MainAppState::Emailing { .. }));
+        let ignored = booth
+            .app
+            .update(MainAppMessage::EmailSubmit, booth.server.clone());
+        assert!(testing::run(ignored).await.is_empty());
+        booth.run(sending).await;
+        assert!(matches!(booth.app.state, MainAppState::EmailSent { .. }));
+        assert_eq!(booth.server.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn camera_filter_is_kept_off_the_photos_and_chosen_first() {
+        let mut booth = Booth::with_settings(CaptureSettings {
+            fast_animations: true,
+            sound_effects: false,
+            color_filter: ColorFilter::Sepia,
+            ..Default::default()
+        })
+        .await;
+        booth.take_photos().await;
+        for photo in &booth.app.captured_photos {
+            assert_eq!(
+                photo.to_image().get_pixel(0, 0),
+                testing::frame(1, 1).get_pixel(0, 0)
+            );
+        }
+        booth.press(KeyMessage::Space).await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::ChooseFilter { selected: 2, .. }
+        ));
+        booth.press(KeyMessage::Space).await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::RenderedPreview { .. }
+        ));
+        assert_eq!(booth.app.photo_filter, ColorFilter::Sepia);
+    }
+
+    #[tokio::test]
+    async fn failed_print_still_emails_the_uploaded_photos() {
+        let mut booth = Booth::new().await;
+        booth.render().await;
+        booth.app.state = MainAppState::Printing;
+        booth
+            .send(MainAppMessage::Printed(Err("out of paper".to_string())))
+            .await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::EmailEntry {
+                print_failed: true,
+                ..
+            }
+        ));
+        assert_eq!(booth.app.upload_handle, Some(1));
+
+        booth
+            .send(MainAppMessage::EmailInput("guest@example.com".to_string()))
+            .await;
+        booth.send(MainAppMessage::EmailSubmit).await;
+        booth.send(MainAppMessage::EmailSubmit).await;
+        assert!(matches!(booth.app.state, MainAppState::EmailSent { .. }));
+        assert_eq!(
+            booth.server.calls()[1..],
+            [ServerCall::Email {
+                handle: 1,
+                emails: vec!["guest@example.com".to_string()],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn still_that_fails_once_is_retried_straight_away() {
+        let mut booth = Booth::new().await;
+        let photo_count = booth.app.photo_count;
+        booth.camera.fail_stills(1, "usb hiccup");
+        booth.start().await;
+        booth
+            .tick_until("the photos to be reviewed", |state| {
+                assert!(
+                    !matches!(
+                        state,
+                        MainAppState::CapturePhotos {
+                            state: CapturePhotosState::Failed { .. },
+                            ..
+                        }
+                    ),
+                    "a single failure shouldn't start the countdown over"
+                );
+                matches!(state, MainAppState::ReviewPhotos { .. })
+            })
+            .await;
+        assert_eq!(booth.camera.still_captures(), photo_count + 1);
+        assert_eq!(booth.app.captured_photos.len(), photo_count);
+    }
+
+    #[tokio::test]
+    async fn still_that_fails_twice_starts_its_countdown_over() {
+        let mut booth = Booth::new().await;
+        let photo_count = booth.app.photo_count;
+        booth.camera.fail_stills(2, "usb hiccup");
+        booth.start().await;
+        booth
+            .tick_until("the retry message", |state| {
+                matches!(
+                    state,
+                    MainAppState::CapturePhotos {
+                        current: 0,
+                        state: CapturePhotosState::Failed { .. },
+                        failures: 1,
+                        ..
+                    }
+                )
+            })
+            .await;
+        assert_eq!(booth.camera.still_captures(), 2);
+        assert!(booth.app.captured_photos.is_empty());
+
+        booth
+            .tick_until("the photos to be reviewed", |state| {
+                matches!(state, MainAppState::ReviewPhotos { .. })
+            })
+            .await;
+        assert_eq!(booth.camera.still_captures(), photo_count + 2);
+        assert_eq!(booth.app.captured_photos.len(), photo_count);
+    }
+
+    #[tokio::test]
+    async fn session_is_given_up_after_too_many_failed_photos() {
+        let mut booth = Booth::new().await;
+        let attempts = 2 * MAX_FAILED_PHOTOS as usize;
+        booth.camera.fail_stills(attempts, "usb hiccup");
+        booth.start().await;
+        booth
+            .tick_until("the start screen", |state| {
+                matches!(state, MainAppState::PaymentRequired { error: Some(_) })
+            })
+            .await;
+        assert_eq!(booth.camera.still_captures(), attempts);
+        assert!(booth.app.captured_photos.is_empty());
+
+        // a still that turns up late changes nothing
+        booth
+            .send(MainAppMessage::StillCaptured(Ok(testing::frame(
+                testing::FRAME_WIDTH,
+                testing::FRAME_HEIGHT,
+            ))))
+            .await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::PaymentRequired { error: Some(_) }
+        ));
+        assert!(booth.app.captured_photos.is_empty());
+    }
+
+    #[tokio::test]
+    async fn results_for_an_earlier_session_are_ignored() {
+        let mut booth = Booth::new().await;
+        booth.render().await;
+        let earlier = booth.app.session_generation - 1;
+
+        booth
+            .send(MainAppMessage::UploadProgress(earlier, 0.5))
+            .await;
+        booth
+            .send(MainAppMessage::Uploaded(
+                earlier,
+                Err("offline".to_string()),
+            ))
+            .await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::RenderedPreview { .. }
+        ));
+        assert_eq!(booth.app.upload_handle, Some(1));
+        assert_eq!(booth.app.upload_progress, Some(1.0));
+
+        // closed straight away, since its guests can't add emails to it
+        let spool = tempfile::tempdir().unwrap();
+        let dir = spool.path().join("earlier.open");
+        std::fs::create_dir(&dir).unwrap();
+        booth
+            .send(MainAppMessage::Queued(earlier, Ok(dir.clone())))
+            .await;
+        assert!(booth.app.queued.is_none());
+        assert!(!dir.exists());
+        assert!(spool.path().join("earlier").is_dir());
+    }
+
+    #[tokio::test]
+    async fn failed_upload_of_an_earlier_session_is_still_queued() {
+        let mut booth = Booth::new().await;
+        booth.render().await;
+        let earlier = booth.app.session_generation - 1;
+        booth.app.unsent.insert(
+            earlier,
+            QueuedSession {
+                strip: testing::frame(40, 120),
+                print: None,
+                photos: vec![testing::frame(30, 20)],
+                gif: None,
+                emails: Vec::new(),
+            },
+        );
+
+        booth
+            .send(MainAppMessage::Uploaded(
+                earlier,
+                Err("offline".to_string()),
+            ))
+            .await;
+        assert!(booth.app.unsent.is_empty());
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::RenderedPreview { .. }
+        ));
+        assert!(booth.app.queued.is_none());
+        let queued: Vec<_> = std::fs::read_dir(booth.spool.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(queued.len(), 1);
+        assert!(!queued[0].to_string_lossy().ends_with(".open"));
+    }
+
+    /// Every state, and whether the booth is waiting on the guests there
+    /// while nothing is uploading.
+    fn every_state(speed: animations::Speed) -> Vec<(MainAppState, bool)> {
+        vec![
+            (MainAppState::PaymentRequired { error: None }, false),
+            (MainAppState::Preview, true),
+            (
+                MainAppState::CapturePhotosPrepare {
+                    ready_timeline: animations::ready::animation(speed).begin_animation(),
+                },
+                false,
+            ),
+            (
+                MainAppState::CapturePhotos {
+                    current: 0,
+                    state: CapturePhotosState::countdown(3, speed),
+                    failures: 0,
+                    retake: false,
+                },
+                false,
+            ),
+            (MainAppState::ReviewPhotos { selected: 0 }, true),
+            (
+                MainAppState::ChooseFilter {
+                    selected: 0,
+                    small_photos: Vec::new(),
+                },
+                true,
+            ),
+            (
+                MainAppState::ChooseTemplate {
+                    selected: 0,
+                    thumbnails: Vec::new(),
+                },
+                true,
+            ),
+            (MainAppState::Rendering, false),
+            (
+                MainAppState::RenderedPreview {
+                    progress_timeline: anim::Options::new(0.0, 1.0).begin_animation(),
+                    template_preview_timeline: animations::upsell_templates::animation(speed)
+                        .begin_animation(),
+                },
+                false,
+            ),
+            (MainAppState::Printing, false),
+            (
+                MainAppState::EmailEntry {
+                    undelivered: Vec::new(),
+                    selected: None,
+                    print_failed: false,
+                },
+                false,
+            ),
+            (
+                MainAppState::Emailing {
+                    progress_timeline: anim::Options::new(0.0, 1.0).begin_animation(),
+                },
+                false,
+            ),
+            (
+                MainAppState::EmailSent {
+                    timeline: animations::email_sent::animation(speed).begin_animation(),
+                },
+                false,
+            ),
+        ]
+    }
+
+    /// The name of `state`'s variant, for saying which one failed.
+    fn state_name(state: &MainAppState) -> &'static str {
+        match state {
+            MainAppState::PaymentRequired { .. } => "PaymentRequired",
+            MainAppState::Preview => "Preview",
+            MainAppState::CapturePhotosPrepare { .. } => "CapturePhotosPrepare",
+            MainAppState::CapturePhotos { .. } => "CapturePhotos",
+            MainAppState::ReviewPhotos { .. } => "ReviewPhotos",
+            MainAppState::ChooseFilter { .. } => "ChooseFilter",
+            MainAppState::ChooseTemplate { .. } => "ChooseTemplate",
+            MainAppState::Rendering => "Rendering",
+            MainAppState::RenderedPreview { .. } => "RenderedPreview",
+            MainAppState::Printing => "Printing",
+            MainAppState::EmailEntry { .. } => "EmailEntry",
+            MainAppState::Emailing { .. } => "Emailing",
+            MainAppState::EmailSent { .. } => "EmailSent",
+        }
+    }
+
+    #[tokio::test]
+    async fn only_screens_waiting_on_the_guests_go_idle() {
+        let mut booth = Booth::with_settings(CaptureSettings {
+            fast_animations: true,
+            sound_effects: false,
+            idle_timeout: 0.0,
+            ..Default::default()
+        })
+        .await;
+        for (state, idle) in every_state(booth.app.settings.animation_speed()) {
+            let name = state_name(&state);
+            booth.app.state = state;
+            assert_eq!(booth.app.is_idle(), idle, "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn nothing_goes_idle_before_the_timeout() {
+        let mut booth = Booth::new().await;
+        booth.app.upload_handle = Some(1);
+        for (state, _) in every_state(booth.app.settings.animation_speed()) {
+            let name = state_name(&state);
+            booth.app.state = state;
+            assert!(!booth.app.is_idle(), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn email_screen_goes_idle_once_the_photos_are_uploaded_or_queued() {
+        let mut booth = Booth::with_settings(CaptureSettings {
+            fast_animations: true,
+            sound_effects: false,
+            idle_timeout: 0.0,
+            ..Default::default()
+        })
+        .await;
+        booth.app.state = MainAppState::EmailEntry {
+            undelivered: Vec::new(),
+            selected: None,
+            print_failed: false,
+        };
+        assert!(!booth.app.is_idle());
+        booth.app.upload_handle = Some(1);
+        assert!(booth.app.is_idle());
+        booth.app.upload_handle = None;
+        booth.app.queued = Some(PathBuf::from("queued.open"));
+        assert!(booth.app.is_idle());
+    }
+
+    #[tokio::test]
+    async fn idle_booth_starts_over_with_nothing_left_of_the_session() {
+        let mut booth = Booth::new().await;
+        booth.render().await;
+        booth
+            .tick_until("the email screen", |state| {
+                matches!(state, MainAppState::EmailEntry { .. })
+            })
+            .await;
+        booth
+            .send(MainAppMessage::EmailInput("guest@example.com".to_string()))
+            .await;
+        booth.send(MainAppMessage::EmailSubmit).await;
+        let generation = booth.app.session_generation;
+
+        booth.app.settings.idle_timeout = 0.0;
+        booth.send(MainAppMessage::Tick).await;
+        assert!(matches!(
+            booth.app.state,
+            MainAppState::PaymentRequired { error: None }
+        ));
+        assert!(booth.app.session_generation > generation);
+        assert!(booth.app.emails.is_empty());
+        assert!(booth.app.upload_handle.is_none());
+        assert!(booth.app.upload_progress.is_none());
+        assert!(booth.app.qr_code_data.is_none());
+        assert!(booth.app.strip.is_none());
+        assert!(booth.app.strip_handle.is_none());
+        assert!(booth.app.print_png.is_none());
+        // nobody emailed the photos
+        assert_eq!(booth.server.calls().len(), 1);
+    }
+}
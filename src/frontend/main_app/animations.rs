@@ -1,10 +1,37 @@
+use std::time::Duration;
+
 pub mod capture_flash;
 pub mod capture_preview;
 pub mod countdown_circle;
+pub mod email_sent;
 pub mod ready;
 pub mod upsell_templates;
 
-#[cfg(feature = "fast_animations")]
-const LENGTH_DIVISOR: u64 = 10;
-#[cfg(not(feature = "fast_animations"))]
-const LENGTH_DIVISOR: u64 = 1;
+/// How many times faster the animations run when sped up.
+const FAST_DIVISOR: u64 = 10;
+
+/// How quickly the animations play, so staff can run through a session
+/// quickly while setting up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    Normal,
+    Fast,
+}
+
+impl Speed {
+    pub fn new(fast: bool) -> Self {
+        if fast {
+            Self::Fast
+        } else {
+            Self::Normal
+        }
+    }
+
+    /// How long something that takes `millis` at normal speed takes.
+    pub fn length(self, millis: u64) -> Duration {
+        Duration::from_millis(match self {
+            Self::Normal => millis,
+            Self::Fast => millis / FAST_DIVISOR,
+        })
+    }
+}
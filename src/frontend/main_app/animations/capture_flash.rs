@@ -1,26 +1,25 @@
-use std::time::Duration;
-
 use anim::{easing, Animatable};
 use iced::{
     widget::{container, Container},
     Color, Length,
 };
 
-use super::LENGTH_DIVISOR;
+use super::Speed;
 
-pub const ANIMATION_LENGTH: u64 = 400 / LENGTH_DIVISOR;
+/// In milliseconds at normal speed.
+pub const ANIMATION_LENGTH: u64 = 400;
 
 #[derive(Debug, Clone, Copy, Animatable)]
 pub struct AnimationState {
     opacity: f32,
 }
 
-pub fn animation() -> impl anim::Animation<Item = AnimationState> {
+pub fn animation(speed: Speed) -> impl anim::Animation<Item = AnimationState> {
     anim::builder::key_frames([
         anim::KeyFrame::new(AnimationState { opacity: 1.0 }).by_percent(0.0),
         anim::KeyFrame::new(AnimationState { opacity: 0.0 })
             .easing(easing::cubic_ease().mode(easing::EasingMode::Out))
-            .by_duration(Duration::from_millis(ANIMATION_LENGTH)),
+            .by_duration(speed.length(ANIMATION_LENGTH)),
     ])
 }
 
@@ -8,9 +8,12 @@ use iced::{
 
 use crate::frontend::main_app::PHOTO_ASPECT_RATIO;
 
-use super::LENGTH_DIVISOR;
+use super::Speed;
 
-pub const ANIMATION_LENGTH: u64 = 3000 / LENGTH_DIVISOR;
+/// In milliseconds at normal speed.
+pub const ANIMATION_LENGTH: u64 = 3000;
+/// Just long enough to see each photo when they're taken a second apart.
+pub const RAPID_ANIMATION_LENGTH: u64 = 800;
 
 #[derive(Debug, Clone, Copy, Animatable)]
 pub struct AnimationState {
@@ -21,7 +24,15 @@ pub struct AnimationState {
     background_opacity: f32,
 }
 
-pub fn animation() -> impl anim::Animation<Item = AnimationState> {
+pub fn animation(speed: Speed) -> impl anim::Animation<Item = AnimationState> {
+    animation_lasting(speed.length(ANIMATION_LENGTH))
+}
+
+pub fn rapid_animation(speed: Speed) -> impl anim::Animation<Item = AnimationState> {
+    animation_lasting(speed.length(RAPID_ANIMATION_LENGTH))
+}
+
+fn animation_lasting(length: Duration) -> impl anim::Animation<Item = AnimationState> {
     anim::builder::key_frames([
         anim::KeyFrame::new(AnimationState {
             opacity: 0.0,
@@ -56,7 +67,7 @@ pub fn animation() -> impl anim::Animation<Item = AnimationState> {
             background_opacity: 0.0,
         })
         .easing(easing::cubic_ease().mode(easing::EasingMode::In))
-        .by_duration(Duration::from_millis(ANIMATION_LENGTH)),
+        .by_duration(length),
     ])
 }
 
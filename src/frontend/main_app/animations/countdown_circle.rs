@@ -1,14 +1,13 @@
-use std::time::Duration;
-
 use anim::{easing, Animatable};
 use iced::{
     widget::{container, text, Container},
     Border, Length,
 };
 
-use super::LENGTH_DIVISOR;
+use super::Speed;
 
-pub const ANIMATION_LENGTH: u64 = 1000 / LENGTH_DIVISOR;
+/// In milliseconds at normal speed.
+pub const ANIMATION_LENGTH: u64 = 1000;
 
 #[derive(Debug, Clone, Copy, Animatable)]
 pub struct AnimationState {
@@ -19,7 +18,7 @@ pub struct AnimationState {
 const MIN_TEXT_SIZE: f32 = f32::MIN_POSITIVE;
 const TEXT_SIZE: f32 = 60.0;
 
-pub fn animation() -> impl anim::Animation<Item = AnimationState> {
+pub fn animation(speed: Speed) -> impl anim::Animation<Item = AnimationState> {
     anim::builder::key_frames([
         anim::KeyFrame::new(AnimationState {
             opacity: 0.0,
@@ -38,11 +37,11 @@ pub fn animation() -> impl anim::Animation<Item = AnimationState> {
         })
         .by_percent(0.8),
         anim::KeyFrame::new(AnimationState {
-            opacity: 9.0,
+            opacity: 0.0,
             text_size: MIN_TEXT_SIZE,
         })
         .easing(easing::cubic_ease().mode(easing::EasingMode::In))
-        .by_duration(Duration::from_millis(ANIMATION_LENGTH)),
+        .by_duration(speed.length(ANIMATION_LENGTH)),
     ])
 }
 
@@ -80,3 +79,39 @@ pub fn view<Message: 'static>(
     )
     .center(Length::Fill)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anim::Animation;
+
+    use super::*;
+
+    #[test]
+    fn fades_out_just_before_the_end() {
+        for speed in [Speed::Normal, Speed::Fast] {
+            let length = speed.length(ANIMATION_LENGTH);
+            // An idle timeline reports the value at the start of its animation.
+            let opacity = |elapsed: Duration| {
+                anim::Timeline::new(animation(speed).skip(elapsed))
+                    .value()
+                    .opacity
+            };
+
+            assert!((opacity(length / 2) - 1.0).abs() < 1e-3);
+            let fading: Vec<f32> = [0.85, 0.9, 0.95, 0.999]
+                .into_iter()
+                .map(|fraction| opacity(length.mul_f64(fraction)))
+                .collect();
+            assert!(
+                fading.windows(2).all(|pair| pair[1] < pair[0]),
+                "{:?} at {:?}",
+                fading,
+                speed
+            );
+            assert!(fading[3] < 0.05, "{} at {:?}", fading[3], speed);
+            assert!(opacity(length).abs() < 1e-3);
+        }
+    }
+}
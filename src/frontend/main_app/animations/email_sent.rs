@@ -0,0 +1,151 @@
+use anim::{easing, Animatable};
+use iced::{
+    mouse,
+    widget::{canvas, column, container, text, Container},
+    Length, Point, Rectangle, Renderer, Theme,
+};
+
+use super::Speed;
+
+/// In milliseconds at normal speed.
+pub const ANIMATION_LENGTH: u64 = 2500;
+
+const CHECKMARK_SIZE: f32 = 160.0;
+const TEXT_SIZE: f32 = 40.0;
+/// The checkmark's corners relative to the middle of the circle, as
+/// fractions of its diameter.
+const CHECKMARK_POINTS: [(f32, f32); 3] = [(-0.22, 0.02), (-0.06, 0.18), (0.24, -0.14)];
+
+#[derive(Debug, Clone, Copy, Animatable)]
+pub struct AnimationState {
+    opacity: f32,
+    circle_scale: f32,
+    /// How much of the checkmark has been drawn, from 0 to 1.
+    check_progress: f32,
+}
+
+pub fn animation(speed: Speed) -> impl anim::Animation<Item = AnimationState> {
+    anim::builder::key_frames([
+        anim::KeyFrame::new(AnimationState {
+            opacity: 1.0,
+            circle_scale: 0.0,
+            check_progress: 0.0,
+        })
+        .by_percent(0.0),
+        anim::KeyFrame::new(AnimationState {
+            opacity: 1.0,
+            circle_scale: 1.0,
+            check_progress: 0.0,
+        })
+        .easing(easing::cubic_ease().mode(easing::EasingMode::Out))
+        .by_percent(0.2),
+        anim::KeyFrame::new(AnimationState {
+            opacity: 1.0,
+            circle_scale: 1.0,
+            check_progress: 1.0,
+        })
+        .easing(easing::cubic_ease().mode(easing::EasingMode::InOut))
+        .by_percent(0.45),
+        anim::KeyFrame::new(AnimationState {
+            opacity: 1.0,
+            circle_scale: 1.0,
+            check_progress: 1.0,
+        })
+        .by_percent(0.85),
+        anim::KeyFrame::new(AnimationState {
+            opacity: 0.0,
+            circle_scale: 1.0,
+            check_progress: 1.0,
+        })
+        .easing(easing::cubic_ease().mode(easing::EasingMode::In))
+        .by_duration(speed.length(ANIMATION_LENGTH)),
+    ])
+}
+
+pub fn view<Message: 'static>(animation_state: AnimationState) -> Container<'static, Message> {
+    container(
+        column([
+            canvas(Checkmark(animation_state))
+                .width(CHECKMARK_SIZE)
+                .height(CHECKMARK_SIZE)
+                .into(),
+            text("Thanks! Check your inbox 🎉")
+                .size(TEXT_SIZE)
+                .style(move |theme: &Theme| text::Style {
+                    color: Some(
+                        theme
+                            .extended_palette()
+                            .background
+                            .base
+                            .text
+                            .scale_alpha(animation_state.opacity),
+                    ),
+                })
+                .into(),
+        ])
+        .align_x(iced::Alignment::Center)
+        .spacing(24),
+    )
+    .center(Length::Fill)
+}
+
+/// A circle with a checkmark drawn into it as the animation goes.
+struct Checkmark(AnimationState);
+
+impl<Message> canvas::Program<Message> for Checkmark {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let palette = theme.extended_palette().success.base;
+        let center = frame.center();
+        let diameter = bounds.width.min(bounds.height);
+
+        frame.fill(
+            &canvas::Path::circle(center, diameter / 2.0 * self.0.circle_scale),
+            palette.color.scale_alpha(self.0.opacity),
+        );
+
+        let [start, corner, end] = CHECKMARK_POINTS
+            .map(|(x, y)| Point::new(center.x + x * diameter, center.y + y * diameter));
+        // both strokes are drawn at the same speed, so the short one is
+        // done first
+        let first = start.distance(corner);
+        let drawn = (first + corner.distance(end)) * self.0.check_progress;
+        if drawn > 0.0 {
+            let check = canvas::Path::new(|path| {
+                path.move_to(start);
+                if drawn <= first {
+                    path.line_to(lerp(start, corner, drawn / first));
+                } else {
+                    path.line_to(corner);
+                    path.line_to(lerp(corner, end, (drawn - first) / corner.distance(end)));
+                }
+            });
+            frame.stroke(
+                &check,
+                canvas::Stroke::default()
+                    .with_color(palette.text.scale_alpha(self.0.opacity))
+                    .with_width(diameter * 0.08)
+                    .with_line_cap(canvas::LineCap::Round)
+                    .with_line_join(canvas::LineJoin::Round),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+fn lerp(from: Point, to: Point, amount: f32) -> Point {
+    Point::new(
+        from.x + (to.x - from.x) * amount,
+        from.y + (to.y - from.y) * amount,
+    )
+}
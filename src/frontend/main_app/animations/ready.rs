@@ -1,14 +1,13 @@
-use std::time::Duration;
-
 use anim::{easing, Animatable};
 use iced::{
     widget::{column, container, text, vertical_space, Container},
     Border, Length,
 };
 
-use super::LENGTH_DIVISOR;
+use super::Speed;
 
-pub const ANIMATION_LENGTH: u64 = 3000 / LENGTH_DIVISOR;
+/// In milliseconds at normal speed.
+pub const ANIMATION_LENGTH: u64 = 3000;
 
 #[derive(Debug, Clone, Copy, Animatable)]
 pub struct AnimationState {
@@ -19,7 +18,7 @@ pub struct AnimationState {
 
 const TEXT_SIZE: f32 = 60.0;
 
-pub fn animation() -> impl anim::Animation<Item = AnimationState> {
+pub fn animation(speed: Speed) -> impl anim::Animation<Item = AnimationState> {
     anim::builder::key_frames([
         anim::KeyFrame::new(AnimationState {
             opacity: 0.0,
@@ -46,7 +45,7 @@ pub fn animation() -> impl anim::Animation<Item = AnimationState> {
             offset: 200.0,
         })
         .easing(easing::cubic_ease().mode(easing::EasingMode::In))
-        .by_duration(Duration::from_millis(ANIMATION_LENGTH)),
+        .by_duration(speed.length(ANIMATION_LENGTH)),
     ])
 }
 
@@ -1,5 +1,3 @@
-use std::time::Duration;
-
 use anim::{easing, Animatable};
 use iced::{
     widget::{column, container, image, image::Handle, responsive, vertical_space, Container},
@@ -8,9 +6,10 @@ use iced::{
 
 use crate::frontend::main_app::PHOTO_ASPECT_RATIO;
 
-use super::LENGTH_DIVISOR;
+use super::Speed;
 
-pub const ANIMATION_LENGTH: u64 = 4000 / LENGTH_DIVISOR;
+/// In milliseconds at normal speed.
+pub const ANIMATION_LENGTH: u64 = 4000;
 
 #[derive(Debug, Clone, Copy, Animatable)]
 pub struct AnimationState {
@@ -20,7 +19,7 @@ pub struct AnimationState {
     background_opacity: f32,
 }
 
-pub fn animation() -> impl anim::Animation<Item = AnimationState> {
+pub fn animation(speed: Speed) -> impl anim::Animation<Item = AnimationState> {
     anim::builder::key_frames([
         anim::KeyFrame::new(AnimationState {
             opacity: 0.0,
@@ -51,7 +50,7 @@ pub fn animation() -> impl anim::Animation<Item = AnimationState> {
             background_opacity: 0.0,
         })
         .easing(easing::cubic_ease().mode(easing::EasingMode::In))
-        .by_duration(Duration::from_millis(ANIMATION_LENGTH)),
+        .by_duration(speed.length(ANIMATION_LENGTH)),
     ])
 }
 
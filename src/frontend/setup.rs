@@ -1,24 +1,154 @@
+use std::time::{Duration, Instant};
+
 use iced::{
-    widget::{button, column, container, pick_list, text},
-    Alignment, Element, Length, Task,
+    widget::{
+        button, checkbox, column, container, pick_list, row, slider, text, text_input, Space,
+    },
+    Alignment, ContentFit, Element, Length, Task,
+};
+
+use crate::{
+    backend::{
+        cameras::{
+            CameraBackendCamera, CameraControl, CameraFormat, CameraOpenFailure, ControlId,
+            ControlValue,
+        },
+        config,
+        filters::ColorFilter,
+        render_take::{self, StripTemplate},
+    },
+    AppPage, PhotoBoothMessage,
 };
 
-use crate::{AppPage, MainAppMessage, PhotoBoothMessage};
+use super::{
+    camera_feed::{CameraFeed, CameraFeedOptions, CameraMessage, Rotation, DEFAULT_TARGET_FPS},
+    loading_spinners,
+    main_app::{CaptureMode, CaptureSettings, MainApp},
+};
 
-use super::{camera_feed::CameraFeed, main_app::MainApp};
+/// How long staff have to cancel starting with the saved setup.
+const AUTO_START_GRACE: Duration = Duration::from_secs(5);
+/// Set to start with the only connected camera when nothing was saved, for
+/// single-camera kiosks.
+const AUTO_START_VAR: &str = "AUTO_START";
+const MIN_ZOOM: f32 = 1.0;
+const MAX_ZOOM: f32 = 3.0;
+const MAX_WARMUP_FRAMES: u32 = 30;
+const MIN_COUNTDOWN: u32 = 1;
+const MAX_COUNTDOWN: u32 = 5;
+/// Seconds; DSLRs writing to a slow card can take a few.
+const MIN_STILL_TIMEOUT: f32 = 2.0;
+const MAX_STILL_TIMEOUT: f32 = 30.0;
+const MAX_CLIPPING_WARNING: f32 = 0.5;
+/// Seconds.
+const MIN_IDLE_TIMEOUT: f32 = 15.0;
+const MAX_IDLE_TIMEOUT: f32 = 300.0;
+const EXPOSURE_RANGE: f32 = 2.0;
+const MIN_WHITE_BALANCE: u32 = 2500;
+const MAX_WHITE_BALANCE: u32 = 7500;
+/// The preview only has to show which camera is which.
+const PREVIEW_FPS: f32 = 10.0;
+const PREVIEW_WIDTH: f32 = 320.0;
+/// Preview frames are shrunk to this before they're uploaded, since they're
+/// shown small.
+const PREVIEW_MAX_DIMENSION: u32 = 320;
+
+/// Named so deriving on `SetupMessage` doesn't ask for the camera itself to
+/// be `Clone` and `Debug`, which the feed doesn't need.
+type PreviewFeed<C> = CameraFeed<<C as crate::backend::cameras::CameraBackend>::Camera>;
 
 #[derive(Debug, Clone)]
 pub enum SetupMessage<C: crate::backend::cameras::CameraBackend + 'static> {
     CameraSelected(C::EnumeratedCamera),
+    SecondaryCameraSelected(C::EnumeratedCamera),
+    SecondaryCameraCleared,
+    FormatSelected(CameraFormat),
+    ColorFilterSelected(ColorFilter),
+    RotationSelected(Rotation),
+    CaptureModeSelected(CaptureMode),
+    CountdownChanged(u32),
+    ZoomChanged(f32),
+    WarmupFramesChanged(u32),
+    StillTimeoutChanged(f32),
+    ClippingWarningChanged(f32),
+    IdleTimeoutChanged(f32),
+    MirrorPreviewToggled(bool),
+    MirrorPhotosToggled(bool),
+    FastAnimationsToggled(bool),
+    FullscreenToggled(bool),
+    SoundEffectsToggled(bool),
+    OfflineModeToggled(bool),
+    LockExposureToggled(bool),
+    LockFocusToggled(bool),
+    ExposureChanged(f32),
+    WhiteBalanceChanged(u32),
+    EventNameChanged(String),
+    TemplateSelected(StripTemplate),
+    AdvancedToggled,
+    /// The selected camera was opened for the preview, along with the
+    /// controls it supports. Ignored unless the number matches the latest
+    /// call to `open_preview`.
+    PreviewOpened(
+        u64,
+        Result<(PreviewFeed<C>, Vec<CameraControl>), CameraOpenFailure>,
+    ),
+    /// Opens the selected camera again after it failed to open.
+    RetryOpen,
+    Preview(CameraMessage),
+    ControlChanged(ControlId, ControlValue),
+    /// Checks whether the saved setup's grace period is over.
+    Tick,
+    /// Any key press, which cancels starting with the saved setup.
+    KeyPressed,
+    RefreshCameras,
+    CamerasRefreshed(Result<Vec<C::EnumeratedCamera>, String>),
     StartPressed,
+    /// The camera and the secondary camera, if it opened, ready for the
+    /// booth.
+    Started(PreviewFeed<C>, Option<PreviewFeed<C>>),
 }
 
 pub struct Setup<
     C: crate::backend::cameras::CameraBackend + 'static,
     S: crate::backend::servers::ServerBackend + 'static,
 > {
-    camera_options: Vec<C::EnumeratedCamera>,
+    camera_options: Result<Vec<C::EnumeratedCamera>, String>,
+    /// Whether `RefreshCameras` is waiting on `enumerate_cameras`.
+    refreshing: bool,
     camera_option: Option<C::EnumeratedCamera>,
+    secondary_camera_option: Option<C::EnumeratedCamera>,
+    format_options: Vec<CameraFormat>,
+    format_option: Option<CameraFormat>,
+    settings: CaptureSettings,
+    /// Printed on the strip. Blank prints the date instead.
+    event_name: String,
+    /// Loaded once at startup. Never empty.
+    templates: Vec<StripTemplate>,
+    template: StripTemplate,
+    /// Whether the booth goes fullscreen when started.
+    fullscreen: bool,
+    advanced_open: bool,
+    /// Controls the selected camera supports.
+    controls: Vec<CameraControl>,
+    /// Controls changed from the camera's own values.
+    control_values: Vec<(ControlId, ControlValue)>,
+    /// The selected camera, already opened the way Start would open it, so
+    /// it can be handed to the booth as is.
+    preview: Option<CameraFeed<C::Camera>>,
+    /// Bumped whenever the preview is opened or closed, so a camera that
+    /// finishes opening after the selection changed is dropped.
+    preview_generation: u64,
+    /// Whether the latest `open_preview` is still opening the camera.
+    opening: bool,
+    /// Set once Start is pressed, until the booth takes over or the camera
+    /// fails to open. The camera is opened in the background, so setup waits
+    /// on it.
+    starting: bool,
+    /// Why the selected camera couldn't be opened last time it was tried.
+    open_error: Option<CameraOpenFailure>,
+    /// When the booth starts by itself with the saved setup, unless a key is
+    /// pressed first.
+    auto_start: Option<Instant>,
     pub new_page: Option<Box<(AppPage<C, S>, Task<PhotoBoothMessage<C, S>>)>>,
 }
 
@@ -27,38 +157,506 @@ impl<
         S: crate::backend::servers::ServerBackend + 'static,
     > Setup<C, S>
 {
-    pub fn new() -> Self {
-        Self {
-            camera_options: C::enumerate_cameras().unwrap(),
+    /// Creates the setup page. Unless `force_setup` is set, the saved setup
+    /// is restored and started after a grace period if its camera is found.
+    pub fn new(force_setup: bool) -> Self {
+        let templates = render_take::load_templates();
+        let mut setup = Self {
+            camera_options: C::enumerate_cameras().map_err(|err| format!("{:?}", err)),
+            refreshing: false,
             camera_option: None,
+            secondary_camera_option: None,
+            format_options: Vec::new(),
+            format_option: None,
+            settings: Default::default(),
+            event_name: String::new(),
+            template: templates[0].clone(),
+            templates,
+            fullscreen: true,
+            advanced_open: false,
+            controls: Vec::new(),
+            control_values: Vec::new(),
+            preview: None,
+            preview_generation: 0,
+            opening: false,
+            starting: false,
+            open_error: None,
+            auto_start: None,
             new_page: None,
+        };
+        if !force_setup {
+            if let Some(saved) = config::load() {
+                setup.restore(saved);
+            }
+            if setup.auto_start.is_none() && auto_start_enabled() {
+                setup.start_with_only_camera();
+            }
+        }
+        setup
+    }
+
+    /// Starts after the grace period with the default settings if exactly one
+    /// camera is connected.
+    fn start_with_only_camera(&mut self) {
+        let camera = match self.camera_options.as_deref() {
+            Ok([camera]) => camera.clone(),
+            Ok(cameras) => {
+                log::info!(
+                    "Not starting automatically with {} cameras connected",
+                    cameras.len()
+                );
+                return;
+            }
+            Err(_) => return,
+        };
+        log::info!("Starting with the only camera, {}", camera);
+        self.format_options = C::supported_formats(&camera).unwrap_or_default();
+        self.camera_option = Some(camera);
+        self.auto_start = Some(Instant::now() + AUTO_START_GRACE);
+    }
+
+    fn find_camera(&self, name: &str) -> Option<C::EnumeratedCamera> {
+        self.camera_options
+            .as_ref()
+            .ok()?
+            .iter()
+            .find(|camera| camera.to_string() == name)
+            .cloned()
+    }
+
+    fn restore(&mut self, saved: config::SavedSetup) {
+        let Some(camera) = self.find_camera(&saved.camera) else {
+            log::info!("Saved camera {} isn't connected", saved.camera);
+            return;
+        };
+        log::info!("Starting with saved camera {}", camera);
+        self.format_options = C::supported_formats(&camera).unwrap_or_default();
+        self.format_option = saved
+            .format
+            .filter(|format| self.format_options.contains(format));
+        self.camera_option = Some(camera);
+        self.secondary_camera_option = saved
+            .secondary_camera
+            .and_then(|name| self.find_camera(&name));
+        self.settings = saved.settings;
+        self.event_name = saved.event_name;
+        if let Some(template) = saved.template.and_then(|name| {
+            self.templates
+                .iter()
+                .find(|template| template.name() == name)
+                .cloned()
+        }) {
+            self.template = template;
+        }
+        self.control_values = saved.controls;
+        self.fullscreen = saved.fullscreen;
+        self.auto_start = Some(Instant::now() + AUTO_START_GRACE);
+    }
+
+    /// Closes the current preview and opens the selected camera in the
+    /// background. The old camera is closed first, since most devices can't
+    /// be opened twice.
+    fn open_preview(&mut self) -> Task<SetupMessage<C>> {
+        self.preview_generation += 1;
+        let generation = self.preview_generation;
+        let old = self.preview.take();
+        let Some(camera) = self.camera_option.clone() else {
+            self.opening = false;
+            return Task::none();
+        };
+        self.opening = true;
+        let format = self.format_option;
+        let settings = self.settings;
+        let controls = self.control_values.clone();
+        Task::perform(
+            async move {
+                if let Some(old) = old {
+                    old.closed().await;
+                }
+                tokio::task::spawn_blocking(move || {
+                    let mut opened =
+                        open_configured::<C>(camera.clone(), format, settings, &controls).map_err(
+                            |err| {
+                                log::error!("Couldn't open {}: {:?}", camera, err);
+                                C::classify_open_error(&err)
+                            },
+                        )?;
+                    let supported = opened.controls();
+                    let mut feed = CameraFeed::new(opened, Default::default());
+                    feed.set_target_fps(PREVIEW_FPS);
+                    Ok((feed, supported))
+                })
+                .await
+                .unwrap_or_else(|err| Err(CameraOpenFailure::Other(err.to_string())))
+            },
+            move |result| SetupMessage::PreviewOpened(generation, result),
+        )
+    }
+
+    fn close_preview(&mut self) {
+        self.preview_generation += 1;
+        self.opening = false;
+        self.preview = None;
+    }
+
+    /// Hands `feed` to the booth once the secondary camera is opened, which
+    /// happens on a blocking thread like the preview's.
+    fn start(&mut self, mut feed: PreviewFeed<C>) -> Task<SetupMessage<C>> {
+        let Some(camera) = self.camera_option.clone() else {
+            self.starting = false;
+            return Task::none();
+        };
+        feed.update_options(Default::default());
+        feed.set_target_fps(DEFAULT_TARGET_FPS);
+        let feed = feed.with_reopen(reopen::<C>(
+            camera,
+            self.format_option,
+            self.settings,
+            self.control_values.clone(),
+        ));
+        let secondary_camera = self.secondary_camera_option.clone();
+        Task::perform(
+            async move {
+                let Some(camera) = secondary_camera else {
+                    return (feed, None);
+                };
+                // the booth works fine without the secondary camera
+                let secondary_feed = tokio::task::spawn_blocking(move || {
+                    match C::open_camera(camera.clone(), None) {
+                        Ok(opened) => Some(
+                            CameraFeed::new(opened, Default::default()).with_reopen(move || {
+                                C::open_camera(camera.clone(), None)
+                                    .map_err(|err| format!("{:?}", err))
+                            }),
+                        ),
+                        Err(err) => {
+                            log::warn!("Failed to open secondary camera {}: {:?}", camera, err);
+                            None
+                        }
+                    }
+                })
+                .await
+                .unwrap_or_else(|err| {
+                    log::warn!("Opening the secondary camera panicked: {}", err);
+                    None
+                });
+                (feed, secondary_feed)
+            },
+            |(feed, secondary_feed)| SetupMessage::Started(feed, secondary_feed),
+        )
+    }
+
+    /// Applies changed camera-side settings to the preview camera.
+    fn configure_preview(&self) -> Task<SetupMessage<C>> {
+        let Some(feed) = &self.preview else {
+            return Task::none();
+        };
+        let settings = self.settings;
+        let controls = self.control_values.clone();
+        Task::future(feed.with_camera(move |camera| configure::<C>(camera, settings, &controls)))
+            .discard()
+    }
+
+    pub fn subscription(&self) -> iced::Subscription<SetupMessage<C>> {
+        match &self.preview {
+            Some(feed) => feed.subscription().map(SetupMessage::Preview),
+            None => iced::Subscription::none(),
         }
     }
 
     pub fn update(&mut self, message: SetupMessage<C>) -> Task<SetupMessage<C>> {
+        if let Some(feed) = &mut self.preview {
+            feed.update_options(CameraFeedOptions {
+                mirror: self.settings.mirror_preview,
+                max_dimension: Some(PREVIEW_MAX_DIMENSION),
+                filter: self.settings.color_filter,
+                rotation: self.settings.rotation,
+                zoom: self.settings.zoom,
+                center: self.settings.center,
+                ..Default::default()
+            });
+        }
+
         match message {
             SetupMessage::CameraSelected(new) => {
+                self.format_options = C::supported_formats(&new).unwrap_or_else(|err| {
+                    log::warn!("Failed to list formats for {}: {:?}", new, err);
+                    Vec::new()
+                });
+                self.format_option = None;
+                // ranges differ between cameras
+                self.controls.clear();
+                self.control_values.clear();
                 self.camera_option = Some(new);
+                self.open_preview()
+            }
+            SetupMessage::SecondaryCameraSelected(camera) => {
+                self.secondary_camera_option = Some(camera);
+                Task::none()
+            }
+            SetupMessage::SecondaryCameraCleared => {
+                self.secondary_camera_option = None;
+                Task::none()
+            }
+            SetupMessage::FormatSelected(format) => {
+                self.format_option = Some(format);
+                self.open_preview()
+            }
+            SetupMessage::ColorFilterSelected(filter) => {
+                self.settings.color_filter = filter;
+                Task::none()
+            }
+            SetupMessage::RotationSelected(rotation) => {
+                self.settings.rotation = rotation;
+                Task::none()
+            }
+            SetupMessage::CaptureModeSelected(mode) => {
+                self.settings.capture_mode = mode;
+                Task::none()
+            }
+            SetupMessage::CountdownChanged(from) => {
+                self.settings.countdown_from = from;
+                Task::none()
+            }
+            SetupMessage::ZoomChanged(zoom) => {
+                self.settings.zoom = zoom.max(1.0);
+                Task::none()
+            }
+            SetupMessage::WarmupFramesChanged(frames) => {
+                self.settings.warmup_frames = frames;
+                self.configure_preview()
+            }
+            SetupMessage::StillTimeoutChanged(timeout) => {
+                self.settings.still_timeout = timeout;
+                Task::none()
+            }
+            SetupMessage::ClippingWarningChanged(fraction) => {
+                self.settings.clipping_warning = fraction;
+                Task::none()
+            }
+            SetupMessage::IdleTimeoutChanged(timeout) => {
+                self.settings.idle_timeout = timeout;
+                Task::none()
+            }
+            SetupMessage::MirrorPreviewToggled(mirror) => {
+                self.settings.mirror_preview = mirror;
+                Task::none()
+            }
+            SetupMessage::MirrorPhotosToggled(mirror) => {
+                self.settings.mirror_photos = mirror;
+                Task::none()
+            }
+            SetupMessage::FastAnimationsToggled(fast) => {
+                self.settings.fast_animations = fast;
+                Task::none()
+            }
+            SetupMessage::FullscreenToggled(fullscreen) => {
+                self.fullscreen = fullscreen;
+                Task::none()
+            }
+            SetupMessage::OfflineModeToggled(offline_mode) => {
+                self.settings.offline_mode = offline_mode;
+                Task::none()
+            }
+            SetupMessage::SoundEffectsToggled(sound_effects) => {
+                self.settings.sound_effects = sound_effects;
+                Task::none()
+            }
+            SetupMessage::LockExposureToggled(lock_exposure) => {
+                self.settings.lock_exposure = lock_exposure;
+                // unlocking can only be undone by reopening the camera
+                self.open_preview()
+            }
+            SetupMessage::LockFocusToggled(lock_focus) => {
+                self.settings.lock_focus = lock_focus;
+                Task::none()
+            }
+            SetupMessage::ExposureChanged(exposure) => {
+                self.settings.exposure = exposure;
+                self.configure_preview()
+            }
+            SetupMessage::WhiteBalanceChanged(white_balance) => {
+                self.settings.white_balance = white_balance;
+                self.configure_preview()
+            }
+            SetupMessage::EventNameChanged(event_name) => {
+                self.event_name = event_name;
+                Task::none()
+            }
+            SetupMessage::TemplateSelected(template) => {
+                self.template = template;
+                Task::none()
+            }
+            SetupMessage::AdvancedToggled => {
+                self.advanced_open = !self.advanced_open;
+                Task::none()
+            }
+            SetupMessage::PreviewOpened(generation, result) => {
+                // a stale camera is dropped here, which closes it
+                if generation == self.preview_generation {
+                    self.opening = false;
+                    match result {
+                        Ok((feed, controls)) => {
+                            self.controls = controls;
+                            self.open_error = None;
+                            if self.starting {
+                                return self.start(feed);
+                            }
+                            self.preview = Some(feed);
+                        }
+                        Err(err) => {
+                            self.open_error = Some(err);
+                            self.starting = false;
+                        }
+                    }
+                }
+                Task::none()
+            }
+            SetupMessage::RetryOpen => {
+                self.open_error = None;
+                self.open_preview()
+            }
+            SetupMessage::Preview(msg) => {
+                if let Some(feed) = &mut self.preview {
+                    feed.update(msg);
+                }
+                Task::none()
+            }
+            SetupMessage::ControlChanged(id, value) => {
+                self.control_values.retain(|(set, _)| *set != id);
+                self.control_values.push((id, value));
+                self.configure_preview()
+            }
+            SetupMessage::Tick => match self.auto_start {
+                Some(deadline) if Instant::now() >= deadline => {
+                    self.auto_start = None;
+                    Task::done(SetupMessage::StartPressed)
+                }
+                _ => Task::none(),
+            },
+            SetupMessage::KeyPressed => {
+                if self.auto_start.take().is_some() {
+                    log::info!("Automatic start cancelled");
+                    // the saved camera wasn't opened while it might start
+                    return self.open_preview();
+                }
+                Task::none()
+            }
+            SetupMessage::RefreshCameras => {
+                self.refreshing = true;
+                Task::perform(
+                    async {
+                        tokio::task::spawn_blocking(|| {
+                            C::enumerate_cameras().map_err(|err| format!("{:?}", err))
+                        })
+                        .await
+                        .unwrap_or_else(|err| Err(err.to_string()))
+                    },
+                    SetupMessage::CamerasRefreshed,
+                )
+            }
+            SetupMessage::CamerasRefreshed(result) => {
+                self.refreshing = false;
+                if let Ok(cameras) = &result {
+                    // forget cameras that have been unplugged
+                    if self
+                        .camera_option
+                        .as_ref()
+                        .is_some_and(|camera| !cameras.contains(camera))
+                    {
+                        self.camera_option = None;
+                        self.format_options.clear();
+                        self.format_option = None;
+                        self.controls.clear();
+                        self.control_values.clear();
+                        self.close_preview();
+                    }
+                    if self
+                        .secondary_camera_option
+                        .as_ref()
+                        .is_some_and(|camera| !cameras.contains(camera))
+                    {
+                        self.secondary_camera_option = None;
+                    }
+                }
+                self.camera_options = result;
                 Task::none()
             }
             SetupMessage::StartPressed => {
-                let (feed, task) = CameraFeed::new(
-                    C::open_camera(self.camera_option.clone().unwrap()).unwrap(),
-                    Default::default(),
+                if self.starting {
+                    return Task::none();
+                }
+                if self.camera_option.is_none() {
+                    log::warn!("Start pressed without a camera selected");
+                    return Task::none();
+                }
+                self.starting = true;
+                // reuse the preview's camera, since it can't be opened twice
+                if let Some(feed) = self.preview.take() {
+                    self.close_preview();
+                    return self.start(feed);
+                }
+                if self.opening {
+                    // the camera it's opening is used once it's open
+                    return Task::none();
+                }
+                self.open_preview()
+            }
+            SetupMessage::Started(feed, secondary_feed) => {
+                self.starting = false;
+                let Some(camera) = &self.camera_option else {
+                    return Task::none();
+                };
+                let camera_name = camera.to_string();
+                config::save(&config::SavedSetup {
+                    camera: camera_name,
+                    secondary_camera: self
+                        .secondary_camera_option
+                        .as_ref()
+                        .map(ToString::to_string),
+                    format: self.format_option,
+                    settings: self.settings,
+                    event_name: self.event_name.clone(),
+                    template: Some(self.template.name().to_string()),
+                    controls: self.control_values.clone(),
+                    fullscreen: self.fullscreen,
+                });
+                // the guests choose after the photos are taken, so only
+                // templates with room for the same number of them are offered
+                let templates = std::iter::once(self.template.clone())
+                    .chain(
+                        self.templates
+                            .iter()
+                            .filter(|template| {
+                                **template != self.template
+                                    && template.layout.photo_count()
+                                        == self.template.layout.photo_count()
+                            })
+                            .cloned(),
+                    )
+                    .collect();
+                let (app, app_task) = MainApp::new(
+                    feed,
+                    secondary_feed,
+                    self.settings,
+                    self.event_name.trim().to_string(),
+                    templates,
                 );
-                let (app, app_task) = MainApp::new(feed);
                 self.new_page = Some(Box::new((
                     AppPage::MainApp(app),
-                    Task::batch([
-                        task.map(MainAppMessage::Camera)
-                            .map(PhotoBoothMessage::MainApp),
-                        app_task.map(PhotoBoothMessage::MainApp),
-                    ]),
+                    app_task.map(PhotoBoothMessage::MainApp),
                 )));
+                if !self.fullscreen {
+                    return Task::none();
+                }
                 iced::window::get_latest().then(|id| {
+                    let Some(id) = id else {
+                        log::warn!("No window to make fullscreen");
+                        return Task::none();
+                    };
                     iced::Task::batch([
-                        iced::window::change_mode(id.unwrap(), iced::window::Mode::Fullscreen),
-                        iced::window::toggle_decorations(id.unwrap()),
+                        iced::window::change_mode(id, iced::window::Mode::Fullscreen),
+                        iced::window::toggle_decorations(id),
                     ])
                 })
             }
@@ -66,16 +664,327 @@ impl<
     }
 
     pub fn view(&self) -> Element<SetupMessage<C>> {
+        if let (Some(deadline), Some(camera)) = (self.auto_start, &self.camera_option) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            return container(
+                container(
+                    column([
+                        text(format!("Starting with saved camera {}…", camera))
+                            .size(24)
+                            .into(),
+                        text(format!(
+                            "Press any key within {} seconds to change the setup.",
+                            remaining.as_secs() + 1
+                        ))
+                        .into(),
+                    ])
+                    .align_x(Alignment::Center)
+                    .spacing(8),
+                )
+                .padding(8)
+                .style(container::rounded_box),
+            )
+            .center_x(Length::Fill)
+            .center_y(Length::Fill)
+            .into();
+        }
+        if let (true, Some(camera)) = (self.starting, &self.camera_option) {
+            return container(
+                container(
+                    row([
+                        loading_spinners::Circular::new()
+                            .size(24.0)
+                            .bar_height(3.0)
+                            .easing(&loading_spinners::easing::STANDARD)
+                            .into(),
+                        text(format!("Starting with {}…", camera)).size(24).into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8),
+                )
+                .padding(8)
+                .style(container::rounded_box),
+            )
+            .center_x(Length::Fill)
+            .center_y(Length::Fill)
+            .into();
+        }
+        let cameras = self.camera_options.as_deref().unwrap_or(&[]);
         container(
             container(
                 column([
                     text("Setup").size(32).into(),
+                    match &self.camera_options {
+                        Ok(cameras) if cameras.is_empty() => error_card(
+                            "No camera detected. Plug one in and press Refresh.".to_string(),
+                        ),
+                        Ok(_) => Space::new(0, 0).into(),
+                        Err(err) => error_card(format!("Couldn't list cameras: {}", err)),
+                    },
+                    row([
+                        pick_list(
+                            cameras,
+                            self.camera_option.as_ref(),
+                            SetupMessage::CameraSelected,
+                        )
+                        .placeholder("Choose a camera")
+                        .into(),
+                        if self.refreshing {
+                            button(
+                                loading_spinners::Circular::new()
+                                    .size(16.0)
+                                    .bar_height(2.0)
+                                    .easing(&loading_spinners::easing::STANDARD),
+                            )
+                            .into()
+                        } else {
+                            button("Refresh")
+                                .on_press(SetupMessage::RefreshCameras)
+                                .into()
+                        },
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    row([
+                        pick_list(
+                            cameras,
+                            self.secondary_camera_option.as_ref(),
+                            SetupMessage::SecondaryCameraSelected,
+                        )
+                        .placeholder("No secondary camera")
+                        .into(),
+                        button("Clear")
+                            .on_press_maybe(
+                                self.secondary_camera_option
+                                    .is_some()
+                                    .then_some(SetupMessage::SecondaryCameraCleared),
+                            )
+                            .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    if self.format_options.is_empty() {
+                        Space::new(0, 0).into()
+                    } else {
+                        pick_list(
+                            self.format_options.as_slice(),
+                            self.format_option,
+                            SetupMessage::FormatSelected,
+                        )
+                        .placeholder("Automatic format")
+                        .into()
+                    },
                     pick_list(
-                        self.camera_options.as_ref(),
-                        self.camera_option.as_ref(),
-                        SetupMessage::CameraSelected,
+                        &ColorFilter::ALL[..],
+                        Some(self.settings.color_filter),
+                        SetupMessage::ColorFilterSelected,
                     )
                     .into(),
+                    pick_list(
+                        &Rotation::ALL[..],
+                        Some(self.settings.rotation),
+                        SetupMessage::RotationSelected,
+                    )
+                    .into(),
+                    pick_list(
+                        &CaptureMode::ALL[..],
+                        Some(self.settings.capture_mode),
+                        SetupMessage::CaptureModeSelected,
+                    )
+                    .into(),
+                    row([
+                        text(format!("Count down from {}", self.settings.countdown_from)).into(),
+                        slider(
+                            MIN_COUNTDOWN..=MAX_COUNTDOWN,
+                            self.settings.countdown_from,
+                            SetupMessage::CountdownChanged,
+                        )
+                        .width(200)
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    row([
+                        text(format!("Zoom {:.1}x", self.settings.zoom)).into(),
+                        slider(
+                            MIN_ZOOM..=MAX_ZOOM,
+                            self.settings.zoom,
+                            SetupMessage::ZoomChanged,
+                        )
+                        .step(0.1)
+                        .width(200)
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    row([
+                        text(format!(
+                            "Discard {} frames before stills",
+                            self.settings.warmup_frames
+                        ))
+                        .into(),
+                        slider(
+                            0..=MAX_WARMUP_FRAMES,
+                            self.settings.warmup_frames,
+                            SetupMessage::WarmupFramesChanged,
+                        )
+                        .width(200)
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    row([
+                        text(format!(
+                            "Retry stills after {:.0} s",
+                            self.settings.still_timeout
+                        ))
+                        .into(),
+                        slider(
+                            MIN_STILL_TIMEOUT..=MAX_STILL_TIMEOUT,
+                            self.settings.still_timeout,
+                            SetupMessage::StillTimeoutChanged,
+                        )
+                        .step(1.0)
+                        .width(200)
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    row([
+                        text(format!(
+                            "Warn when {:.0}% of the picture is blown out or black",
+                            self.settings.clipping_warning * 100.0
+                        ))
+                        .into(),
+                        slider(
+                            0.01..=MAX_CLIPPING_WARNING,
+                            self.settings.clipping_warning,
+                            SetupMessage::ClippingWarningChanged,
+                        )
+                        .step(0.01)
+                        .width(200)
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    row([
+                        text(format!(
+                            "Start over after {:.0} s without a key press",
+                            self.settings.idle_timeout
+                        ))
+                        .into(),
+                        slider(
+                            MIN_IDLE_TIMEOUT..=MAX_IDLE_TIMEOUT,
+                            self.settings.idle_timeout,
+                            SetupMessage::IdleTimeoutChanged,
+                        )
+                        .step(5.0)
+                        .width(200)
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(8)
+                    .into(),
+                    checkbox("Mirror preview", self.settings.mirror_preview)
+                        .on_toggle(SetupMessage::MirrorPreviewToggled)
+                        .into(),
+                    checkbox("Mirror captured photos", self.settings.mirror_photos)
+                        .on_toggle(SetupMessage::MirrorPhotosToggled)
+                        .into(),
+                    checkbox("Speed up animations", self.settings.fast_animations)
+                        .on_toggle(SetupMessage::FastAnimationsToggled)
+                        .into(),
+                    checkbox("Start fullscreen", self.fullscreen)
+                        .on_toggle(SetupMessage::FullscreenToggled)
+                        .into(),
+                    checkbox("Sound effects", self.settings.sound_effects)
+                        .on_toggle_maybe(
+                            cfg!(feature = "audio").then_some(SetupMessage::SoundEffectsToggled),
+                        )
+                        .into(),
+                    checkbox("Offline mode (upload later)", self.settings.offline_mode)
+                        .on_toggle(SetupMessage::OfflineModeToggled)
+                        .into(),
+                    checkbox(
+                        "Lock exposure and white balance",
+                        self.settings.lock_exposure,
+                    )
+                    .on_toggle(SetupMessage::LockExposureToggled)
+                    .into(),
+                    checkbox("Lock focus at capture start", self.settings.lock_focus)
+                        .on_toggle(SetupMessage::LockFocusToggled)
+                        .into(),
+                    if self.settings.lock_exposure {
+                        column([
+                            row([
+                                text(format!("Exposure {:+.1} EV", self.settings.exposure)).into(),
+                                slider(
+                                    -EXPOSURE_RANGE..=EXPOSURE_RANGE,
+                                    self.settings.exposure,
+                                    SetupMessage::ExposureChanged,
+                                )
+                                .step(0.1)
+                                .width(200)
+                                .into(),
+                            ])
+                            .align_y(Alignment::Center)
+                            .spacing(8)
+                            .into(),
+                            row([
+                                text(format!("White balance {}K", self.settings.white_balance))
+                                    .into(),
+                                slider(
+                                    MIN_WHITE_BALANCE..=MAX_WHITE_BALANCE,
+                                    self.settings.white_balance,
+                                    SetupMessage::WhiteBalanceChanged,
+                                )
+                                .step(100u32)
+                                .width(200)
+                                .into(),
+                            ])
+                            .align_y(Alignment::Center)
+                            .spacing(8)
+                            .into(),
+                        ])
+                        .align_x(Alignment::Center)
+                        .spacing(8)
+                        .into()
+                    } else {
+                        Space::new(0, 0).into()
+                    },
+                    self.advanced_view(),
+                    pick_list(
+                        self.templates.as_slice(),
+                        Some(&self.template),
+                        SetupMessage::TemplateSelected,
+                    )
+                    .into(),
+                    text_input(
+                        "Event name, like CAJ Winter Festival 2025",
+                        &self.event_name,
+                    )
+                    .on_input(SetupMessage::EventNameChanged)
+                    .width(400)
+                    .into(),
+                    match (&self.open_error, &self.camera_option) {
+                        (Some(failure), Some(camera)) => open_failure_card::<C>(camera, failure),
+                        _ => Space::new(0, 0).into(),
+                    },
+                    match &self.preview {
+                        Some(feed) => feed
+                            .view()
+                            .width(PREVIEW_WIDTH)
+                            .content_fit(ContentFit::Contain)
+                            .into(),
+                        None => Space::new(0, 0).into(),
+                    },
                     button("Start")
                         .on_press_maybe(
                             self.camera_option
@@ -94,4 +1003,219 @@ impl<
         .center_y(Length::Fill)
         .into()
     }
+
+    /// The camera's own controls, hidden unless opened.
+    fn advanced_view(&self) -> Element<SetupMessage<C>> {
+        if self.controls.is_empty() {
+            return Space::new(0, 0).into();
+        }
+        let toggle = button(if self.advanced_open {
+            "Advanced ▾"
+        } else {
+            "Advanced ▸"
+        })
+        .style(button::text)
+        .on_press(SetupMessage::AdvancedToggled);
+        if !self.advanced_open {
+            return toggle.into();
+        }
+        column(
+            std::iter::once(Element::from(toggle)).chain(self.controls.iter().map(|control| {
+                let id = control.id;
+                let value = self
+                    .control_values
+                    .iter()
+                    .find(|(set, _)| *set == id)
+                    .map_or(control.value, |(_, value)| *value);
+                row([
+                    text(format!("{} {}", id, value)).into(),
+                    slider(control.min..=control.max, value, move |value| {
+                        SetupMessage::ControlChanged(id, value)
+                    })
+                    .step(control.step)
+                    .width(200)
+                    .into(),
+                ])
+                .align_y(Alignment::Center)
+                .spacing(8)
+                .into()
+            })),
+        )
+        .align_x(Alignment::Center)
+        .spacing(8)
+        .into()
+    }
+}
+
+/// Whether `AUTO_START` is set to something truthy.
+fn auto_start_enabled() -> bool {
+    std::env::var(AUTO_START_VAR)
+        .is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"))
+}
+
+fn error_card<'a, Message: 'a>(message: String) -> Element<'a, Message> {
+    error_container(text(message).size(16))
+}
+
+fn error_container<'a, Message: 'a>(
+    content: impl Into<Element<'a, Message>>,
+) -> Element<'a, Message> {
+    container(content)
+        .style(|theme: &iced::Theme| container::Style {
+            border: iced::Border::default()
+                .rounded(4.0)
+                .color(theme.extended_palette().danger.strong.color)
+                .width(1.0),
+            background: Some(theme.extended_palette().danger.weak.color.into()),
+            text_color: Some(theme.extended_palette().danger.weak.text),
+            ..Default::default()
+        })
+        .padding(8)
+        .max_width(600)
+        .into()
+}
+
+/// Explains why `camera` couldn't be opened and how to fix it, with a button
+/// to try again once it's fixed.
+fn open_failure_card<'a, C: crate::backend::cameras::CameraBackend + 'static>(
+    camera: &C::EnumeratedCamera,
+    failure: &CameraOpenFailure,
+) -> Element<'a, SetupMessage<C>> {
+    let help = match failure {
+        CameraOpenFailure::PermissionDenied => {
+            if cfg!(target_os = "macos") {
+                "Allow the photo booth in System Settings > Privacy & Security > Camera, then restart it."
+            } else if cfg!(target_os = "windows") {
+                "Turn on \"Let desktop apps access your camera\" in Settings > Privacy & security > Camera."
+            } else {
+                "Add this user to the video group (or plugdev for DSLRs), then log out and back in."
+            }
+        }
+        CameraOpenFailure::DeviceBusy => {
+            if cfg!(target_os = "linux") {
+                "Quit video call apps like Zoom or Teams, and unmount the camera if the file manager opened it."
+            } else {
+                "Quit video call apps like Zoom or Teams, or any other app showing the camera."
+            }
+        }
+        CameraOpenFailure::NotFound => {
+            "Check the cable and that the camera is turned on, then refresh the camera list."
+        }
+        CameraOpenFailure::Other(_) => "Try unplugging the camera and plugging it back in.",
+    };
+    error_container(
+        column([
+            text(format!("Couldn't open {}: {}.", camera, failure))
+                .size(16)
+                .into(),
+            text(help).size(16).into(),
+            button("Try again").on_press(SetupMessage::RetryOpen).into(),
+        ])
+        .spacing(8),
+    )
+}
+
+/// Opens `camera` and applies the camera-side settings chosen in setup.
+fn open_configured<C: crate::backend::cameras::CameraBackend + 'static>(
+    camera: C::EnumeratedCamera,
+    format: Option<CameraFormat>,
+    settings: CaptureSettings,
+    controls: &[(ControlId, ControlValue)],
+) -> Result<C::Camera, C::Error> {
+    let mut camera = C::open_camera(camera, format)?;
+    configure::<C>(&mut camera, settings, controls);
+    Ok(camera)
+}
+
+/// Applies the camera-side settings chosen in setup to an open camera.
+fn configure<C: crate::backend::cameras::CameraBackend + 'static>(
+    camera: &mut C::Camera,
+    settings: CaptureSettings,
+    controls: &[(ControlId, ControlValue)],
+) {
+    for &(id, value) in controls {
+        if let Err(err) = camera.set_control(id, value) {
+            log::warn!("Failed to set {}: {:?}", id, err);
+        }
+    }
+    camera.set_warmup_frames(settings.warmup_frames);
+    if settings.lock_exposure {
+        // an unsupported control shouldn't stop the booth from starting
+        if let Err(err) = camera.set_exposure(settings.exposure) {
+            log::warn!("Failed to lock exposure: {:?}", err);
+        }
+        if let Err(err) = camera.set_white_balance(settings.white_balance) {
+            log::warn!("Failed to lock white balance: {:?}", err);
+        }
+    }
+}
+
+/// Reopens `camera` the way it was opened in setup, for when it is unplugged
+/// and plugged back in.
+fn reopen<C: crate::backend::cameras::CameraBackend + 'static>(
+    camera: C::EnumeratedCamera,
+    format: Option<CameraFormat>,
+    settings: CaptureSettings,
+    controls: Vec<(ControlId, ControlValue)>,
+) -> impl FnMut() -> Result<C::Camera, String> + Send + 'static {
+    move || {
+        open_configured::<C>(camera.clone(), format, settings, &controls)
+            .map_err(|err| format!("{:?}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{
+        self, ScriptedCameraBackend, ScriptedCameraDescriptor, ScriptedServerBackend,
+    };
+
+    type TestSetup = Setup<ScriptedCameraBackend, ScriptedServerBackend>;
+
+    /// Presses Refresh and feeds the result back, checking the button is
+    /// disabled until it arrives.
+    async fn refresh(setup: &mut TestSetup) {
+        let task = setup.update(SetupMessage::RefreshCameras);
+        assert!(setup.refreshing);
+        for message in testing::run(task).await {
+            let _ = setup.update(message);
+        }
+        assert!(!setup.refreshing);
+    }
+
+    #[tokio::test]
+    async fn refresh_recovers_from_a_failed_enumeration() {
+        let mut setup = TestSetup::new(true);
+        assert_eq!(setup.camera_options, Ok(vec![ScriptedCameraDescriptor]));
+
+        ScriptedCameraBackend::script_enumerations([
+            Err("no permission".to_string()),
+            Ok(vec![ScriptedCameraDescriptor]),
+        ]);
+        refresh(&mut setup).await;
+        assert!(setup
+            .camera_options
+            .as_ref()
+            .is_err_and(|err| err.contains("no permission")));
+
+        refresh(&mut setup).await;
+        assert_eq!(setup.camera_options, Ok(vec![ScriptedCameraDescriptor]));
+    }
+
+    #[tokio::test]
+    async fn start_waits_for_the_preview_being_opened() {
+        let mut setup = TestSetup::new(true);
+        let opening = setup.update(SetupMessage::CameraSelected(ScriptedCameraDescriptor));
+        // the camera isn't opened a second time while the preview holds it
+        let start = setup.update(SetupMessage::StartPressed);
+        assert!(testing::run(start).await.is_empty());
+        assert!(setup.starting);
+
+        let mut opened = testing::run(opening).await;
+        assert_eq!(opened.len(), 1);
+        let started = testing::run(setup.update(opened.remove(0))).await;
+        assert!(setup.preview.is_none());
+        assert!(matches!(started[..], [SetupMessage::Started(_, None)]));
+    }
 }
@@ -0,0 +1,258 @@
+use std::time::Duration;
+
+use backend::{
+    cameras::{CameraBackend, DefaultCameraBackend},
+    queue,
+    servers::{DefaultServerBackend, ServerBackend},
+};
+use frontend::{
+    main_app::{MainApp, MainAppMessage},
+    setup::{Setup, SetupMessage},
+};
+use iced::{keyboard::Key, theme::Palette, Font, Task};
+
+mod backend;
+mod frontend;
+#[cfg(test)]
+mod testing;
+
+// what the benches drive directly
+pub use backend::{cameras, render_take};
+pub use frontend::camera_feed;
+
+/// How often sessions that couldn't be uploaded are tried again.
+const QUEUE_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+enum AppPage<
+    C: crate::backend::cameras::CameraBackend + 'static,
+    S: crate::backend::servers::ServerBackend + 'static,
+> {
+    Setup(Setup<C, S>),
+    MainApp(MainApp<C, S>),
+}
+
+struct PhotoBoothApplication<
+    C: crate::backend::cameras::CameraBackend + 'static,
+    S: crate::backend::servers::ServerBackend + 'static,
+> {
+    page: AppPage<C, S>,
+    server_backend: S,
+    /// Whether queued sessions are being uploaded right now.
+    flushing_queue: bool,
+}
+
+#[derive(Debug, Clone)]
+enum PhotoBoothMessage<
+    C: crate::backend::cameras::CameraBackend + 'static,
+    S: crate::backend::servers::ServerBackend + 'static,
+> {
+    Setup(SetupMessage<C>),
+    MainApp(MainAppMessage<S>),
+    Tick,
+    /// Time to try uploading the queued sessions again.
+    FlushQueue,
+    QueueFlushed(Result<usize, String>),
+    SpaceReleased,
+    EscapeReleased,
+    UpReleased,
+    DownReleased,
+    OtherKeyRelease,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum KeyMessage {
+    Space,
+    Up,
+    Down,
+    Escape,
+}
+
+impl<
+        C: crate::backend::cameras::CameraBackend + 'static + Clone,
+        S: crate::backend::servers::ServerBackend + 'static,
+    > PhotoBoothApplication<C, S>
+{
+    fn update(&mut self, message: PhotoBoothMessage<C, S>) -> Task<PhotoBoothMessage<C, S>> {
+        match message {
+            PhotoBoothMessage::Setup(msg) => match &mut self.page {
+                AppPage::Setup(page) => {
+                    let update_task = page.update(msg).map(PhotoBoothMessage::Setup);
+                    if let Some(new_page) = page.new_page.take() {
+                        let (new_page, new_task) = *new_page;
+                        self.page = new_page;
+                        update_task.chain(new_task)
+                    } else {
+                        update_task
+                    }
+                }
+                _ => Task::none(),
+            },
+            PhotoBoothMessage::MainApp(msg) => match &mut self.page {
+                AppPage::MainApp(page) => {
+                    let update_task = page
+                        .update(msg, self.server_backend.clone())
+                        .map(PhotoBoothMessage::MainApp);
+                    if let Some(new_page) = page.new_page.take() {
+                        let (new_page, new_task) = *new_page;
+                        self.page = new_page;
+                        update_task.chain(new_task)
+                    } else {
+                        update_task
+                    }
+                }
+                _ => Task::none(),
+            },
+            PhotoBoothMessage::Tick => match &mut self.page {
+                AppPage::MainApp(page) => page
+                    .update(MainAppMessage::Tick, self.server_backend.clone())
+                    .map(PhotoBoothMessage::MainApp),
+                AppPage::Setup(page) => page
+                    .update(SetupMessage::Tick)
+                    .map(PhotoBoothMessage::Setup),
+            },
+            PhotoBoothMessage::FlushQueue => {
+                if self.flushing_queue {
+                    return Task::none();
+                }
+                self.flushing_queue = true;
+                Task::perform(
+                    queue::flush(self.server_backend.clone()),
+                    PhotoBoothMessage::QueueFlushed,
+                )
+            }
+            PhotoBoothMessage::QueueFlushed(result) => {
+                self.flushing_queue = false;
+                match result {
+                    Ok(0) => {}
+                    Ok(sent) => log::info!("Uploaded {} queued sessions", sent),
+                    Err(err) => log::warn!("Still can't upload queued sessions: {}", err),
+                }
+                Task::none()
+            }
+            PhotoBoothMessage::SpaceReleased
+            | PhotoBoothMessage::DownReleased
+            | PhotoBoothMessage::UpReleased
+            | PhotoBoothMessage::EscapeReleased => match &mut self.page {
+                AppPage::MainApp(page) => page
+                    .update(
+                        MainAppMessage::KeyReleased(match message {
+                            PhotoBoothMessage::SpaceReleased => KeyMessage::Space,
+                            PhotoBoothMessage::DownReleased => KeyMessage::Down,
+                            PhotoBoothMessage::UpReleased => KeyMessage::Up,
+                            PhotoBoothMessage::EscapeReleased => KeyMessage::Escape,
+                            _ => unreachable!(),
+                        }),
+                        self.server_backend.clone(),
+                    )
+                    .map(PhotoBoothMessage::MainApp),
+                AppPage::Setup(page) => page
+                    .update(SetupMessage::KeyPressed)
+                    .map(PhotoBoothMessage::Setup),
+            },
+            PhotoBoothMessage::OtherKeyRelease => match &mut self.page {
+                AppPage::MainApp(page) => page
+                    .update(MainAppMessage::OtherKeyPress, self.server_backend.clone())
+                    .map(PhotoBoothMessage::MainApp),
+                AppPage::Setup(page) => page
+                    .update(SetupMessage::KeyPressed)
+                    .map(PhotoBoothMessage::Setup),
+            },
+        }
+    }
+
+    fn view(&self) -> iced::Element<PhotoBoothMessage<C, S>> {
+        match &self.page {
+            AppPage::MainApp(page) => page
+                .view(&self.server_backend)
+                .map(PhotoBoothMessage::MainApp),
+            AppPage::Setup(page) => page.view().map(PhotoBoothMessage::Setup),
+        }
+    }
+
+    fn subscription(&self) -> iced::Subscription<PhotoBoothMessage<C, S>> {
+        const FPS: f32 = 30.0;
+        iced::Subscription::batch([
+            match &self.page {
+                AppPage::MainApp(page) => page.subscription().map(PhotoBoothMessage::MainApp),
+                AppPage::Setup(page) => page.subscription().map(PhotoBoothMessage::Setup),
+            },
+            iced::time::every(Duration::from_secs_f32(1.0 / FPS))
+                .map(|_tick| PhotoBoothMessage::Tick),
+            iced::time::every(QUEUE_RETRY_INTERVAL).map(|_| PhotoBoothMessage::FlushQueue),
+            iced::keyboard::on_key_press(|key, _modifiers| match key {
+                Key::Named(iced::keyboard::key::Named::Space)
+                | Key::Named(iced::keyboard::key::Named::Enter) => {
+                    Some(PhotoBoothMessage::SpaceReleased)
+                }
+                Key::Named(iced::keyboard::key::Named::Escape) => {
+                    Some(PhotoBoothMessage::EscapeReleased)
+                }
+                Key::Named(iced::keyboard::key::Named::PageUp)
+                | Key::Named(iced::keyboard::key::Named::ArrowUp) => {
+                    Some(PhotoBoothMessage::UpReleased)
+                }
+                Key::Named(iced::keyboard::key::Named::PageDown)
+                | Key::Named(iced::keyboard::key::Named::ArrowDown) => {
+                    Some(PhotoBoothMessage::DownReleased)
+                }
+                _ => Some(PhotoBoothMessage::OtherKeyRelease),
+            }),
+        ])
+    }
+}
+
+/// Runs the booth until its window is closed.
+pub fn run() -> iced::Result {
+    // Set up logging
+    env_logger::init();
+    log::info!("Starting Photo Booth");
+
+    type CameraBackend = DefaultCameraBackend;
+    type ServerBackend = DefaultServerBackend;
+
+    CameraBackend::initialize().expect("failed to initialize camera backend");
+
+    // lets staff get back to the camera picker when a saved setup would
+    // otherwise start by itself
+    let force_setup = std::env::args().any(|arg| arg == "--force-setup");
+
+    iced::application(
+        "Photo Booth",
+        PhotoBoothApplication::update,
+        PhotoBoothApplication::view,
+    )
+    .font(include_bytes!(
+        "../assets/fonts/Noto_Color_Emoji/NotoColorEmoji-Regular.ttf"
+    ))
+    .font(include_bytes!(
+        "../assets/fonts/Montserrat/Montserrat-Regular.ttf"
+    ))
+    .default_font(Font::with_name("Montserrat"))
+    .theme(|_| {
+        iced::Theme::custom(
+            "CAJ".to_owned(),
+            Palette {
+                background: iced::Color::from_rgb8(0xbb, 0xbb, 0xdd),
+                text: iced::Color::from_rgb8(0xff, 0xff, 0xff),
+                primary: iced::Color::from_rgb8(0x01, 0x00, 0x80),
+                success: iced::Color::from_rgb8(0x00, 0xff, 0x00),
+                danger: iced::Color::from_rgb8(0xff, 0x00, 0x00),
+            },
+        )
+    })
+    .subscription(PhotoBoothApplication::subscription)
+    .run_with(move || {
+        let server_backend = ServerBackend::new().expect("failed to initialize server backend");
+        // nothing can be open yet, so anything still open was left over
+        queue::recover();
+        (
+            PhotoBoothApplication::<CameraBackend, ServerBackend> {
+                page: AppPage::Setup(Setup::new(force_setup)),
+                server_backend,
+                flushing_queue: false,
+            },
+            // whatever was left from last time goes first
+            Task::done(PhotoBoothMessage::FlushQueue),
+        )
+    })
+}
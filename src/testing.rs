@@ -0,0 +1,328 @@
+//! A camera and a server backend that do what a test tells them to, for
+//! driving the app through its states without hardware or a network.
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use iced::{futures::StreamExt, Task};
+use image::{Rgba, RgbaImage};
+
+use crate::backend::{
+    cameras::{CameraBackend, CameraBackendCamera, CameraFormat, VideoFrame},
+    servers::{EmailDelivery, Progress, ServerBackend},
+};
+
+/// Size of the frames scripted cameras take unless told otherwise. Small,
+/// so rendering strips from them stays quick.
+pub const FRAME_WIDTH: u32 = 300;
+pub const FRAME_HEIGHT: u32 = 200;
+
+/// A plain gray frame.
+pub fn frame(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_pixel(width, height, Rgba([128, 128, 128, 255]))
+}
+
+/// Runs `task` to the end and returns the messages it produced. Anything
+/// meant for the runtime itself, like focusing a widget, is dropped.
+pub async fn run<T: Send + 'static>(task: Task<T>) -> Vec<T> {
+    let Some(stream) = iced_runtime::task::into_stream(task) else {
+        return Vec::new();
+    };
+    stream
+        .filter_map(|action| async move {
+            match action {
+                iced_runtime::Action::Output(message) => Some(message),
+                _ => None,
+            }
+        })
+        .collect()
+        .await
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Debug, Default)]
+struct CameraScript {
+    /// Results for the next video captures, before going back to `frame`.
+    video: VecDeque<Result<RgbaImage, String>>,
+    stills: VecDeque<Result<RgbaImage, String>>,
+    video_captures: usize,
+    still_captures: usize,
+    disconnected: bool,
+    /// Whether video frames are sent JPEG encoded when they may be.
+    jpeg: bool,
+}
+
+/// A camera that takes gray frames, except where a test has scripted what
+/// its next captures return. Clones share the script, so a test can keep one
+/// to script and inspect the camera after handing another to a feed.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedCamera {
+    script: Arc<Mutex<CameraScript>>,
+}
+
+impl ScriptedCamera {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Has the next video captures fail with `err`, `times` times.
+    pub fn fail_video(&self, times: usize, err: &str) {
+        let mut script = lock(&self.script);
+        script
+            .video
+            .extend((0..times).map(|_| Err(err.to_string())));
+    }
+
+    /// Has the next stills fail with `err`, `times` times.
+    pub fn fail_stills(&self, times: usize, err: &str) {
+        let mut script = lock(&self.script);
+        script
+            .stills
+            .extend((0..times).map(|_| Err(err.to_string())));
+    }
+
+    /// Makes `is_connected` report the camera as unplugged, or back again.
+    pub fn set_connected(&self, connected: bool) {
+        lock(&self.script).disconnected = !connected;
+    }
+
+    /// Has video frames sent JPEG encoded, the way an MJPEG webcam sends
+    /// them, whenever the feed doesn't need their pixels.
+    pub fn send_jpeg(&self) {
+        lock(&self.script).jpeg = true;
+    }
+
+    /// Video captures so far, failed ones included.
+    pub fn video_captures(&self) -> usize {
+        lock(&self.script).video_captures
+    }
+
+    /// Still captures so far, failed ones included.
+    pub fn still_captures(&self) -> usize {
+        lock(&self.script).still_captures
+    }
+}
+
+impl CameraBackendCamera for ScriptedCamera {
+    type Error = String;
+
+    fn capture_video_frame(&mut self) -> Result<RgbaImage, String> {
+        let mut script = lock(&self.script);
+        script.video_captures += 1;
+        script
+            .video
+            .pop_front()
+            .unwrap_or_else(|| Ok(frame(FRAME_WIDTH, FRAME_HEIGHT)))
+    }
+
+    fn capture_video_frame_encoded(&mut self) -> Result<VideoFrame, String> {
+        let frame = self.capture_video_frame()?;
+        if !lock(&self.script).jpeg {
+            return Ok(VideoFrame::Rgba(frame));
+        }
+        let mut encoded = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut encoded)
+            .encode_image(&image::DynamicImage::ImageRgba8(frame).into_rgb8())
+            .map_err(|err| err.to_string())?;
+        Ok(VideoFrame::Jpeg(encoded.into()))
+    }
+
+    fn capture_still_frame(&mut self) -> Result<RgbaImage, String> {
+        let mut script = lock(&self.script);
+        script.still_captures += 1;
+        script
+            .stills
+            .pop_front()
+            .unwrap_or_else(|| Ok(frame(FRAME_WIDTH, FRAME_HEIGHT)))
+    }
+
+    fn is_connected(&mut self) -> bool {
+        !lock(&self.script).disconnected
+    }
+}
+
+/// Results for the next calls to `ScriptedCameraBackend::enumerate_cameras`.
+/// Shared by every test, since it's called without a backend to hold it, so
+/// only one test may script it.
+static ENUMERATIONS: Mutex<VecDeque<Result<Vec<ScriptedCameraDescriptor>, String>>> =
+    Mutex::new(VecDeque::new());
+
+/// Opens `ScriptedCamera`s. Lists a single camera unless a test scripted
+/// something else with `script_enumerations`.
+#[derive(Debug, Clone)]
+pub struct ScriptedCameraBackend;
+
+impl ScriptedCameraBackend {
+    /// Has the next calls to `enumerate_cameras` return `results` in order.
+    pub fn script_enumerations(
+        results: impl IntoIterator<Item = Result<Vec<ScriptedCameraDescriptor>, String>>,
+    ) {
+        lock(&ENUMERATIONS).extend(results);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedCameraDescriptor;
+
+impl Display for ScriptedCameraDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Scripted camera")
+    }
+}
+
+impl CameraBackend for ScriptedCameraBackend {
+    type Error = String;
+    type EnumeratedCamera = ScriptedCameraDescriptor;
+    type Camera = ScriptedCamera;
+
+    fn enumerate_cameras() -> Result<Vec<ScriptedCameraDescriptor>, String> {
+        lock(&ENUMERATIONS)
+            .pop_front()
+            .unwrap_or_else(|| Ok(vec![ScriptedCameraDescriptor]))
+    }
+
+    fn open_camera(
+        _item: ScriptedCameraDescriptor,
+        _format: Option<CameraFormat>,
+    ) -> Result<ScriptedCamera, String> {
+        Ok(ScriptedCamera::new())
+    }
+}
+
+/// A call made to a `ScriptedServerBackend`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerCall {
+    Upload {
+        photos: usize,
+        print: bool,
+        gif: bool,
+    },
+    Email {
+        handle: usize,
+        emails: Vec<String>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct ServerScript {
+    /// Errors for the next uploads, `None` for one that succeeds.
+    uploads: VecDeque<Option<String>>,
+    emails: VecDeque<Option<String>>,
+    calls: Vec<ServerCall>,
+    uploaded: usize,
+}
+
+/// Succeeds straight away at everything, except where a test has scripted a
+/// failure, and records every call. Calls are recorded when they're made,
+/// not when their future runs. Clones share the script.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedServerBackend {
+    script: Arc<Mutex<ServerScript>>,
+}
+
+impl ScriptedServerBackend {
+    /// Has the next uploads fail with `err`, `times` times.
+    pub fn fail_uploads(&self, times: usize, err: &str) {
+        let mut script = lock(&self.script);
+        script
+            .uploads
+            .extend((0..times).map(|_| Some(err.to_string())));
+    }
+
+    /// Has the next emails fail with `err`, `times` times.
+    pub fn fail_emails(&self, times: usize, err: &str) {
+        let mut script = lock(&self.script);
+        script
+            .emails
+            .extend((0..times).map(|_| Some(err.to_string())));
+    }
+
+    /// Every call so far, oldest first.
+    pub fn calls(&self) -> Vec<ServerCall> {
+        lock(&self.script).calls.clone()
+    }
+}
+
+impl ServerBackend for ScriptedServerBackend {
+    type Error = String;
+    /// Which successful upload this was, counting from 1.
+    type UploadHandle = usize;
+
+    fn new() -> Result<Self, String> {
+        Ok(Self::default())
+    }
+
+    fn upload_photo(
+        self,
+        _strip: RgbaImage,
+        print: Option<Vec<u8>>,
+        photos: Vec<RgbaImage>,
+        gif: Option<Vec<u8>>,
+        progress: Progress,
+    ) -> impl Future<Output = Result<usize, String>> + Send {
+        let mut script = lock(&self.script);
+        script.calls.push(ServerCall::Upload {
+            photos: photos.len(),
+            print: print.is_some(),
+            gif: gif.is_some(),
+        });
+        let result = match script.uploads.pop_front().flatten() {
+            Some(err) => Err(err),
+            None => {
+                script.uploaded += 1;
+                Ok(script.uploaded)
+            }
+        };
+        // one step per file, like the real backends
+        let steps = 1 + usize::from(print.is_some()) + usize::from(gif.is_some()) + photos.len();
+        async move {
+            if result.is_ok() {
+                progress.set_steps(steps);
+                for _ in 0..steps {
+                    progress.step_done();
+                }
+            }
+            result
+        }
+    }
+
+    fn send_email(
+        self,
+        handle: usize,
+        emails: Vec<String>,
+        progress: Progress,
+    ) -> impl Future<Output = Result<Vec<EmailDelivery>, String>> + Send {
+        let mut script = lock(&self.script);
+        script.calls.push(ServerCall::Email {
+            handle,
+            emails: emails.clone(),
+        });
+        let result = match script.emails.pop_front().flatten() {
+            Some(err) => Err(err),
+            None => Ok(emails
+                .into_iter()
+                .map(|address| EmailDelivery {
+                    address,
+                    delivered: true,
+                })
+                .collect()),
+        };
+        async move {
+            if result.is_ok() {
+                progress.step_done();
+            }
+            result
+        }
+    }
+
+    fn get_link(self, handle: usize) -> String {
+        format!("https://example.com/photo-booth/{}", handle)
+    }
+}